@@ -0,0 +1,45 @@
+#!/usr/bin/env python3
+"""Reference bot for the game's stdin/stdout protocol, see "Bots" in the README.
+
+It moves its paddle toward the ball while the ball comes at it and back to
+the middle otherwise. Copy it to start your own bot in any language: read a
+line, print a line, and flush.
+"""
+
+import sys
+
+# How far off the paddle center the ball may be before it moves.
+DEAD_ZONE = 10.0
+
+
+def main():
+    greeting = sys.stdin.readline().split()
+    if greeting[:2] != ["pong", "1"]:
+        print("unsupported protocol: " + " ".join(greeting), file=sys.stderr)
+        return
+
+    side, height = greeting[2], float(greeting[4])
+    paddle = 0 if side == "left" else 1
+    print("ready", flush=True)
+
+    for line in sys.stdin:
+        words = line.split()
+        if words[0] == "end":
+            break
+
+        ball_y, ball_dx = float(words[3]), float(words[4])
+        paddle_y = float(words[6 + paddle])
+
+        coming = ball_dx < 0 if paddle == 0 else ball_dx > 0
+        target = ball_y if coming else height / 2
+
+        if target < paddle_y - DEAD_ZONE:
+            print("UP", flush=True)
+        elif target > paddle_y + DEAD_ZONE:
+            print("DOWN", flush=True)
+        else:
+            print("STAY", flush=True)
+
+
+if __name__ == "__main__":
+    main()
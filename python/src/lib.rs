@@ -0,0 +1,267 @@
+// pyo3's macros convert `PyErr` into itself in every generated wrapper.
+#![allow(clippy::useless_conversion)]
+
+use ggezgame::{
+    arena::ArenaEvent,
+    env::{self, Action, EnvConfig, EnvState, Replay, FEATURE_NAMES, OBSERVATION_SIZE},
+    mlp::{self, Activation, Layer},
+};
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyDict, PyList},
+};
+
+// (observation, (left reward, right reward), done, info)
+type StepResult<'py> = (Vec<f32>, (f32, f32), bool, Bound<'py, PyDict>);
+
+// (weights with a row per output, biases, activation name)
+type LayerSpec = (Vec<Vec<f32>>, Vec<f32>, String);
+
+// Names of the actions, by index.
+const ACTION_NAMES: [&str; 3] = ["stay", "up", "down"];
+
+// An action given as its index in `ACTION_NAMES` or as its name.
+fn action(value: &Bound<'_, PyAny>) -> PyResult<Action> {
+    let action = match value.extract::<usize>() {
+        Ok(index) => Action::from_index(index),
+        Err(_) => Action::from_name(&value.extract::<String>()?.to_lowercase()),
+    };
+
+    action.ok_or_else(|| PyValueError::new_err(format!("unknown action {}", value)))
+}
+
+// Sets the `EnvConfig` field called `key`.
+fn set_option(config: &mut EnvConfig, key: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    let tuning = &mut config.tuning;
+
+    match key {
+        "width" => config.width = value.extract()?,
+        "height" => config.height = value.extract()?,
+        "dt" => config.dt = value.extract()?,
+        "max_steps" => config.max_steps = value.extract()?,
+        "goal_reward" => config.rewards.goal = value.extract()?,
+        "hit_bonus" => config.rewards.hit_bonus = value.extract()?,
+        "ball_radius" => tuning.ball_radius = value.extract()?,
+        "ball_speed" => tuning.ball_speed = value.extract()?,
+        "paddle_width" => tuning.paddle_width = value.extract()?,
+        "paddle_height" => tuning.paddle_height = value.extract()?,
+        "paddle_speed" => tuning.paddle_speed = value.extract()?,
+        "winning_score" => tuning.winning_score = value.extract()?,
+        "bounce_angle" => tuning.bounce_angle = value.extract()?,
+        _ => return Err(PyKeyError::new_err(format!("unknown option {}", key))),
+    }
+
+    Ok(())
+}
+
+fn event_dict<'py>(py: Python<'py>, event: &ArenaEvent) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+
+    match *event {
+        ArenaEvent::Goal { scorer } => {
+            dict.set_item("type", "goal")?;
+            dict.set_item("scorer", scorer)?;
+        }
+        ArenaEvent::PaddleHit { paddle } => {
+            dict.set_item("type", "paddle_hit")?;
+            dict.set_item("paddle", paddle)?;
+        }
+        ArenaEvent::WallBounce => dict.set_item("type", "wall_bounce")?,
+    }
+
+    Ok(dict)
+}
+
+fn json_error(e: serde_json::Error) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+// The game rules without a window, see `env.rs` in the game. Options are the
+// `EnvConfig` and `Tuning` fields, e.g. `Env(max_steps=1000, hit_bonus=0.1)`.
+#[pyclass]
+struct Env {
+    env: ggezgame::env::Env,
+}
+
+#[pymethods]
+impl Env {
+    #[new]
+    #[pyo3(signature = (**options))]
+    fn new(options: Option<&Bound<'_, PyDict>>) -> PyResult<Env> {
+        let mut config = EnvConfig::default();
+
+        if let Some(options) = options {
+            for (key, value) in options.iter() {
+                set_option(&mut config, &key.extract::<String>()?, &value)?;
+            }
+        }
+
+        Ok(Env {
+            env: ggezgame::env::Env::new(config),
+        })
+    }
+
+    #[pyo3(signature = (seed=0))]
+    fn reset(&mut self, seed: u64) -> Vec<f32> {
+        self.env.reset(seed).to_vec()
+    }
+
+    // `actions` is a (left, right) pair.
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        actions: (Bound<'py, PyAny>, Bound<'py, PyAny>),
+    ) -> PyResult<StepResult<'py>> {
+        let actions = [action(&actions.0)?, action(&actions.1)?];
+        let (observation, rewards, done, step) = self.env.step(actions);
+
+        let info = PyDict::new_bound(py);
+        info.set_item("steps", step.steps)?;
+        info.set_item("truncated", step.truncated)?;
+        info.set_item("scorer", step.scorer)?;
+        info.set_item("hits", (step.hits[0], step.hits[1]))?;
+        info.set_item("wall_bounces", step.wall_bounces)?;
+        info.set_item("events", self.events(py)?)?;
+
+        Ok((observation.to_vec(), (rewards[0], rewards[1]), done, info))
+    }
+
+    #[getter]
+    fn observation(&self) -> Vec<f32> {
+        self.env.observe().to_vec()
+    }
+
+    #[getter]
+    fn score(&self) -> (usize, usize) {
+        (self.env.arena.player1_score, self.env.arena.player2_score)
+    }
+
+    // What happened during the last step, as dicts with a "type".
+    fn events<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let events = self
+            .env
+            .events()
+            .iter()
+            .map(|event| event_dict(py, event))
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(PyList::new_bound(py, events))
+    }
+
+    // The whole match as JSON, `load_state` continues exactly from it.
+    fn save_state(&self) -> PyResult<String> {
+        serde_json::to_string(&self.env.state()).map_err(json_error)
+    }
+
+    fn load_state(&mut self, state: &str) -> PyResult<()> {
+        let state: EnvState = serde_json::from_str(state).map_err(json_error)?;
+        self.env.restore(&state);
+        Ok(())
+    }
+
+    // The episode so far as JSON, for `play_replay`.
+    fn replay(&self) -> PyResult<String> {
+        serde_json::to_string(self.env.replay()).map_err(json_error)
+    }
+}
+
+// A network in the game's weights format, for exporting trained agents, see
+// `mlp.rs` in the game. Layers are (weights, biases, activation) with a row of
+// weights per output, like PyTorch's `Linear.weight.tolist()`.
+#[pyclass]
+struct Mlp {
+    mlp: mlp::Mlp,
+}
+
+#[pymethods]
+impl Mlp {
+    #[new]
+    fn new(layers: Vec<LayerSpec>) -> PyResult<Mlp> {
+        let inputs = layers
+            .first()
+            .and_then(|(weights, _, _)| weights.first())
+            .map_or(0, Vec::len);
+
+        let layers = layers
+            .into_iter()
+            .map(|(weights, biases, activation)| {
+                let activation = Activation::from_name(&activation).ok_or_else(|| {
+                    PyValueError::new_err(format!("unknown activation {}", activation))
+                })?;
+                if weights.len() != biases.len() {
+                    return Err(PyValueError::new_err("need a row of weights per bias"));
+                }
+
+                Ok(Layer {
+                    weights: weights.concat(),
+                    biases,
+                    activation,
+                })
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let mlp = mlp::Mlp::new(inputs, layers).map_err(PyValueError::new_err)?;
+        Ok(Mlp { mlp })
+    }
+
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Mlp> {
+        let mlp = mlp::Mlp::load(path.as_ref()).map_err(PyValueError::new_err)?;
+        Ok(Mlp { mlp })
+    }
+
+    // Writes the weights file, put it in `resources/agents` to play it.
+    fn save(&self, path: &str) -> PyResult<()> {
+        self.mlp.save(path.as_ref()).map_err(PyValueError::new_err)
+    }
+
+    fn forward(&self, inputs: Vec<f32>) -> PyResult<Vec<f32>> {
+        if inputs.len() != self.mlp.inputs {
+            return Err(PyValueError::new_err(format!(
+                "expected {} inputs, got {}",
+                self.mlp.inputs,
+                inputs.len()
+            )));
+        }
+
+        Ok(self.mlp.evaluate(&inputs))
+    }
+
+    // The index in `ACTIONS` the game would pick for these outputs.
+    fn act(&self, observation: Vec<f32>) -> PyResult<usize> {
+        let action = mlp::best_action(&self.forward(observation)?);
+        Ok(Action::ALL.iter().position(|a| *a == action).unwrap_or(0))
+    }
+
+    fn __str__(&self) -> String {
+        self.mlp.to_text()
+    }
+}
+
+// The observation as the right player sees it when playing on the left. The
+// game does this for networks playing the right paddle.
+#[pyfunction]
+fn mirror(observation: [f32; OBSERVATION_SIZE]) -> Vec<f32> {
+    env::mirror(&observation).to_vec()
+}
+
+// Plays a replay from `Env.replay()` and returns the environment at its end.
+#[pyfunction]
+fn play_replay(replay: &str) -> PyResult<Env> {
+    let replay: Replay = serde_json::from_str(replay).map_err(json_error)?;
+
+    Ok(Env { env: replay.play() })
+}
+
+#[pymodule]
+fn pong_sim(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Env>()?;
+    m.add_class::<Mlp>()?;
+    m.add_function(wrap_pyfunction!(play_replay, m)?)?;
+    m.add_function(wrap_pyfunction!(mirror, m)?)?;
+    m.add("FEATURE_NAMES", FEATURE_NAMES.to_vec())?;
+    m.add("ACTIONS", ACTION_NAMES.to_vec())?;
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+"""Smoke tests for the pong_sim bindings.
+
+Run with pytest once the module is installed (`maturin develop`), or offline
+after `cargo build -p pong-sim` with `python3 python/tests/test_smoke.py`,
+which loads the library straight from `target/`.
+"""
+
+import importlib.machinery
+import importlib.util
+import json
+import os
+import sys
+import tempfile
+
+
+def _load():
+    try:
+        import pong_sim
+
+        return pong_sim
+    except ImportError:
+        pass
+
+    root = os.path.join(os.path.dirname(__file__), "..", "..", "target")
+    for profile in ("release", "debug"):
+        path = os.path.join(root, profile, "libpong_sim.so")
+        if os.path.exists(path):
+            loader = importlib.machinery.ExtensionFileLoader("pong_sim", path)
+            spec = importlib.util.spec_from_loader("pong_sim", loader)
+            module = importlib.util.module_from_spec(spec)
+            loader.exec_module(module)
+            return module
+
+    raise ImportError("pong_sim is not installed, run `cargo build -p pong-sim` first")
+
+
+pong_sim = _load()
+
+
+def follow_ball(observation, paddle):
+    offset = observation[1] - observation[4 + paddle]
+    if offset < -0.02:
+        return "up"
+    if offset > 0.02:
+        return "down"
+    return "stay"
+
+
+def test_reset_gives_an_observation():
+    env = pong_sim.Env()
+    observation = env.reset(1)
+
+    assert len(observation) == len(pong_sim.FEATURE_NAMES)
+    assert observation[0] == 0.5
+    assert env.score == (0, 0)
+
+
+def test_steps_report_rewards_and_events():
+    env = pong_sim.Env(max_steps=20000, hit_bonus=0.5)
+    observation = env.reset(2)
+    events = []
+    total = [0.0, 0.0]
+
+    done = False
+    while not done:
+        observation, rewards, done, info = env.step((follow_ball(observation, 0), 0))
+        total[0] += rewards[0]
+        total[1] += rewards[1]
+        events.extend(info["events"])
+
+    goals = [event for event in events if event["type"] == "goal"]
+    assert goals
+    assert sum(env.score) == len(goals)
+    assert any(event["type"] == "paddle_hit" for event in events)
+    assert total[1] <= -env.score[0] + 0.5 * len(events)
+
+
+def test_saved_states_continue_the_same_way():
+    env = pong_sim.Env()
+    observation = env.reset(3)
+    for _ in range(500):
+        observation = env.step(("down", "up"))[0]
+
+    state = env.save_state()
+    json.loads(state)
+    copy = pong_sim.Env()
+    copy.load_state(state)
+
+    for _ in range(1000):
+        actions = (follow_ball(observation, 0), "stay")
+        observation = env.step(actions)[0]
+        assert copy.step(actions)[0] == observation
+
+
+def test_replays_end_where_the_episode_did():
+    env = pong_sim.Env()
+    observation = env.reset(4)
+    for _ in range(1500):
+        observation = env.step((follow_ball(observation, 0), 2))[0]
+
+    replayed = pong_sim.play_replay(env.replay())
+    assert replayed.observation == observation
+
+
+def test_exported_networks_play_in_the_game():
+    # The shipped follower network, as it would come out of training.
+    layers = [
+        ([[0, -1, 0, 0, 1, 0, 0, 0], [0, 1, 0, 0, -1, 0, 0, 0]], [0, 0], "relu"),
+        ([[0, 0], [1, 0], [0, 1]], [0.02, 0, 0], "linear"),
+    ]
+    path = os.path.join(tempfile.mkdtemp(), "follower.mlp")
+    pong_sim.Mlp(layers).save(path)
+    mlp = pong_sim.Mlp.load(path)
+
+    env = pong_sim.Env()
+    observation = env.reset(5)
+    for _ in range(1000):
+        actions = (mlp.act(observation), mlp.act(pong_sim.mirror(observation)))
+        assert pong_sim.ACTIONS[actions[0]] == follow_ball(observation, 0)
+        assert pong_sim.ACTIONS[actions[1]] == follow_ball(observation, 1)
+        observation = env.step(actions)[0]
+
+    try:
+        pong_sim.Mlp([([[1, 2]], [0, 0], "relu")])
+    except ValueError:
+        return
+    raise AssertionError("expected an error")
+
+
+def test_bad_input_raises():
+    env = pong_sim.Env()
+    env.reset()
+
+    for call in (lambda: env.step(("sideways", 0)), lambda: pong_sim.Env(gravity=1)):
+        try:
+            call()
+        except (ValueError, KeyError):
+            continue
+        raise AssertionError("expected an error")
+
+
+if __name__ == "__main__":
+    tests = [value for name, value in sorted(globals().items()) if name.startswith("test_")]
+    for test in tests:
+        test()
+        print("ok", test.__name__)
+    print("{} passed".format(len(tests)))
+    sys.exit(0)
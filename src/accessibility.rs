@@ -0,0 +1,278 @@
+use ggez::graphics::{self, Color};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Tuning, game_state::GameState, theme::Theme};
+
+// UI text sizes offered by the Accessibility menu.
+pub const TEXT_SCALES: [f32; 3] = [1.0, 1.25, 1.5];
+
+// How much bigger the ball and paddles are with `large_objects` on.
+pub const LARGE_OBJECT_SCALE: f32 = 1.5;
+
+// Particle colors that stay apart for common kinds of color blindness,
+// taken from the Okabe-Ito palette.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticlePalette {
+    // Whatever the theme uses.
+    Theme,
+    // Deuteranopia and protanopia.
+    RedGreen,
+    // Tritanopia.
+    BlueYellow,
+    Monochrome,
+}
+
+impl ParticlePalette {
+    pub const ALL: [ParticlePalette; 4] = [
+        ParticlePalette::Theme,
+        ParticlePalette::RedGreen,
+        ParticlePalette::BlueYellow,
+        ParticlePalette::Monochrome,
+    ];
+
+    // Locale key of the name shown in the menu.
+    pub fn label(self) -> &'static str {
+        match self {
+            ParticlePalette::Theme => "palette-theme",
+            ParticlePalette::RedGreen => "palette-red-green",
+            ParticlePalette::BlueYellow => "palette-blue-yellow",
+            ParticlePalette::Monochrome => "palette-monochrome",
+        }
+    }
+
+    // Indexed by `ParticleType` like a theme's `particle_colors`, `None`
+    // keeps the theme's own.
+    pub fn colors(self) -> Option<[Color; 3]> {
+        let rgb = match self {
+            ParticlePalette::Theme => return None,
+            ParticlePalette::RedGreen => [(86, 180, 233), (230, 159, 0), (0, 114, 178)],
+            ParticlePalette::BlueYellow => [(0, 158, 115), (213, 94, 0), (204, 121, 167)],
+            ParticlePalette::Monochrome => [(255, 255, 255), (170, 170, 170), (100, 100, 100)],
+        };
+
+        Some(rgb.map(|(r, g, b)| Color::from_rgb(r, g, b)))
+    }
+}
+
+// The `[accessibility]` table of the config file.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Accessibility {
+    pub high_contrast: bool,
+    pub particle_palette: ParticlePalette,
+    // No particles, no shimmer and no fades between screens.
+    pub reduced_motion: bool,
+    // Multiplies every UI text size.
+    pub text_scale: f32,
+    pub large_objects: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Accessibility {
+        Accessibility {
+            high_contrast: false,
+            particle_palette: ParticlePalette::Theme,
+            reduced_motion: false,
+            text_scale: 1.0,
+            large_objects: false,
+        }
+    }
+}
+
+// Colors of UI text and the shade drawn under overlay menus.
+#[derive(Clone, Copy)]
+pub struct UiColors {
+    pub text: Color,
+    pub dim_text: Color,
+    pub highlight: Color,
+    pub shade: Color,
+}
+
+impl Accessibility {
+    // Ball and paddle sizes grow, speeds stay the same.
+    pub fn resize(&self, tuning: Tuning) -> Tuning {
+        if !self.large_objects {
+            return tuning;
+        }
+
+        Tuning {
+            ball_radius: tuning.ball_radius * LARGE_OBJECT_SCALE,
+            paddle_width: tuning.paddle_width * LARGE_OBJECT_SCALE,
+            paddle_height: tuning.paddle_height * LARGE_OBJECT_SCALE,
+            ..tuning
+        }
+    }
+
+    // Index into `TEXT_SCALES` closest to `text_scale`, the config file can
+    // hold any value.
+    pub fn text_scale_index(&self) -> usize {
+        let distance = |scale: &f32| (scale - self.text_scale).abs();
+
+        (0..TEXT_SCALES.len())
+            .min_by(|a, b| distance(&TEXT_SCALES[*a]).total_cmp(&distance(&TEXT_SCALES[*b])))
+            .unwrap_or(0)
+    }
+
+    // High contrast replaces the theme's faint text with opaque white and
+    // yellow, and makes overlay menus hide the game completely.
+    pub fn ui_colors(&self, theme: &Theme) -> UiColors {
+        if self.high_contrast {
+            return UiColors {
+                text: graphics::WHITE,
+                dim_text: graphics::WHITE,
+                highlight: Color::from_rgb(255, 230, 0),
+                shade: graphics::BLACK,
+            };
+        }
+
+        let mut shade = theme.background;
+        shade.a = 0.85;
+
+        UiColors {
+            text: theme.text,
+            dim_text: theme.dim_text,
+            highlight: theme.highlight,
+            shade,
+        }
+    }
+}
+
+impl GameState {
+    pub fn ui_colors(&self) -> UiColors {
+        self.config.accessibility.ui_colors(self.theme())
+    }
+
+    pub fn particles_visible(&self) -> bool {
+        self.show_particles && !self.config.accessibility.reduced_motion
+    }
+
+    pub fn toggle_high_contrast(&mut self) {
+        let accessibility = &mut self.config.accessibility;
+        accessibility.high_contrast = !accessibility.high_contrast;
+        self.save_config();
+    }
+
+    pub fn toggle_reduced_motion(&mut self) {
+        let accessibility = &mut self.config.accessibility;
+        accessibility.reduced_motion = !accessibility.reduced_motion;
+        self.stop_particles();
+        self.save_config();
+    }
+
+    // `index` into `ParticlePalette::ALL`.
+    pub fn set_particle_palette(&mut self, index: usize) {
+        if let Some(palette) = ParticlePalette::ALL.get(index) {
+            self.config.accessibility.particle_palette = *palette;
+            self.save_config();
+        }
+    }
+
+    // `index` into `TEXT_SCALES`.
+    pub fn set_text_scale(&mut self, index: usize) {
+        if let Some(scale) = TEXT_SCALES.get(index) {
+            self.config.accessibility.text_scale = *scale;
+            self.save_config();
+        }
+    }
+
+    pub fn toggle_large_objects(&mut self) {
+        let accessibility = &mut self.config.accessibility;
+        accessibility.large_objects = !accessibility.large_objects;
+        self.apply_tuning();
+        self.save_config();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // WCAG relative luminance and contrast ratio.
+    fn luminance(color: Color) -> f32 {
+        let channel = |c: f32| {
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+
+        0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+    }
+
+    fn contrast(a: Color, b: Color) -> f32 {
+        let (a, b) = (luminance(a), luminance(b));
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    #[test]
+    fn high_contrast_text_is_opaque_and_readable() {
+        let accessibility = Accessibility {
+            high_contrast: true,
+            ..Accessibility::default()
+        };
+
+        for theme in [Theme::classic(), Theme::neon()].iter() {
+            let colors = accessibility.ui_colors(theme);
+
+            for color in [colors.text, colors.dim_text, colors.highlight].iter() {
+                assert_eq!(color.a, 1.0);
+                assert!(contrast(*color, colors.shade) >= 7.0);
+            }
+        }
+    }
+
+    #[test]
+    fn themes_are_kept_without_high_contrast() {
+        let theme = Theme::classic();
+        let colors = Accessibility::default().ui_colors(&theme);
+
+        assert_eq!(colors.dim_text, theme.dim_text);
+        assert_eq!(colors.highlight, theme.highlight);
+    }
+
+    #[test]
+    fn palettes_have_a_distinct_color_per_particle_type() {
+        assert!(ParticlePalette::Theme.colors().is_none());
+
+        for palette in ParticlePalette::ALL.iter().skip(1) {
+            let colors = palette.colors().unwrap();
+
+            for (i, a) in colors.iter().enumerate() {
+                for b in colors.iter().skip(i + 1) {
+                    let distance =
+                        ((a.r - b.r).powi(2) + (a.g - b.g).powi(2) + (a.b - b.b).powi(2)).sqrt();
+                    assert!(distance > 0.3, "{:?} has similar colors", palette);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn large_objects_only_change_sizes() {
+        let tuning = Tuning::default();
+        let accessibility = Accessibility {
+            large_objects: true,
+            ..Accessibility::default()
+        };
+        let resized = accessibility.resize(tuning);
+
+        assert_eq!(resized.ball_radius, tuning.ball_radius * LARGE_OBJECT_SCALE);
+        assert_eq!(
+            resized.paddle_height,
+            tuning.paddle_height * LARGE_OBJECT_SCALE
+        );
+        assert_eq!(resized.ball_speed, tuning.ball_speed);
+        assert_eq!(resized.paddle_speed, tuning.paddle_speed);
+    }
+
+    #[test]
+    fn text_scale_snaps_to_the_closest_option() {
+        let mut accessibility = Accessibility::default();
+        assert_eq!(accessibility.text_scale_index(), 0);
+
+        accessibility.text_scale = 1.4;
+        assert_eq!(accessibility.text_scale_index(), 2);
+    }
+}
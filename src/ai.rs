@@ -0,0 +1,324 @@
+use std::collections::VecDeque;
+
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{Arena, ArenaRng, Side},
+    ball::Ball,
+    env::Action,
+};
+
+// How an `Ai` plays.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Personality {
+    // Seconds between the ball moving and the AI seeing it.
+    pub reaction_time: f32,
+    // Standard deviation of where it thinks the ball will arrive, in arena
+    // units. Picked again for every incoming ball.
+    pub error: f32,
+    // 0.0 to 1.0, how steep a return it aims for. Only matters with
+    // `Tuning::bounce_angle`, and steep returns are hit closer to the edge.
+    pub aggression: f32,
+    // 0.0 to 1.0, how far back toward the middle it moves while the ball
+    // goes away.
+    pub centering: f32,
+}
+
+impl Default for Personality {
+    fn default() -> Personality {
+        Personality {
+            reaction_time: 0.15,
+            error: 20.0,
+            aggression: 0.5,
+            centering: 0.8,
+        }
+    }
+}
+
+// Named personalities for the Controls menu and tournaments.
+pub const PRESETS: [(&str, Personality); 4] = [
+    (
+        "rookie",
+        Personality {
+            reaction_time: 0.35,
+            error: 70.0,
+            aggression: 0.1,
+            centering: 0.3,
+        },
+    ),
+    (
+        "steady",
+        Personality {
+            reaction_time: 0.15,
+            error: 20.0,
+            aggression: 0.3,
+            centering: 1.0,
+        },
+    ),
+    (
+        "sniper",
+        Personality {
+            reaction_time: 0.1,
+            error: 8.0,
+            aggression: 0.9,
+            centering: 0.8,
+        },
+    ),
+    (
+        "wall",
+        Personality {
+            reaction_time: 0.05,
+            error: 4.0,
+            aggression: 0.0,
+            centering: 1.0,
+        },
+    ),
+];
+
+pub fn preset(name: &str) -> Option<Personality> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| *preset == name)
+        .map(|(_, personality)| *personality)
+}
+
+// Furthest from the center it tries to hit the ball, as a fraction of half
+// the paddle, so aiming doesn't make it miss.
+const MAX_AIM_OFFSET: f32 = 0.8;
+
+// Plays one paddle by predicting where the ball crosses it and placing the
+// paddle so the hit sends the ball back at the angle it wants.
+#[derive(Clone)]
+pub struct Ai {
+    pub personality: Personality,
+    paddle: usize,
+    rng: ArenaRng,
+    // Time since the AI started, and what it saw of the ball then.
+    clock: f64,
+    seen: VecDeque<(f64, Ball)>,
+    // Where the paddle's center should go.
+    target: Option<f32>,
+    // Whether the ball came toward the paddle last time it looked.
+    incoming: bool,
+    // Error for the current incoming ball.
+    miss: f32,
+}
+
+impl Ai {
+    pub fn new(personality: Personality, paddle: usize, seed: u64) -> Ai {
+        Ai {
+            personality,
+            paddle,
+            rng: ArenaRng::seed_from_u64(seed),
+            clock: 0.0,
+            seen: VecDeque::new(),
+            target: None,
+            incoming: false,
+            miss: 0.0,
+        }
+    }
+
+    // The move for the next `dt` seconds.
+    pub fn act(&mut self, arena: &Arena, dt: f64) -> Action {
+        self.clock += dt;
+        self.seen.push_back((self.clock, arena.ball.clone()));
+
+        // Forget everything older than what it is reacting to now.
+        let reaction = self.personality.reaction_time.max(0.0) as f64;
+        while self.seen.len() > 1 && self.clock - self.seen[1].0 >= reaction {
+            self.seen.pop_front();
+        }
+
+        let ball = self.seen[0].1.clone();
+        let paddle = &arena.paddles[self.paddle];
+        let center = paddle.rect.y + paddle.rect.h / 2.0;
+        let (top, bottom) = wall_lines(arena);
+
+        let incoming = arena.paused.is_none() && (ball.dx < 0.0) == (self.paddle == 0);
+        if incoming && !self.incoming {
+            self.miss = gaussian(&mut self.rng) * self.personality.error;
+        }
+        if !incoming && (self.incoming || self.target.is_none()) {
+            let middle = (top + bottom) / 2.0;
+            self.target = Some(center + (middle - center) * self.personality.centering);
+        }
+        self.incoming = incoming;
+
+        if incoming {
+            let face = match paddle.side {
+                Side::Left => paddle.rect.x + paddle.rect.w + ball.radius,
+                _ => paddle.rect.x - ball.radius,
+            };
+
+            if let Some((y, _)) = predict_crossing(&ball, face, top, bottom) {
+                let opponent = &arena.paddles[1 - self.paddle].rect;
+                let opponent = opponent.y + opponent.h / 2.0;
+                let offset = self.aim(y, opponent, arena);
+
+                self.target = Some(y + self.miss - offset * paddle.rect.h / 2.0);
+            }
+        }
+
+        // Moving less than a step's worth would only make it jitter.
+        let dead_zone = (paddle.dy as f64 * dt) as f32;
+        match self.target {
+            Some(target) if target < center - dead_zone => Action::Up,
+            Some(target) if target > center + dead_zone => Action::Down,
+            _ => Action::Stay,
+        }
+    }
+
+    // Where on the paddle to meet the ball arriving at `y`, -1.0 at the top
+    // edge and 1.0 at the bottom, to send it away from the opponent.
+    fn aim(&self, y: f32, opponent: f32, arena: &Arena) -> f32 {
+        if arena.tuning.bounce_angle <= 0.0 {
+            return 0.0;
+        }
+
+        // Send the ball to the half the opponent isn't in, or away from the
+        // nearer wall when it is in the middle.
+        let away = if (opponent - arena.height / 2.0).abs() > arena.tuning.paddle_height / 2.0 {
+            opponent < arena.height / 2.0
+        } else {
+            y < arena.height / 2.0
+        };
+        let direction = if away { 1.0 } else { -1.0 };
+
+        direction * self.personality.aggression.clamp(0.0, 1.0) * MAX_AIM_OFFSET
+    }
+}
+
+// Normally distributed with a standard deviation of 1.
+pub fn gaussian<R: Rng>(rng: &mut R) -> f32 {
+    let u: f32 = 1.0 - rng.gen::<f32>();
+    let v: f32 = rng.gen();
+
+    (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos()
+}
+
+// The y the ball's center can't go above and below, from the top and
+// bottom `Wall`s.
+pub fn wall_lines(arena: &Arena) -> (f32, f32) {
+    let mut lines = (0.0, arena.height);
+
+    for wall in arena.walls.iter() {
+        match wall.side() {
+            Side::Top => lines.0 = wall.rect().y,
+            Side::Bottom => lines.1 = wall.rect().y,
+            _ => {}
+        }
+    }
+
+    lines
+}
+
+// Where and after how many seconds the ball's center reaches `x`, bouncing
+// between the walls at `top` and `bottom` on the way. None when it moves
+// away from `x`.
+pub fn predict_crossing(ball: &Ball, x: f32, top: f32, bottom: f32) -> Option<(f32, f32)> {
+    let time = (x - ball.x) / ball.dx;
+    if !time.is_finite() || time < 0.0 {
+        return None;
+    }
+
+    // Unfold the bounces: the ball moves in a straight line through mirror
+    // images of the arena, then fold the end point back in.
+    let low = top + ball.radius;
+    let span = bottom - ball.radius - low;
+    if span <= 0.0 {
+        return Some((low, time));
+    }
+
+    let y = (ball.y + ball.dy * time - low).rem_euclid(2.0 * span);
+    let y = if y > span { 2.0 * span - y } else { y };
+
+    Some((low + y, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Tuning;
+
+    fn arena(bounce_angle: f32) -> Arena {
+        let tuning = Tuning {
+            bounce_angle,
+            ..Tuning::default()
+        };
+        let mut arena = Arena::seeded(1280.0, 720.0, tuning, 5);
+        arena.paused = None;
+        arena
+    }
+
+    #[test]
+    fn predictions_match_the_simulation() {
+        let mut arena = arena(0.0);
+        arena.ball.dx = 350.0;
+        arena.ball.dy = -900.0;
+        // Out of the way, so the ball reaches the line.
+        arena.paddles[1].rect.y = -1000.0;
+
+        let (top, bottom) = wall_lines(&arena);
+        let x = 1100.0;
+        let (y, time) = predict_crossing(&arena.ball, x, top, bottom).unwrap();
+
+        let steps = (time as f64 * 600.0).round() as usize;
+        for _ in 0..steps {
+            arena.simulate(1.0 / 600.0);
+        }
+
+        assert!((arena.ball.x - x).abs() < 2.0, "{}", arena.ball.x);
+        assert!((arena.ball.y - y).abs() < 4.0, "{} vs {}", arena.ball.y, y);
+        assert!(predict_crossing(&arena.ball, 0.0, top, bottom).is_none());
+    }
+
+    #[test]
+    fn perfect_ais_never_miss() {
+        let personality = Personality {
+            reaction_time: 0.0,
+            error: 0.0,
+            ..Personality::default()
+        };
+
+        for bounce_angle in [0.0, 60.0] {
+            let mut arena = arena(bounce_angle);
+            let mut ais = [Ai::new(personality, 0, 1), Ai::new(personality, 1, 2)];
+
+            for _ in 0..60 * 60 {
+                for (paddle, ai) in ais.iter_mut().enumerate() {
+                    let action = ai.act(&arena, 1.0 / 60.0);
+                    arena.paddles[paddle].is_up_holding = action == Action::Up;
+                    arena.paddles[paddle].is_down_holding = action == Action::Down;
+                }
+                arena.simulate(1.0 / 60.0);
+            }
+
+            assert_eq!(arena.stats.points, 0, "bounce angle {}", bounce_angle);
+            assert!(arena.stats.paddle_hits > 10);
+        }
+    }
+
+    #[test]
+    fn aggressive_ais_aim_away_from_the_opponent() {
+        let arena = arena(60.0);
+        let mut ai = Ai::new(preset("sniper").unwrap(), 1, 1);
+        ai.personality.aggression = 1.0;
+
+        // Opponent at the top, so it aims low by hitting with its bottom half.
+        let offset = ai.aim(360.0, 100.0, &arena);
+        assert!(offset > 0.0);
+        assert!(ai.aim(360.0, 650.0, &arena) < 0.0);
+
+        ai.personality.aggression = 0.0;
+        assert_eq!(ai.aim(360.0, 100.0, &arena), 0.0);
+    }
+
+    #[test]
+    fn presets_are_found_by_name() {
+        assert_eq!(preset("wall").unwrap().aggression, 0.0);
+        assert!(preset("nobody").is_none());
+    }
+}
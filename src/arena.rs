@@ -0,0 +1,512 @@
+use std::time::Duration;
+
+use ggez::graphics::Rect;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{ball::Ball, config::Tuning, paddle::Paddle, stats::MatchStats};
+
+// Time between a point and the next serve.
+pub const SERVE_DELAY: Duration = Duration::from_millis(1200);
+
+// The generator behind `StdRng`, named so it can be saved with the arena.
+pub type ArenaRng = ChaCha12Rng;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+#[derive(Clone)]
+pub struct Wall {
+    rect: Rect,
+    side: Side,
+}
+
+impl Wall {
+    pub fn new(rect: Rect, side: Side) -> Wall {
+        Wall { rect, side }
+    }
+
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+}
+
+// The ball's velocity after hitting a paddle `offset` from its center, -1.0
+// at the top edge and 1.0 at the bottom edge, see `Tuning::bounce_angle`.
+// Angled bounces keep the ball's speed.
+pub fn paddle_bounce(dx: f32, dy: f32, offset: f32, bounce_angle: f32) -> (f32, f32) {
+    if bounce_angle <= 0.0 {
+        return (-dx, dy);
+    }
+
+    let speed = dx.hypot(dy);
+    let angle = (offset.clamp(-1.0, 1.0) * bounce_angle.min(89.0)).to_radians();
+
+    (-dx.signum() * speed * angle.cos(), speed * angle.sin())
+}
+
+// The rules for single objects, shared by `Arena::simulate` and `BatchSim`
+// so both always play the same game.
+
+// Counts down the pause before a serve, true while it lasts.
+pub fn wait_for_serve(paused: &mut Option<Duration>, time: f64) -> bool {
+    match *paused {
+        Some(time_paused) => {
+            *paused = time_paused.checked_sub(Duration::from_secs_f64(time));
+            true
+        }
+        None => false,
+    }
+}
+
+// A position moved at `velocity` for `time` seconds.
+pub fn advance(position: f32, velocity: f32, time: f64) -> f32 {
+    (position as f64 + (velocity as f64 * time)) as f32
+}
+
+// The top of `paddle` once it is pushed back inside the top and bottom walls.
+pub fn clamp_paddle(mut paddle: Rect, walls: &[Wall], height: f32) -> f32 {
+    for wall in walls.iter() {
+        if !paddle.overlaps(&wall.rect) {
+            continue;
+        }
+
+        match wall.side {
+            Side::Top => paddle.y = 0.0,
+            Side::Bottom => paddle.y = height - paddle.h,
+            _ => {}
+        }
+    }
+
+    paddle.y
+}
+
+// What touching a wall did to the ball.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WallContact {
+    // Player 1 or 2 scored, the caller serves again.
+    Goal { scorer: usize },
+    Bounce,
+}
+
+// Bounces the ball off `wall` if `ball_rect`, where the ball is this step,
+// touches it. Past the top or bottom edge counts as touching.
+pub fn wall_contact(
+    ball: &mut Ball,
+    ball_rect: Rect,
+    wall: &Wall,
+    height: f32,
+) -> Option<WallContact> {
+    if !ball_rect.overlaps(&wall.rect) && (ball_rect.top() > 0.0 || ball_rect.bottom() < height) {
+        return None;
+    }
+
+    match wall.side {
+        Side::Left => Some(WallContact::Goal { scorer: 2 }),
+        Side::Right => Some(WallContact::Goal { scorer: 1 }),
+        Side::Top => {
+            ball.y = ball.radius;
+            ball.dy = -ball.dy;
+            Some(WallContact::Bounce)
+        }
+        Side::Bottom => {
+            ball.y = height - ball.radius;
+            ball.dy = -ball.dy;
+            Some(WallContact::Bounce)
+        }
+    }
+}
+
+// Returns the ball off the paddle on `side` if `ball_rect` touches it, true
+// if it did.
+pub fn paddle_contact(
+    ball: &mut Ball,
+    ball_rect: Rect,
+    paddle: Rect,
+    side: Side,
+    bounce_angle: f32,
+) -> bool {
+    if !ball_rect.overlaps(&paddle) {
+        return false;
+    }
+
+    match side {
+        Side::Left => ball.x = paddle.x + paddle.w + ball.radius,
+        Side::Right => ball.x = paddle.x - ball.radius,
+        _ => {}
+    }
+
+    let half = paddle.h / 2.0;
+    let offset = (ball.y - paddle.y - half) / half;
+    let (dx, dy) = paddle_bounce(ball.dx, ball.dy, offset, bounce_angle);
+    ball.dx = dx;
+    ball.dy = dy;
+
+    true
+}
+
+// What `ArenaState` keeps of a paddle, the rest comes from the tuning.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PaddleState {
+    pub y: f32,
+    pub is_up_holding: bool,
+    pub is_down_holding: bool,
+}
+
+// A saved `Arena`, see `Arena::state`. Walls, paddle sizes and pending events
+// aren't kept.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArenaState {
+    pub width: f32,
+    pub height: f32,
+    pub tuning: Tuning,
+    pub ball: Ball,
+    pub paddles: [PaddleState; 2],
+    pub paused: Option<Duration>,
+    pub scores: [usize; 2],
+    pub rally: u32,
+    pub stats: MatchStats,
+    pub rng: ArenaRng,
+    pub god_paddles: [bool; 2],
+}
+
+// Something that happened during `Arena::simulate`, for sounds, rewards
+// and the like.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArenaEvent {
+    // Player 1 or 2 scored.
+    Goal { scorer: usize },
+    // Paddle 0 (left) or 1 (right) hit the ball.
+    PaddleHit { paddle: usize },
+    WallBounce,
+}
+
+// The match itself: ball, paddles, walls and scores, and the rules that move
+// them. It knows nothing about windows, sound or input, so it also runs
+// headless, see `env.rs`.
+#[derive(Clone)]
+pub struct Arena {
+    pub width: f32,
+    pub height: f32,
+    // Effective tuning, with accessibility sizes applied.
+    pub tuning: Tuning,
+    pub ball: Ball,
+    pub walls: Vec<Wall>,
+    // Left then right.
+    pub paddles: Vec<Paddle>,
+    // Time left before the next serve.
+    pub paused: Option<Duration>,
+    pub player1_score: usize,
+    pub player2_score: usize,
+    // Paddle hits since the last serve.
+    pub rally: u32,
+    pub stats: MatchStats,
+    // Serve directions, seeded for repeatable matches.
+    pub rng: ArenaRng,
+    // Paddles that follow the ball on their own, left then right.
+    pub god_paddles: [bool; 2],
+    // What happened since the owner last emptied it.
+    pub events: Vec<ArenaEvent>,
+}
+
+impl Arena {
+    pub fn new(width: f32, height: f32, tuning: Tuning, rng: ArenaRng) -> Arena {
+        let mut rng = rng;
+
+        // Vector of paddles to easily iterate over each paddle and check collisions with one loop.
+        let paddles = vec![
+            Paddle::new(width, height, Side::Left, &tuning),
+            Paddle::new(width, height, Side::Right, &tuning),
+        ];
+
+        // Vec of walls for the same reason.
+        let walls = vec![
+            Wall::new(Rect::new(0.0, 0.0, width, 0.0), Side::Top),
+            Wall::new(Rect::new(0.0, 0.0, 0.0, height), Side::Left),
+            Wall::new(Rect::new(width, 0.0, 0.0, height), Side::Right),
+            Wall::new(Rect::new(0.0, height, width, 0.0), Side::Bottom),
+        ];
+
+        let ball = Ball::new(width, height, &tuning, &mut rng);
+
+        Arena {
+            width,
+            height,
+            tuning,
+            ball,
+            walls,
+            paddles,
+            paused: None,
+            player1_score: 0,
+            player2_score: 0,
+            rally: 0,
+            stats: MatchStats::default(),
+            rng,
+            god_paddles: [false, false],
+            events: Vec::new(),
+        }
+    }
+
+    pub fn seeded(width: f32, height: f32, tuning: Tuning, seed: u64) -> Arena {
+        Arena::new(width, height, tuning, ArenaRng::seed_from_u64(seed))
+    }
+
+    // A copy of the match that `restore` can continue exactly where it was.
+    pub fn state(&self) -> ArenaState {
+        let paddle = |i: usize| {
+            let paddle = &self.paddles[i];
+            PaddleState {
+                y: paddle.rect.y,
+                is_up_holding: paddle.is_up_holding,
+                is_down_holding: paddle.is_down_holding,
+            }
+        };
+
+        ArenaState {
+            width: self.width,
+            height: self.height,
+            tuning: self.tuning,
+            ball: self.ball.clone(),
+            paddles: [paddle(0), paddle(1)],
+            paused: self.paused,
+            scores: [self.player1_score, self.player2_score],
+            rally: self.rally,
+            stats: self.stats,
+            rng: self.rng.clone(),
+            god_paddles: self.god_paddles,
+        }
+    }
+
+    pub fn restore(state: &ArenaState) -> Arena {
+        let mut arena = Arena::new(state.width, state.height, state.tuning, state.rng.clone());
+
+        arena.ball = state.ball.clone();
+        for (paddle, saved) in arena.paddles.iter_mut().zip(state.paddles.iter()) {
+            paddle.rect.y = saved.y;
+            paddle.is_up_holding = saved.is_up_holding;
+            paddle.is_down_holding = saved.is_down_holding;
+        }
+        arena.paused = state.paused;
+        arena.player1_score = state.scores[0];
+        arena.player2_score = state.scores[1];
+        arena.rally = state.rally;
+        arena.stats = state.stats;
+        arena.rng = state.rng.clone();
+        arena.god_paddles = state.god_paddles;
+
+        arena
+    }
+
+    // Serves again from the center, and starts a new match with `reset_score`.
+    pub fn reset(&mut self, reset_score: bool) {
+        self.ball = Ball::new(self.width, self.height, &self.tuning, &mut self.rng);
+
+        for paddle in self.paddles.iter_mut() {
+            *paddle = Paddle::new(self.width, self.height, paddle.side, &self.tuning);
+        }
+
+        self.rally = 0;
+
+        if reset_score {
+            self.player1_score = 0;
+            self.player2_score = 0;
+            self.stats = MatchStats::default();
+        }
+
+        self.paused = Some(SERVE_DELAY);
+    }
+
+    // Applies new tuning values without resetting the match.
+    pub fn retune(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+
+        self.ball.retune(&tuning);
+        for paddle in self.paddles.iter_mut() {
+            paddle.retune(self.width, &tuning);
+        }
+    }
+
+    // Player number of the winner once the match is over.
+    pub fn winner(&self) -> Option<usize> {
+        let winning_score = self.tuning.winning_score;
+
+        if self.player1_score >= winning_score {
+            Some(1)
+        } else if self.player2_score >= winning_score {
+            Some(2)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_match_point(&self) -> bool {
+        let winning_score = self.tuning.winning_score;
+
+        self.player1_score + 1 >= winning_score || self.player2_score + 1 >= winning_score
+    }
+
+    // Moves everything by `time` seconds and applies the rules.
+    pub fn simulate(&mut self, time: f64) {
+        // If the game is paused, then we don't want to simulate.
+        if wait_for_serve(&mut self.paused, time) {
+            return;
+        }
+
+        self.stats.play_time += time;
+
+        // Update paddle positions and check paddle collisions.
+        for i in 0..self.paddles.len() {
+            let paddle = &mut self.paddles[i];
+            paddle.rect.y = advance(paddle.rect.y, paddle.velocity(), time);
+
+            // God mode keeps the paddle centered on the ball.
+            if self.god_paddles.get(i) == Some(&true) {
+                paddle.rect.y = self.ball.y - paddle.rect.h / 2.0;
+            }
+
+            paddle.rect.y = clamp_paddle(paddle.rect, &self.walls, self.height);
+        }
+
+        // Update ball position
+        self.ball.x = advance(self.ball.x, self.ball.dx, time);
+        self.ball.y = advance(self.ball.y, self.ball.dy, time);
+
+        let ball_rect = self.ball.rect();
+
+        // Check if ball collides with any walls
+        for i in 0..self.walls.len() {
+            match wall_contact(&mut self.ball, ball_rect, &self.walls[i], self.height) {
+                Some(WallContact::Goal { scorer }) => {
+                    match scorer {
+                        1 => self.player1_score += 1,
+                        _ => self.player2_score += 1,
+                    }
+                    self.stats.points += 1;
+                    self.events.push(ArenaEvent::Goal { scorer });
+
+                    self.reset(false);
+                }
+                Some(WallContact::Bounce) => {
+                    self.stats.wall_bounces += 1;
+                    self.events.push(ArenaEvent::WallBounce);
+                }
+                None => {}
+            }
+        }
+
+        for i in 0..self.paddles.len() {
+            let paddle = &self.paddles[i];
+
+            if paddle_contact(
+                &mut self.ball,
+                ball_rect,
+                paddle.rect,
+                paddle.side,
+                self.tuning.bounce_angle,
+            ) {
+                self.rally += 1;
+                self.stats.paddle_hits += 1;
+                self.stats.longest_rally = self.stats.longest_rally.max(self.rally);
+                self.events.push(ArenaEvent::PaddleHit { paddle: i });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::SQRT_2;
+
+    use super::*;
+
+    fn arena() -> Arena {
+        let mut arena = Arena::seeded(1280.0, 720.0, Tuning::default(), 7);
+        arena.paused = None;
+        arena
+    }
+
+    #[test]
+    fn serves_wait_for_the_delay() {
+        let mut arena = arena();
+        arena.reset(true);
+        let start = arena.ball.x;
+
+        arena.simulate(1.0);
+        assert_eq!(arena.ball.x, start);
+
+        arena.simulate(0.3);
+        arena.simulate(0.1);
+        assert_ne!(arena.ball.x, start);
+    }
+
+    #[test]
+    fn missing_the_ball_scores_for_the_other_player() {
+        let mut arena = arena();
+        arena.ball.x = 5.0;
+        arena.ball.y = 100.0;
+        arena.ball.dx = -500.0;
+        arena.paddles[0].rect.y = 500.0;
+
+        arena.simulate(1.0 / 60.0);
+
+        assert_eq!(arena.player2_score, 1);
+        assert_eq!(arena.events, vec![ArenaEvent::Goal { scorer: 2 }]);
+        assert_eq!(arena.paused, Some(SERVE_DELAY));
+    }
+
+    #[test]
+    fn paddles_return_the_ball() {
+        let mut arena = arena();
+        let paddle = arena.paddles[1].rect;
+        arena.ball.x = paddle.x - 5.0;
+        arena.ball.y = paddle.y + paddle.h / 2.0;
+        arena.ball.dx = 500.0;
+
+        arena.simulate(1.0 / 60.0);
+
+        assert!(arena.ball.dx < 0.0);
+        assert_eq!(arena.rally, 1);
+        assert_eq!(arena.events, vec![ArenaEvent::PaddleHit { paddle: 1 }]);
+    }
+
+    #[test]
+    fn same_seed_same_match() {
+        let run = || {
+            let mut arena = arena();
+            for _ in 0..5000 {
+                arena.simulate(1.0 / 60.0);
+            }
+            (
+                arena.ball.x,
+                arena.ball.y,
+                arena.player1_score,
+                arena.player2_score,
+            )
+        };
+
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn paddle_hits_angle_the_ball_when_tuned() {
+        assert_eq!(paddle_bounce(500.0, -500.0, 0.7, 0.0), (-500.0, -500.0));
+
+        let (dx, dy) = paddle_bounce(500.0, -500.0, 0.0, 60.0);
+        assert!((dx + 500.0 * SQRT_2).abs() < 0.01 && dy.abs() < 0.01);
+
+        let (dx, dy) = paddle_bounce(-500.0, 0.0, 2.0, 60.0);
+        assert!((dx - 250.0).abs() < 0.01);
+        assert!((dy - 500.0 * 0.75_f32.sqrt()).abs() < 0.01);
+    }
+}
@@ -1,61 +1,314 @@
-use ggez::{
-    audio::{self, SoundSource},
-    graphics, Context, GameResult,
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Read,
 };
 
-use crate::{particle::ParticleType, pong::SoundType};
+use ggez::{filesystem, graphics, Context, GameResult};
+
+use crate::{
+    audio::SoundClip,
+    manifest::{self, AssetKind, AssetManifest, MANIFEST_PATH},
+    particle::ParticleType,
+    synth::{self, SoundSettings, SoundSource, SynthParams},
+};
 
 pub struct Assets {
-    pub goal_sound: audio::Source,
-    pub wall_sound: audio::Source,
-    pub pad_sound: audio::Source,
-    pub blue_particle: graphics::Image,
-    pub red_particle: graphics::Image,
-    pub green_particle: graphics::Image,
-    pub shimmer_particle: graphics::Image,
+    manifest: AssetManifest,
+    sounds: HashMap<String, SoundClip>,
+    // Events switched to the synthesizer in the config, these win over `sounds`.
+    synthesized: HashMap<String, SoundClip>,
+    images: HashMap<String, graphics::Image>,
+    fonts: HashMap<String, graphics::Font>,
+    // Parsed copies of the fonts, to check which characters they can draw.
+    glyphs: HashMap<String, rusttype::Font<'static>>,
+    pub blank_particle: graphics::Image,
 }
 
 impl Assets {
     pub fn new(ctx: &mut Context) -> GameResult<Assets> {
-        let goal_sound = audio::Source::new(ctx, "/goal.wav")?;
-        let wall_sound = audio::Source::new(ctx, "/wall.wav")?;
-        let pad_sound = audio::Source::new(ctx, "/pad.wav")?;
+        let manifest = read_manifest(ctx).unwrap_or_else(|e| {
+            eprintln!("warning: {}, using the built-in manifest", e);
+            AssetManifest::builtin()
+        });
+
+        let mut sounds = HashMap::new();
+        for (id, path) in manifest.sounds.iter() {
+            let sound = match load_sound(ctx, path) {
+                Ok(sound) => sound,
+                Err(e) => {
+                    warn_placeholder(id, path, &e);
+                    SoundClip::new(synth::render_wav(&SynthParams::preset(id)))
+                }
+            };
+
+            sounds.insert(id.clone(), sound);
+        }
 
-        let green_particle = graphics::Image::new(ctx, "/green.bmp")?;
-        let red_particle = graphics::Image::new(ctx, "/red.bmp")?;
-        let blue_particle = graphics::Image::new(ctx, "/blue.bmp")?;
-        let shimmer_particle = graphics::Image::new(ctx, "/shimmer.bmp")?;
+        let mut images = HashMap::new();
+        for (id, path) in manifest.images.iter() {
+            let image = match load_image(ctx, path) {
+                Ok(image) => image,
+                Err(e) => {
+                    warn_placeholder(id, path, &e);
+                    placeholder_sprite(ctx)?
+                }
+            };
+
+            images.insert(id.clone(), image);
+        }
+
+        let mut fonts = HashMap::new();
+        let mut glyphs = HashMap::new();
+        for (id, path) in manifest.fonts.iter() {
+            let font = match load_font(ctx, path) {
+                Ok((font, font_glyphs)) => {
+                    glyphs.insert(id.clone(), font_glyphs);
+                    font
+                }
+                Err(e) => {
+                    warn_placeholder(id, path, &e);
+                    graphics::Font::default()
+                }
+            };
+
+            fonts.insert(id.clone(), font);
+        }
+
+        // Tinted with the theme's particle colors.
+        let blank_particle = graphics::Image::solid(ctx, 5, graphics::WHITE)?;
 
         Ok(Assets {
-            goal_sound,
-            wall_sound,
-            pad_sound,
-            green_particle,
-            red_particle,
-            blue_particle,
-            shimmer_particle,
+            manifest,
+            sounds,
+            synthesized: HashMap::new(),
+            images,
+            fonts,
+            glyphs,
+            blank_particle,
         })
     }
 
-    pub fn play_sound(&mut self, play_sounds: bool, sound_type: SoundType) {
-        if !play_sounds {
-            return;
+    // Reloads every asset stored at the resource `path`, or the assets whose
+    // entry changed when `path` is the manifest. Assets that fail to load keep
+    // their previous version. Returns the IDs that were reloaded.
+    pub fn reload(&mut self, ctx: &mut Context, path: &str) -> Result<Vec<String>, String> {
+        let entries: Vec<(AssetKind, String, String)> = if path == MANIFEST_PATH {
+            let manifest = read_manifest(ctx)?;
+
+            let changed = manifest
+                .entries()
+                .filter(|(kind, id, path)| self.manifest_path(*kind, id) != Some(path))
+                .map(|(kind, id, path)| (kind, id.clone(), path.clone()))
+                .collect();
+
+            self.manifest = manifest;
+            changed
+        } else {
+            self.manifest
+                .entries()
+                .filter(|(_, _, entry_path)| entry_path.as_str() == path)
+                .map(|(kind, id, path)| (kind, id.clone(), path.clone()))
+                .collect()
         };
 
-        match sound_type {
-            SoundType::Goal => self.goal_sound.play_detached().unwrap(),
-            SoundType::Pad => self.pad_sound.play_detached().unwrap(),
-            SoundType::Wall => self.wall_sound.play_detached().unwrap(),
+        let mut reloaded = Vec::new();
+        let mut errors = Vec::new();
+
+        for (kind, id, path) in entries {
+            let result = match kind {
+                AssetKind::Sound => replace(&mut self.sounds, &id, load_sound(ctx, &path)),
+                AssetKind::Image => replace(&mut self.images, &id, load_image(ctx, &path)),
+                AssetKind::Font => load_font(ctx, &path).map(|(font, glyphs)| {
+                    self.fonts.insert(id.clone(), font);
+                    self.glyphs.insert(id.clone(), glyphs);
+                }),
+            };
+
+            match result {
+                Ok(()) => reloaded.push(id),
+                Err(e) => errors.push(format!("{} ({}): {}", id, path, e)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(reloaded)
+        } else {
+            Err(errors.join(", "))
         }
     }
 
+    fn manifest_path(&self, kind: AssetKind, id: &str) -> Option<&String> {
+        match kind {
+            AssetKind::Sound => self.manifest.sounds.get(id),
+            AssetKind::Image => self.manifest.images.get(id),
+            AssetKind::Font => self.manifest.fonts.get(id),
+        }
+    }
+
+    // Renders the sounds that are set to come from the synthesizer.
+    pub fn synthesize(&mut self, settings: &BTreeMap<String, SoundSettings>) {
+        self.synthesized = settings
+            .iter()
+            .filter(|(_, sound)| sound.source == SoundSource::Synth)
+            .map(|(id, sound)| {
+                let clip = SoundClip::new(synth::render_wav(&sound.synth_params(id)));
+                (id.clone(), clip)
+            })
+            .collect();
+    }
+
+    pub fn sound(&self, id: &str) -> Option<&SoundClip> {
+        self.synthesized.get(id).or_else(|| self.sounds.get(id))
+    }
+
+    // Unknown IDs get the blank sprite rather than failing mid-frame.
+    pub fn image(&self, id: &str) -> &graphics::Image {
+        self.images.get(id).unwrap_or(&self.blank_particle)
+    }
+
+    pub fn font(&self, id: &str) -> graphics::Font {
+        self.fonts.get(id).copied().unwrap_or_default()
+    }
+
+    // Fonts that failed to load were replaced by the built-in font, which is
+    // assumed to draw everything.
+    pub fn font_covers(&self, id: &str, text: &str) -> bool {
+        self.glyphs
+            .get(id)
+            .is_none_or(|glyphs| covers(glyphs, text))
+    }
+
     pub fn particle_image(&self, particle: ParticleType) -> &graphics::Image {
         match particle {
-            ParticleType::Green => &self.green_particle,
-            ParticleType::Red => &self.red_particle,
-            ParticleType::Blue => &self.blue_particle,
-            ParticleType::Shimmer => &self.shimmer_particle,
-            _ => &self.green_particle,
+            ParticleType::Green => self.image("particle_green"),
+            ParticleType::Red => self.image("particle_red"),
+            ParticleType::Blue => self.image("particle_blue"),
+            ParticleType::Shimmer => self.image("particle_shimmer"),
+            _ => self.image("particle_green"),
         }
     }
 }
+
+fn read_manifest(ctx: &mut Context) -> Result<AssetManifest, String> {
+    let mut contents = String::new();
+    filesystem::open(ctx, MANIFEST_PATH)
+        .and_then(|mut file| Ok(file.read_to_string(&mut contents)?))
+        .map_err(|e| format!("could not read {} ({})", MANIFEST_PATH, e))?;
+
+    AssetManifest::parse(&contents).map_err(|e| format!("invalid {} ({})", MANIFEST_PATH, e))
+}
+
+// Reads an asset and makes sure it decodes before handing it to ggez.
+fn load_bytes(ctx: &mut Context, kind: AssetKind, path: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    filesystem::open(ctx, path)
+        .and_then(|mut file| Ok(file.read_to_end(&mut bytes)?))
+        .map_err(|e| e.to_string())?;
+
+    manifest::validate(kind, &bytes)?;
+
+    Ok(bytes)
+}
+
+fn load_sound(ctx: &mut Context, path: &str) -> Result<SoundClip, String> {
+    load_bytes(ctx, AssetKind::Sound, path).map(SoundClip::new)
+}
+
+fn load_image(ctx: &mut Context, path: &str) -> Result<graphics::Image, String> {
+    graphics::Image::new(ctx, path).map_err(|e| e.to_string())
+}
+
+fn load_font(
+    ctx: &mut Context,
+    path: &str,
+) -> Result<(graphics::Font, rusttype::Font<'static>), String> {
+    let bytes = load_bytes(ctx, AssetKind::Font, path)?;
+    let font = graphics::Font::new_glyph_font_bytes(ctx, &bytes).map_err(|e| e.to_string())?;
+    let glyphs = rusttype::Font::from_bytes(bytes).map_err(|e| e.to_string())?;
+
+    Ok((font, glyphs))
+}
+
+// Stores a reloaded asset, or keeps the previous one if it failed to load.
+fn replace<T>(
+    assets: &mut HashMap<String, T>,
+    id: &str,
+    reloaded: Result<T, String>,
+) -> Result<(), String> {
+    assets.insert(id.to_string(), reloaded?);
+    Ok(())
+}
+
+// Glyph 0 is the "missing character" box.
+fn covers(font: &rusttype::Font, text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_whitespace() || font.glyph(c).id().0 != 0)
+}
+
+fn warn_placeholder(id: &str, path: &str, error: &str) {
+    eprintln!(
+        "warning: asset \"{}\" ({}) failed to load: {}. Using a placeholder.",
+        id, path, error
+    );
+}
+
+// Magenta so a missing sprite is obvious on screen.
+fn placeholder_sprite(ctx: &mut Context) -> GameResult<graphics::Image> {
+    graphics::Image::solid(ctx, 5, graphics::Color::from_rgba(255, 0, 255, 255))
+}
+
+pub fn wav_bytes(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_spots_missing_glyphs() {
+        let bytes = std::fs::read("resources/joystix_mono.ttf").unwrap();
+        let font = rusttype::Font::from_bytes(bytes).unwrap();
+
+        assert!(covers(&font, "PONG 10 \t 7"));
+        assert!(!covers(&font, "日本語"));
+    }
+
+    #[test]
+    fn broken_reload_keeps_the_previous_asset() {
+        let decode = |bytes: Vec<u8>| {
+            manifest::validate(AssetKind::Sound, &bytes).map(|_| SoundClip::new(bytes))
+        };
+        let previous = wav_bytes(&[0, 1000, 0], 44100);
+        let mut sounds = HashMap::new();
+        sounds.insert(String::from("goal"), SoundClip::new(previous.clone()));
+
+        let truncated = previous[..30].to_vec();
+        assert!(replace(&mut sounds, "goal", decode(truncated)).is_err());
+        assert_eq!(sounds["goal"].as_ref(), &previous[..]);
+
+        let next = wav_bytes(&[0, -1000, 0], 44100);
+        assert!(replace(&mut sounds, "goal", decode(next.clone())).is_ok());
+        assert_eq!(sounds["goal"].as_ref(), &next[..]);
+    }
+}
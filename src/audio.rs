@@ -0,0 +1,669 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufReader, Cursor},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::{source::ChannelVolume, Sink, Source};
+use serde::{Deserialize, Serialize};
+
+// Validated, still encoded sound file. Cheap to clone.
+#[derive(Clone)]
+pub struct SoundClip(Arc<[u8]>);
+
+impl SoundClip {
+    pub fn new(bytes: Vec<u8>) -> SoundClip {
+        SoundClip(Arc::from(bytes))
+    }
+}
+
+impl AsRef<[u8]> for SoundClip {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// How a single sound is played. `pan` goes from -1.0 (left) to 1.0 (right),
+// `pitch` is a playback speed factor where 1.0 is the original pitch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlayParams {
+    pub volume: f32,
+    pub pan: f32,
+    pub pitch: f32,
+}
+
+impl PlayParams {
+    pub fn centered(volume: f32) -> PlayParams {
+        PlayParams {
+            volume,
+            pan: 0.0,
+            pitch: 1.0,
+        }
+    }
+
+    // Balance style panning: the far channel fades out while the near one
+    // stays at full volume, so centered sounds are as loud as before.
+    fn channel_volumes(&self) -> Vec<f32> {
+        let pan = self.pan.clamp(-1.0, 1.0);
+        vec![
+            (1.0 - pan).min(1.0) * self.volume,
+            (1.0 + pan).min(1.0) * self.volume,
+        ]
+    }
+}
+
+pub trait AudioBackend {
+    fn play(&mut self, id: &str, clip: &SoundClip, params: PlayParams) -> Result<(), String>;
+
+    // Continuous tone that follows the ball, `None` silences it.
+    fn set_cue(&mut self, cue: Option<PlayParams>);
+
+    // Streams a music track made of one or more layers that play in sync.
+    // Tracks start silent until `set_track_volumes` is called.
+    fn start_track(&mut self, layers: &[PathBuf]) -> Result<TrackId, String>;
+
+    fn set_track_volumes(&mut self, track: TrackId, volumes: &[f32]);
+
+    fn track_finished(&self, track: TrackId) -> bool;
+
+    fn stop_track(&mut self, track: TrackId);
+}
+
+pub type TrackId = usize;
+
+// Plays through the default output device with rodio.
+pub struct RodioBackend {
+    device: rodio::Device,
+    // Keeps the output stream opened by `new` alive, rodio shares it with
+    // every later sink on the device.
+    _stream: Sink,
+    // Started on first use, then kept alive and silenced through `cue`.
+    cue_sink: Option<Sink>,
+    // Not retried every frame once it failed.
+    cue_failed: bool,
+    cue: Arc<Mutex<PlayParams>>,
+    // One sink per layer so every layer has its own volume.
+    tracks: HashMap<TrackId, Vec<Sink>>,
+    next_track: TrackId,
+}
+
+impl RodioBackend {
+    // `None` when there is no device or it can't open a stream.
+    pub fn new() -> Option<RodioBackend> {
+        let device = rodio::default_output_device()?;
+
+        if let Err(e) = device.default_output_format() {
+            eprintln!("warning: the audio device has no output format: {:?}", e);
+            return None;
+        }
+
+        let stream = match open_stream(|| Sink::new(&device)) {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("warning: {}", e);
+                return None;
+            }
+        };
+
+        Some(RodioBackend {
+            device,
+            _stream: stream,
+            cue_sink: None,
+            cue_failed: false,
+            cue: Arc::new(Mutex::new(PlayParams::centered(0.0))),
+            tracks: HashMap::new(),
+            next_track: 0,
+        })
+    }
+}
+
+// rodio 0.9 panics when a device fails to open a stream, turn that into an
+// error.
+fn open_stream<T>(open: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(open))
+        .map_err(|_| String::from("the audio device failed to open a stream"))
+}
+
+impl AudioBackend for RodioBackend {
+    fn play(&mut self, _id: &str, clip: &SoundClip, params: PlayParams) -> Result<(), String> {
+        let decoder = rodio::Decoder::new(Cursor::new(clip.clone())).map_err(|e| e.to_string())?;
+        let source = decoder.speed(params.pitch).convert_samples::<f32>();
+
+        open_stream(|| {
+            rodio::play_raw(
+                &self.device,
+                ChannelVolume::new(source, params.channel_volumes()),
+            )
+        })
+    }
+
+    fn set_cue(&mut self, cue: Option<PlayParams>) {
+        *self.cue.lock().unwrap() = cue.unwrap_or_else(|| PlayParams::centered(0.0));
+
+        if cue.is_some() && self.cue_sink.is_none() && !self.cue_failed {
+            match open_stream(|| Sink::new(&self.device)) {
+                Ok(sink) => {
+                    sink.append(CueTone::new(self.cue.clone()));
+                    self.cue_sink = Some(sink);
+                }
+                Err(e) => {
+                    eprintln!("warning: {}", e);
+                    self.cue_failed = true;
+                }
+            }
+        }
+    }
+
+    fn start_track(&mut self, layers: &[PathBuf]) -> Result<TrackId, String> {
+        let mut sinks = Vec::with_capacity(layers.len());
+
+        for path in layers.iter() {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let decoder = rodio::Decoder::new(BufReader::new(file))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+            let sink = open_stream(|| Sink::new(&self.device))?;
+            sink.pause();
+            sink.set_volume(0.0);
+            sink.append(decoder);
+            sinks.push(sink);
+        }
+
+        // Unpaused together so the layers stay in sync.
+        for sink in sinks.iter() {
+            sink.play();
+        }
+
+        let track = self.next_track;
+        self.next_track += 1;
+        self.tracks.insert(track, sinks);
+
+        Ok(track)
+    }
+
+    fn set_track_volumes(&mut self, track: TrackId, volumes: &[f32]) {
+        if let Some(sinks) = self.tracks.get(&track) {
+            for (sink, volume) in sinks.iter().zip(volumes) {
+                sink.set_volume(*volume);
+            }
+        }
+    }
+
+    fn track_finished(&self, track: TrackId) -> bool {
+        self.tracks
+            .get(&track)
+            .is_none_or(|sinks| sinks.iter().all(|sink| sink.empty()))
+    }
+
+    fn stop_track(&mut self, track: TrackId) {
+        // Dropping a sink stops it.
+        self.tracks.remove(&track);
+    }
+}
+
+const CUE_SAMPLE_RATE: u32 = 44100;
+const CUE_FREQUENCY: f32 = 330.0;
+const CUE_GAIN: f32 = 0.25;
+// Per sample smoothing so moving the ball doesn't click.
+const CUE_SMOOTHING: f32 = 0.002;
+
+// Endless stereo triangle wave reading its volume, pan and pitch from shared state.
+struct CueTone {
+    params: Arc<Mutex<PlayParams>>,
+    volumes: [f32; 2],
+    frequency: f32,
+    phase: f32,
+    channel: usize,
+}
+
+impl CueTone {
+    fn new(params: Arc<Mutex<PlayParams>>) -> CueTone {
+        CueTone {
+            params,
+            volumes: [0.0, 0.0],
+            frequency: CUE_FREQUENCY,
+            phase: 0.0,
+            channel: 0,
+        }
+    }
+}
+
+impl Iterator for CueTone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == 0 {
+            let params = *self.params.lock().unwrap();
+            let targets = params.channel_volumes();
+            for (volume, target) in self.volumes.iter_mut().zip(targets) {
+                *volume += (target - *volume) * CUE_SMOOTHING;
+            }
+            self.frequency += (CUE_FREQUENCY * params.pitch - self.frequency) * CUE_SMOOTHING;
+            self.phase = (self.phase + self.frequency / CUE_SAMPLE_RATE as f32).fract();
+        }
+
+        let triangle = 1.0 - 4.0 * (self.phase - 0.5).abs();
+        let sample = triangle * CUE_GAIN * self.volumes[self.channel];
+        self.channel = 1 - self.channel;
+
+        Some(sample)
+    }
+}
+
+impl Source for CueTone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        CUE_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// Used when there is no output device.
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self, _id: &str, _clip: &SoundClip, _params: PlayParams) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn set_cue(&mut self, _cue: Option<PlayParams>) {}
+
+    fn start_track(&mut self, _layers: &[PathBuf]) -> Result<TrackId, String> {
+        Ok(0)
+    }
+
+    fn set_track_volumes(&mut self, _track: TrackId, _volumes: &[f32]) {}
+
+    fn track_finished(&self, _track: TrackId) -> bool {
+        false
+    }
+
+    fn stop_track(&mut self, _track: TrackId) {}
+}
+
+// Remembers what would have been played, for tests. Clones share the log so
+// one can be handed to a `Mixer` and the other inspected.
+#[derive(Clone, Default)]
+pub struct RecordingBackend {
+    played: Rc<RefCell<Vec<(String, PlayParams)>>>,
+    cue: Rc<RefCell<Option<PlayParams>>>,
+    // Stopped tracks are kept as `None` so their IDs are not reused.
+    tracks: Rc<RefCell<BTreeMap<TrackId, Option<RecordedTrack>>>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedTrack {
+    pub layers: Vec<PathBuf>,
+    pub volumes: Vec<f32>,
+    pub finished: bool,
+}
+
+impl RecordingBackend {
+    pub fn played(&self) -> Vec<(String, PlayParams)> {
+        self.played.borrow().clone()
+    }
+
+    pub fn cue(&self) -> Option<PlayParams> {
+        *self.cue.borrow()
+    }
+
+    // Tracks that are still playing, stopped ones are removed.
+    pub fn tracks(&self) -> BTreeMap<TrackId, RecordedTrack> {
+        self.tracks
+            .borrow()
+            .iter()
+            .filter_map(|(track, recorded)| recorded.clone().map(|recorded| (*track, recorded)))
+            .collect()
+    }
+
+    pub fn finish_track(&self, track: TrackId) {
+        if let Some(Some(recorded)) = self.tracks.borrow_mut().get_mut(&track) {
+            recorded.finished = true;
+        }
+    }
+}
+
+impl AudioBackend for RecordingBackend {
+    fn play(&mut self, id: &str, _clip: &SoundClip, params: PlayParams) -> Result<(), String> {
+        self.played.borrow_mut().push((id.to_string(), params));
+        Ok(())
+    }
+
+    fn set_cue(&mut self, cue: Option<PlayParams>) {
+        *self.cue.borrow_mut() = cue;
+    }
+
+    fn start_track(&mut self, layers: &[PathBuf]) -> Result<TrackId, String> {
+        let mut tracks = self.tracks.borrow_mut();
+        let track = tracks.len();
+
+        tracks.insert(
+            track,
+            Some(RecordedTrack {
+                layers: layers.to_vec(),
+                volumes: vec![0.0; layers.len()],
+                finished: false,
+            }),
+        );
+
+        Ok(track)
+    }
+
+    fn set_track_volumes(&mut self, track: TrackId, volumes: &[f32]) {
+        if let Some(Some(recorded)) = self.tracks.borrow_mut().get_mut(&track) {
+            recorded.volumes = volumes.to_vec();
+        }
+    }
+
+    fn track_finished(&self, track: TrackId) -> bool {
+        self.tracks
+            .borrow()
+            .get(&track)
+            .and_then(|recorded| recorded.as_ref())
+            .is_none_or(|recorded| recorded.finished)
+    }
+
+    fn stop_track(&mut self, track: TrackId) {
+        if let Some(recorded) = self.tracks.borrow_mut().get_mut(&track) {
+            *recorded = None;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MixerSettings {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+    pub master_muted: bool,
+    pub sfx_muted: bool,
+    pub music_muted: bool,
+    pub shuffle_music: bool,
+    // Accessibility: a continuous tone panned with the ball's position.
+    pub ball_cue: bool,
+}
+
+impl Default for MixerSettings {
+    fn default() -> MixerSettings {
+        MixerSettings {
+            master: 1.0,
+            sfx: 1.0,
+            music: 0.7,
+            master_muted: false,
+            sfx_muted: false,
+            music_muted: false,
+            shuffle_music: false,
+            ball_cue: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Channel {
+    Master,
+    Sfx,
+    Music,
+}
+
+pub struct Mixer {
+    pub settings: MixerSettings,
+    backend: Box<dyn AudioBackend>,
+    failed: bool,
+}
+
+impl Mixer {
+    pub fn new(settings: MixerSettings, backend: Box<dyn AudioBackend>) -> Mixer {
+        Mixer {
+            settings,
+            backend,
+            failed: false,
+        }
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.volume(Channel::Master) * self.volume(Channel::Sfx)
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.volume(Channel::Master) * self.volume(Channel::Music)
+    }
+
+    // Level of a single channel, zero when muted.
+    pub fn volume(&self, channel: Channel) -> f32 {
+        let (level, muted) = self.level(channel);
+        if muted {
+            0.0
+        } else {
+            level
+        }
+    }
+
+    pub fn level(&self, channel: Channel) -> (f32, bool) {
+        let s = &self.settings;
+        match channel {
+            Channel::Master => (s.master, s.master_muted),
+            Channel::Sfx => (s.sfx, s.sfx_muted),
+            Channel::Music => (s.music, s.music_muted),
+        }
+    }
+
+    pub fn adjust(&mut self, channel: Channel, delta: f32) {
+        let s = &mut self.settings;
+        let level = match channel {
+            Channel::Master => &mut s.master,
+            Channel::Sfx => &mut s.sfx,
+            Channel::Music => &mut s.music,
+        };
+
+        // Round so repeated steps land exactly on 0% and 100%.
+        *level = ((*level + delta) * 100.0).round().clamp(0.0, 100.0) / 100.0;
+    }
+
+    pub fn toggle_mute(&mut self, channel: Channel) {
+        let s = &mut self.settings;
+        let muted = match channel {
+            Channel::Master => &mut s.master_muted,
+            Channel::Sfx => &mut s.sfx_muted,
+            Channel::Music => &mut s.music_muted,
+        };
+
+        *muted = !*muted;
+    }
+
+    pub fn play_sfx(&mut self, id: &str, clip: &SoundClip, pan: f32, pitch: f32) {
+        let volume = self.sfx_volume();
+        if volume <= 0.0 {
+            return;
+        }
+
+        let params = PlayParams { volume, pan, pitch };
+
+        // A broken device should cost us the sound, not the game. Only the
+        // first failure is reported to avoid flooding the log every bounce.
+        if let Err(e) = self.backend.play(id, clip, params) {
+            if !self.failed {
+                eprintln!("warning: could not play sound \"{}\": {}", id, e);
+                self.failed = true;
+            }
+        }
+    }
+
+    // Called every frame, `active` is false when there is no ball in play.
+    pub fn update_ball_cue(&mut self, pan: f32, pitch: f32, active: bool) {
+        let volume = self.sfx_volume();
+
+        let cue = if self.settings.ball_cue && active && volume > 0.0 {
+            Some(PlayParams { volume, pan, pitch })
+        } else {
+            None
+        };
+
+        self.backend.set_cue(cue);
+    }
+
+    pub fn start_track(&mut self, layers: &[PathBuf]) -> Result<TrackId, String> {
+        self.backend.start_track(layers)
+    }
+
+    // `levels` are relative to the music channel volume.
+    pub fn set_track_levels(&mut self, track: TrackId, levels: &[f32]) {
+        let volume = self.music_volume();
+        let volumes: Vec<f32> = levels.iter().map(|level| level * volume).collect();
+
+        self.backend.set_track_volumes(track, &volumes);
+    }
+
+    pub fn track_finished(&self, track: TrackId) -> bool {
+        self.backend.track_finished(track)
+    }
+
+    pub fn stop_track(&mut self, track: TrackId) {
+        self.backend.stop_track(track);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FailingBackend;
+
+    impl AudioBackend for FailingBackend {
+        fn play(
+            &mut self,
+            _id: &str,
+            _clip: &SoundClip,
+            _params: PlayParams,
+        ) -> Result<(), String> {
+            Err(String::from("device unplugged"))
+        }
+
+        fn set_cue(&mut self, _cue: Option<PlayParams>) {}
+
+        fn start_track(&mut self, _layers: &[PathBuf]) -> Result<TrackId, String> {
+            Err(String::from("device unplugged"))
+        }
+
+        fn set_track_volumes(&mut self, _track: TrackId, _volumes: &[f32]) {}
+
+        fn track_finished(&self, _track: TrackId) -> bool {
+            true
+        }
+
+        fn stop_track(&mut self, _track: TrackId) {}
+    }
+
+    fn clip() -> SoundClip {
+        SoundClip::new(Vec::new())
+    }
+
+    #[test]
+    fn sfx_volume_combines_master_and_sfx() {
+        let settings = MixerSettings {
+            master: 0.5,
+            sfx: 0.5,
+            ..MixerSettings::default()
+        };
+        let mut mixer = Mixer::new(settings, Box::new(NullBackend));
+
+        assert_eq!(mixer.sfx_volume(), 0.25);
+
+        mixer.toggle_mute(Channel::Master);
+        assert_eq!(mixer.sfx_volume(), 0.0);
+        assert_eq!(mixer.music_volume(), 0.0);
+    }
+
+    #[test]
+    fn adjust_clamps_to_range() {
+        let mut mixer = Mixer::new(MixerSettings::default(), Box::new(NullBackend));
+
+        mixer.adjust(Channel::Sfx, 0.1);
+        assert_eq!(mixer.settings.sfx, 1.0);
+
+        for _ in 0..15 {
+            mixer.adjust(Channel::Sfx, -0.1);
+        }
+        assert_eq!(mixer.settings.sfx, 0.0);
+    }
+
+    #[test]
+    fn muted_sfx_are_not_sent_to_the_backend() {
+        let recording = RecordingBackend::default();
+        let settings = MixerSettings {
+            sfx: 0.5,
+            ..MixerSettings::default()
+        };
+        let mut mixer = Mixer::new(settings, Box::new(recording.clone()));
+
+        mixer.play_sfx("pad", &clip(), -0.5, 1.1);
+        mixer.toggle_mute(Channel::Sfx);
+        mixer.play_sfx("wall", &clip(), 0.5, 1.0);
+
+        let params = PlayParams {
+            volume: 0.5,
+            pan: -0.5,
+            pitch: 1.1,
+        };
+        assert_eq!(recording.played(), vec![(String::from("pad"), params)]);
+    }
+
+    #[test]
+    fn panning_keeps_the_center_at_full_volume() {
+        assert_eq!(PlayParams::centered(0.5).channel_volumes(), vec![0.5, 0.5]);
+
+        let params = PlayParams {
+            pan: -0.5,
+            ..PlayParams::centered(1.0)
+        };
+        assert_eq!(params.channel_volumes(), vec![1.0, 0.5]);
+    }
+
+    #[test]
+    fn ball_cue_follows_the_setting() {
+        let recording = RecordingBackend::default();
+        let mut mixer = Mixer::new(MixerSettings::default(), Box::new(recording.clone()));
+
+        mixer.update_ball_cue(0.3, 1.0, true);
+        assert_eq!(recording.cue(), None);
+
+        mixer.settings.ball_cue = true;
+        mixer.update_ball_cue(0.3, 1.0, true);
+        assert_eq!(recording.cue().map(|cue| cue.pan), Some(0.3));
+
+        mixer.update_ball_cue(0.3, 1.0, false);
+        assert_eq!(recording.cue(), None);
+    }
+
+    #[test]
+    fn backend_errors_do_not_panic() {
+        let mut mixer = Mixer::new(MixerSettings::default(), Box::new(FailingBackend));
+
+        mixer.play_sfx("goal", &clip(), 0.0, 1.0);
+        mixer.play_sfx("goal", &clip(), 0.0, 1.0);
+
+        assert!(mixer.failed);
+    }
+
+    #[test]
+    fn stream_panics_become_errors() {
+        assert_eq!(open_stream(|| 1), Ok(1));
+        assert!(open_stream(|| -> u32 { panic!("no format") }).is_err());
+    }
+}
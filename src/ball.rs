@@ -1,8 +1,12 @@
 use rand::distributions::{Distribution, Uniform};
-use rand::thread_rng;
+use rand::Rng;
 
-const BALL_RADIUS: f32 = 8.0;
-const BALL_SPEED: f32 = 500.0;
+use ggez::graphics::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Tuning;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ball {
     pub x: f32,
     pub y: f32,
@@ -12,25 +16,42 @@ pub struct Ball {
 }
 
 impl Ball {
-    pub fn new(game_width: f32, game_height: f32) -> Ball {
-        let mut rng = thread_rng();
-
+    // Serves from the center in a direction picked by `rng`, which is seeded
+    // for repeatable matches, see the `seed` console command.
+    pub fn new(game_width: f32, game_height: f32, tuning: &Tuning, rng: &mut impl Rng) -> Ball {
         let x = game_width / 2.0;
         let y = game_height / 2.0;
 
-        let directions = vec![1, -1];
+        let directions = [1, -1];
 
         let direction_die = Uniform::new_inclusive(0, directions.len() - 1);
 
-        let dx = BALL_SPEED * directions[direction_die.sample(&mut rng)] as f32;
-        let dy = BALL_SPEED * directions[direction_die.sample(&mut rng)] as f32;
+        let dx = tuning.ball_speed * directions[direction_die.sample(rng)] as f32;
+        let dy = tuning.ball_speed * directions[direction_die.sample(rng)] as f32;
 
         Ball {
             x,
             y,
             dx,
             dy,
-            radius: BALL_RADIUS,
+            radius: tuning.ball_radius,
         }
     }
+
+    // The square used for collisions.
+    pub fn rect(&self) -> Rect {
+        Rect::new(
+            self.x - self.radius,
+            self.y - self.radius,
+            self.radius * 2.0,
+            self.radius * 2.0,
+        )
+    }
+
+    // Applies new tuning values without changing the ball's direction.
+    pub fn retune(&mut self, tuning: &Tuning) {
+        self.radius = tuning.ball_radius;
+        self.dx = tuning.ball_speed * self.dx.signum();
+        self.dy = tuning.ball_speed * self.dy.signum();
+    }
 }
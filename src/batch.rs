@@ -0,0 +1,480 @@
+use std::time::Duration;
+
+use ggez::graphics::Rect;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    arena::{
+        advance, clamp_paddle, paddle_contact, wait_for_serve, wall_contact, Arena, Side, Wall,
+        WallContact, SERVE_DELAY,
+    },
+    ball::Ball,
+    env::{features, Action, EnvConfig, Observation},
+};
+
+// Below this many arenas, handing them to another thread costs more than
+// stepping them.
+const MIN_ARENAS_PER_TASK: usize = 256;
+
+// What `Arena` keeps per match that is the same in every arena of a batch.
+struct Rules {
+    config: EnvConfig,
+    walls: Vec<Wall>,
+    // Paddles at the start of a serve, left then right.
+    paddles: [Rect; 2],
+    // Episode seeds come from these, see `episode_seed`.
+    seed: u64,
+    count: u64,
+}
+
+// Mutable slices of every per-arena column, for a range of arenas.
+struct Lanes<'a> {
+    start: usize,
+    ball_x: &'a mut [f32],
+    ball_y: &'a mut [f32],
+    ball_dx: &'a mut [f32],
+    ball_dy: &'a mut [f32],
+    paddle_y: &'a mut [[f32; 2]],
+    scores: &'a mut [[usize; 2]],
+    paused: &'a mut [Option<Duration>],
+    steps: &'a mut [u32],
+    episodes: &'a mut [u64],
+    rngs: &'a mut [StdRng],
+    observations: &'a mut [Observation],
+    rewards: &'a mut [[f32; 2]],
+    dones: &'a mut [bool],
+}
+
+// N independent arenas stepped in lockstep, stored as structure of arrays.
+// They use the same rule functions as `Arena::simulate` and give the same
+// observations, rewards and episode ends as one `Env` per arena would.
+//
+// Finished episodes start over right away with the next seed, the arena's
+// observation is then the first one of the new episode.
+pub struct BatchSim {
+    rules: Rules,
+    ball_x: Vec<f32>,
+    ball_y: Vec<f32>,
+    ball_dx: Vec<f32>,
+    ball_dy: Vec<f32>,
+    // Paddle tops, left then right.
+    paddle_y: Vec<[f32; 2]>,
+    scores: Vec<[usize; 2]>,
+    paused: Vec<Option<Duration>>,
+    steps: Vec<u32>,
+    episodes: Vec<u64>,
+    rngs: Vec<StdRng>,
+    observations: Vec<Observation>,
+    rewards: Vec<[f32; 2]>,
+    dones: Vec<bool>,
+}
+
+impl BatchSim {
+    // `count` arenas, arena `i` starts with seed `seed + i`.
+    pub fn new(config: EnvConfig, count: usize, seed: u64) -> BatchSim {
+        let template = Arena::seeded(config.width, config.height, config.tuning, seed);
+        let rules = Rules {
+            config,
+            walls: template.walls.clone(),
+            paddles: [template.paddles[0].rect, template.paddles[1].rect],
+            seed,
+            count: count as u64,
+        };
+
+        let mut batch = BatchSim {
+            rules,
+            ball_x: vec![0.0; count],
+            ball_y: vec![0.0; count],
+            ball_dx: vec![0.0; count],
+            ball_dy: vec![0.0; count],
+            paddle_y: vec![[0.0; 2]; count],
+            scores: vec![[0; 2]; count],
+            paused: vec![None; count],
+            steps: vec![0; count],
+            episodes: vec![0; count],
+            rngs: (0..count).map(|_| StdRng::seed_from_u64(0)).collect(),
+            observations: vec![[0.0; 8]; count],
+            rewards: vec![[0.0; 2]; count],
+            dones: vec![false; count],
+        };
+
+        let (rules, mut lanes) = batch.lanes();
+        for i in 0..count {
+            lanes.reset(rules, i);
+        }
+
+        batch
+    }
+
+    pub fn config(&self) -> &EnvConfig {
+        &self.rules.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.ball_x.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ball_x.is_empty()
+    }
+
+    pub fn observations(&self) -> &[Observation] {
+        &self.observations
+    }
+
+    // Rewards and episode ends of the last step.
+    pub fn rewards(&self) -> &[[f32; 2]] {
+        &self.rewards
+    }
+
+    pub fn dones(&self) -> &[bool] {
+        &self.dones
+    }
+
+    // Moves the paddles of arena `i` as told by `actions[i]` for one step,
+    // on rayon's thread pool.
+    pub fn step(&mut self, actions: &[[Action; 2]]) {
+        assert_eq!(actions.len(), self.len(), "one pair of actions per arena");
+
+        let (rules, lanes) = self.lanes();
+        lanes.step_parallel(rules, actions);
+    }
+
+    fn lanes(&mut self) -> (&Rules, Lanes<'_>) {
+        let lanes = Lanes {
+            start: 0,
+            ball_x: &mut self.ball_x,
+            ball_y: &mut self.ball_y,
+            ball_dx: &mut self.ball_dx,
+            ball_dy: &mut self.ball_dy,
+            paddle_y: &mut self.paddle_y,
+            scores: &mut self.scores,
+            paused: &mut self.paused,
+            steps: &mut self.steps,
+            episodes: &mut self.episodes,
+            rngs: &mut self.rngs,
+            observations: &mut self.observations,
+            rewards: &mut self.rewards,
+            dones: &mut self.dones,
+        };
+
+        (&self.rules, lanes)
+    }
+}
+
+// Seed of arena `index`'s `episode`, different for every arena and episode.
+pub fn episode_seed(seed: u64, count: u64, index: usize, episode: u64) -> u64 {
+    seed.wrapping_add(episode.wrapping_mul(count))
+        .wrapping_add(index as u64)
+}
+
+impl<'a> Lanes<'a> {
+    fn split_at(self, mid: usize) -> (Lanes<'a>, Lanes<'a>) {
+        let (ball_x, ball_x_tail) = self.ball_x.split_at_mut(mid);
+        let (ball_y, ball_y_tail) = self.ball_y.split_at_mut(mid);
+        let (ball_dx, ball_dx_tail) = self.ball_dx.split_at_mut(mid);
+        let (ball_dy, ball_dy_tail) = self.ball_dy.split_at_mut(mid);
+        let (paddle_y, paddle_y_tail) = self.paddle_y.split_at_mut(mid);
+        let (scores, scores_tail) = self.scores.split_at_mut(mid);
+        let (paused, paused_tail) = self.paused.split_at_mut(mid);
+        let (steps, steps_tail) = self.steps.split_at_mut(mid);
+        let (episodes, episodes_tail) = self.episodes.split_at_mut(mid);
+        let (rngs, rngs_tail) = self.rngs.split_at_mut(mid);
+        let (observations, observations_tail) = self.observations.split_at_mut(mid);
+        let (rewards, rewards_tail) = self.rewards.split_at_mut(mid);
+        let (dones, dones_tail) = self.dones.split_at_mut(mid);
+
+        let head = Lanes {
+            start: self.start,
+            ball_x,
+            ball_y,
+            ball_dx,
+            ball_dy,
+            paddle_y,
+            scores,
+            paused,
+            steps,
+            episodes,
+            rngs,
+            observations,
+            rewards,
+            dones,
+        };
+        let tail = Lanes {
+            start: self.start + mid,
+            ball_x: ball_x_tail,
+            ball_y: ball_y_tail,
+            ball_dx: ball_dx_tail,
+            ball_dy: ball_dy_tail,
+            paddle_y: paddle_y_tail,
+            scores: scores_tail,
+            paused: paused_tail,
+            steps: steps_tail,
+            episodes: episodes_tail,
+            rngs: rngs_tail,
+            observations: observations_tail,
+            rewards: rewards_tail,
+            dones: dones_tail,
+        };
+
+        (head, tail)
+    }
+
+    // Halves the lanes until they are small enough to step on one thread.
+    fn step_parallel(self, rules: &Rules, actions: &[[Action; 2]]) {
+        if actions.len() <= MIN_ARENAS_PER_TASK {
+            self.step(rules, actions);
+            return;
+        }
+
+        let mid = actions.len() / 2;
+        let (head, tail) = self.split_at(mid);
+        let (head_actions, tail_actions) = actions.split_at(mid);
+
+        rayon::join(
+            || head.step_parallel(rules, head_actions),
+            || tail.step_parallel(rules, tail_actions),
+        );
+    }
+
+    fn step(mut self, rules: &Rules, actions: &[[Action; 2]]) {
+        let config = &rules.config;
+
+        for (i, actions) in actions.iter().enumerate() {
+            self.rewards[i] = [0.0; 2];
+            self.simulate(rules, i, *actions);
+            self.steps[i] += 1;
+
+            let winning_score = config.tuning.winning_score;
+            let won = self.scores[i].iter().any(|score| *score >= winning_score);
+            let truncated = config.max_steps > 0 && self.steps[i] >= config.max_steps;
+            self.dones[i] = won || truncated;
+
+            if self.dones[i] {
+                self.episodes[i] += 1;
+                self.reset(rules, i);
+            } else {
+                self.observe(rules, i);
+            }
+        }
+    }
+
+    // Starts arena `i`'s next episode, like `Env::reset`.
+    fn reset(&mut self, rules: &Rules, i: usize) {
+        let seed = episode_seed(rules.seed, rules.count, self.start + i, self.episodes[i]);
+        self.rngs[i] = StdRng::seed_from_u64(seed);
+        self.serve(rules, i);
+        self.scores[i] = [0; 2];
+        self.paused[i] = None;
+        self.steps[i] = 0;
+
+        self.observe(rules, i);
+    }
+
+    // New ball and centered paddles, like `Arena::reset`.
+    fn serve(&mut self, rules: &Rules, i: usize) {
+        let config = &rules.config;
+        let ball = Ball::new(
+            config.width,
+            config.height,
+            &config.tuning,
+            &mut self.rngs[i],
+        );
+
+        self.ball_x[i] = ball.x;
+        self.ball_y[i] = ball.y;
+        self.ball_dx[i] = ball.dx;
+        self.ball_dy[i] = ball.dy;
+        self.paddle_y[i] = [rules.paddles[0].y, rules.paddles[1].y];
+    }
+
+    fn observe(&mut self, rules: &Rules, i: usize) {
+        let config = &rules.config;
+        let half_height = |side: usize| rules.paddles[side].h / 2.0;
+
+        self.observations[i] = features(
+            (config.width, config.height),
+            &config.tuning,
+            [
+                self.ball_x[i],
+                self.ball_y[i],
+                self.ball_dx[i],
+                self.ball_dy[i],
+            ],
+            [
+                self.paddle_y[i][0] + half_height(0),
+                self.paddle_y[i][1] + half_height(1),
+            ],
+            self.scores[i],
+        );
+    }
+
+    // `Arena::simulate` for arena `i`, without god mode or stats.
+    fn simulate(&mut self, rules: &Rules, i: usize, actions: [Action; 2]) {
+        let config = &rules.config;
+        let time = config.dt;
+
+        if wait_for_serve(&mut self.paused[i], time) {
+            return;
+        }
+
+        // Paddles
+        for (side, action) in actions.iter().enumerate() {
+            let velocity = match action {
+                Action::Stay => 0.0,
+                Action::Up => -config.tuning.paddle_speed,
+                Action::Down => config.tuning.paddle_speed,
+            };
+
+            let mut paddle = rules.paddles[side];
+            paddle.y = advance(self.paddle_y[i][side], velocity, time);
+            self.paddle_y[i][side] = clamp_paddle(paddle, &rules.walls, config.height);
+        }
+
+        // Ball
+        let mut ball = self.ball(rules, i);
+        ball.x = advance(ball.x, ball.dx, time);
+        ball.y = advance(ball.y, ball.dy, time);
+
+        let ball_rect = ball.rect();
+
+        for wall in rules.walls.iter() {
+            if let Some(WallContact::Goal { scorer }) =
+                wall_contact(&mut ball, ball_rect, wall, config.height)
+            {
+                self.goal(rules, i, scorer - 1);
+                ball = self.ball(rules, i);
+            }
+        }
+
+        for (side, wall_side) in [Side::Left, Side::Right].iter().enumerate() {
+            let mut paddle = rules.paddles[side];
+            paddle.y = self.paddle_y[i][side];
+
+            let bounce_angle = config.tuning.bounce_angle;
+            if paddle_contact(&mut ball, ball_rect, paddle, *wall_side, bounce_angle) {
+                self.rewards[i][side] += config.rewards.hit_bonus;
+            }
+        }
+
+        self.ball_x[i] = ball.x;
+        self.ball_y[i] = ball.y;
+        self.ball_dx[i] = ball.dx;
+        self.ball_dy[i] = ball.dy;
+    }
+
+    // Arena `i`'s ball, as a `Ball` for the shared rules.
+    fn ball(&self, rules: &Rules, i: usize) -> Ball {
+        Ball {
+            x: self.ball_x[i],
+            y: self.ball_y[i],
+            dx: self.ball_dx[i],
+            dy: self.ball_dy[i],
+            radius: rules.config.tuning.ball_radius,
+        }
+    }
+
+    // Paddle `scored` (0 left, 1 right) scored in arena `i`.
+    fn goal(&mut self, rules: &Rules, i: usize, scored: usize) {
+        let goal = rules.config.rewards.goal;
+
+        self.scores[i][scored] += 1;
+        self.rewards[i][scored] += goal;
+        self.rewards[i][1 - scored] -= goal;
+
+        self.serve(rules, i);
+        self.paused[i] = Some(SERVE_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Tuning,
+        env::{follow_ball, Env},
+    };
+
+    fn actions(observations: &[Observation]) -> Vec<[Action; 2]> {
+        observations
+            .iter()
+            .enumerate()
+            .map(|(i, observation)| match i % 2 {
+                0 => [follow_ball(observation, 0), follow_ball(observation, 1)],
+                _ => [follow_ball(observation, 0), Action::Up],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_one_env_per_arena() {
+        // Classic and angled paddle bounces.
+        for bounce_angle in [0.0, 45.0] {
+            let config = EnvConfig {
+                max_steps: 1500,
+                tuning: Tuning {
+                    bounce_angle,
+                    ..Tuning::default()
+                },
+                ..EnvConfig::default()
+            };
+            let (count, seed) = (3, 10);
+            let mut batch = BatchSim::new(config, count, seed);
+            let mut envs: Vec<Env> = (0..count).map(|_| Env::new(config)).collect();
+            let mut episodes = vec![0; count];
+            let mut observations: Vec<Observation> = envs
+                .iter_mut()
+                .enumerate()
+                .map(|(i, env)| env.reset(episode_seed(seed, count as u64, i, 0)))
+                .collect();
+            let mut resets = 0;
+
+            assert_eq!(batch.observations(), &observations[..]);
+
+            for _ in 0..5000 {
+                let actions = actions(&observations);
+                batch.step(&actions);
+
+                for (i, env) in envs.iter_mut().enumerate() {
+                    let (observation, reward, done, _) = env.step(actions[i]);
+                    observations[i] = observation;
+
+                    if done {
+                        episodes[i] += 1;
+                        resets += 1;
+                        observations[i] =
+                            env.reset(episode_seed(seed, count as u64, i, episodes[i]));
+                    }
+
+                    assert_eq!(batch.rewards()[i], reward);
+                    assert_eq!(batch.dones()[i], done);
+                }
+
+                assert_eq!(batch.observations(), &observations[..]);
+            }
+
+            assert!(resets >= count);
+        }
+    }
+
+    #[test]
+    fn threads_do_not_change_the_results() {
+        let run = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let mut batch = BatchSim::new(EnvConfig::default(), 1000, 0);
+
+            pool.install(|| {
+                for _ in 0..300 {
+                    let actions = actions(batch.observations());
+                    batch.step(&actions);
+                }
+            });
+            batch.observations().to_vec()
+        };
+
+        assert_eq!(run(1), run(3));
+    }
+}
@@ -0,0 +1,113 @@
+use ggez::{Context, GameResult};
+
+use std::time::Instant;
+
+use crate::{
+    alloc_counter,
+    batch::BatchSim,
+    env::{follow_ball, Action, Env, EnvConfig},
+    game_state::GameState,
+    menu::MenuPage,
+    scene::Transition,
+    scenes::{GameScene, MenuScene},
+};
+
+const WARMUP_FRAMES: usize = 10;
+
+// Batch sizes compared by `run_batch_benchmark`.
+const BATCH_SIZES: [usize; 3] = [1, 64, 1024];
+
+// Draws `frames` frames of the game and the menu with the render cache off
+// and on, and prints the average number of allocations per drawn frame.
+pub fn run_frame_benchmark(ctx: &mut Context, game: &mut GameState, frames: usize) -> GameResult {
+    for screen in ["game", "menu"].iter() {
+        let transition = match *screen {
+            "game" => Transition::Reset(Box::new(GameScene::new_match())),
+            _ => Transition::Push(Box::new(MenuScene::new(MenuPage::Pause))),
+        };
+        game.with_scenes(|scenes, game| scenes.apply(game, ctx, transition));
+
+        for enabled in [false, true].iter() {
+            game.render_cache.enabled = *enabled;
+
+            for _ in 0..WARMUP_FRAMES {
+                draw_frame(ctx, game)?;
+            }
+
+            let before = alloc_counter::allocations();
+            for _ in 0..frames {
+                draw_frame(ctx, game)?;
+            }
+            let per_frame = (alloc_counter::allocations() - before) as f64 / frames as f64;
+
+            println!(
+                "{:<5} render cache {:<3}: {:>8.1} allocations per frame",
+                screen,
+                if *enabled { "on" } else { "off" },
+                per_frame
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn draw_frame(ctx: &mut Context, game: &mut GameState) -> GameResult {
+    ctx.timer_context.tick();
+    game.draw(ctx)
+}
+
+// Steps the headless environment `steps` times with both paddles following
+// the ball and prints the steps per second. Needs no window.
+pub fn run_env_benchmark(steps: usize) {
+    let mut env = Env::new(EnvConfig::default());
+    let mut observation = env.reset(0);
+    let mut episodes = 0;
+
+    let start = Instant::now();
+    for _ in 0..steps {
+        let actions = [follow_ball(&observation, 0), follow_ball(&observation, 1)];
+        let (next, _, done, _) = env.step(actions);
+        observation = next;
+
+        if done {
+            episodes += 1;
+            observation = env.reset(episodes);
+        }
+    }
+    let seconds = start.elapsed().as_secs_f64();
+
+    println!(
+        "{} steps, {} episodes in {:.3}s: {:.0} steps per second",
+        steps,
+        episodes,
+        seconds,
+        steps as f64 / seconds
+    );
+}
+
+// Steps a `BatchSim` of each size in `BATCH_SIZES` until `steps` arena steps
+// have run, and prints the arena steps per second.
+pub fn run_batch_benchmark(steps: usize) {
+    for count in BATCH_SIZES.iter() {
+        let mut batch = BatchSim::new(EnvConfig::default(), *count, 0);
+        let mut actions = vec![[Action::Stay; 2]; *count];
+        let rounds = (steps / count).max(1);
+
+        let start = Instant::now();
+        for _ in 0..rounds {
+            for (actions, observation) in actions.iter_mut().zip(batch.observations()) {
+                *actions = [follow_ball(observation, 0), follow_ball(observation, 1)];
+            }
+            batch.step(&actions);
+        }
+        let seconds = start.elapsed().as_secs_f64();
+
+        println!(
+            "N = {:>4}, {} threads: {:>12.0} steps per second",
+            count,
+            rayon::current_num_threads(),
+            (rounds * count) as f64 / seconds
+        );
+    }
+}
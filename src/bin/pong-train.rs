@@ -0,0 +1,104 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use ggezgame::evolve::{Population, TrainConfig, CSV_HEADER};
+
+// Evolves networks that play a paddle, without a window. See "Training" in
+// the README.
+//
+// pong-train [--config <train.toml>] [--resume <checkpoint.toml>]
+//            [--out <dir>] [--generations <N>]
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let value = |flag: &str| match args.iter().position(|arg| arg == flag) {
+        Some(i) => match args.get(i + 1) {
+            Some(value) => Some(value.clone()),
+            None => fail(&format!("{} needs a value", flag)),
+        },
+        None => None,
+    };
+
+    let out = PathBuf::from(value("--out").unwrap_or_else(|| "train".to_string()));
+
+    let mut population = match (value("--resume"), value("--config")) {
+        (Some(path), _) => Population::load(Path::new(&path)),
+        (None, Some(path)) => fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| toml::from_str::<TrainConfig>(&text).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", path, e))
+            .and_then(Population::new),
+        (None, None) => Population::new(TrainConfig::default()),
+    }
+    .unwrap_or_else(|e| fail(&e));
+
+    // The total to reach, also when resuming.
+    if let Some(generations) = value("--generations") {
+        population.config.generations = generations
+            .parse()
+            .unwrap_or_else(|_| fail("--generations needs a number"));
+    }
+
+    if let Err(e) = train(&mut population, &out) {
+        fail(&e);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+// Runs the remaining generations, logging each to `fitness.csv` and saving
+// the champion, with a checkpoint and a numbered champion every
+// `checkpoint_every` generations and at the end.
+fn train(population: &mut Population, out: &Path) -> Result<(), String> {
+    fs::create_dir_all(out).map_err(|e| format!("{}: {}", out.display(), e))?;
+
+    let log_path = out.join("fitness.csv");
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .map_err(|e| format!("{}: {}", log_path.display(), e))?;
+    if log.metadata().map(|m| m.len() == 0).unwrap_or(false) {
+        writeln!(log, "{}", CSV_HEADER).map_err(|e| e.to_string())?;
+    }
+
+    println!(
+        "Training {} networks of {} weights from generation {} to {}, writing to {}",
+        population.config.population,
+        population.config.genome_len(),
+        population.generation,
+        population.config.generations,
+        out.display()
+    );
+
+    while population.generation < population.config.generations {
+        let start = Instant::now();
+        let stats = population.evolve();
+        let seconds = start.elapsed().as_secs_f64();
+
+        writeln!(log, "{}", stats.csv_row(seconds)).map_err(|e| e.to_string())?;
+        println!(
+            "generation {}: best {:.2}, mean {:.2}, worst {:.2} ({:.1}s)",
+            stats.generation, stats.best, stats.mean, stats.worst, seconds
+        );
+
+        stats.champion.save(&out.join("champion.mlp"))?;
+
+        let every = population.config.checkpoint_every;
+        let last = population.generation == population.config.generations;
+        if last || (every > 0 && stats.generation.is_multiple_of(every)) {
+            population.save(&out.join("checkpoint.toml"))?;
+            stats
+                .champion
+                .save(&out.join(format!("champion-{}.mlp", stats.generation)))?;
+        }
+    }
+
+    Ok(())
+}
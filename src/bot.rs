@@ -0,0 +1,571 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rand::{thread_rng, Rng};
+
+use crate::{
+    ai::{self, Ai, PRESETS},
+    arena::Arena,
+    env::Action,
+    game_state::GameState,
+    mlp::{self, NeuralAi},
+    toast::Toast,
+};
+
+// Sent in the greeting, bots can refuse versions they don't speak.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+// Time a bot has to answer the greeting, e.g. to start an interpreter.
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Most a simulation step waits for the bots' replies, for both together.
+// Bots that take longer keep their last move until their reply arrives.
+pub const TICK_BUDGET: Duration = Duration::from_millis(4);
+
+// Controls starting with this are played by the built-in AI instead of a
+// program, e.g. "ai:sniper" for that preset, see `ai.rs`.
+pub const AI_PREFIX: &str = "ai:";
+
+// Controls starting with this are played by a network from a weights file,
+// e.g. "nn:follower" for `resources/agents/follower.mlp`, see `mlp.rs`.
+pub const NETWORK_PREFIX: &str = "nn:";
+
+// A program playing one paddle over stdin/stdout, see "Bots" in the README.
+// The game never waits long for a reply: the paddle keeps its last move
+// until the bot answers, and a bot that is slower than its timeout loses the
+// match instead of stalling the game.
+pub struct Bot {
+    pub command: String,
+    child: Child,
+    stdin: ChildStdin,
+    // Lines the bot printed, read on their own thread so waiting can time out.
+    lines: Receiver<String>,
+    timeout: Duration,
+    // When the greeting or the last tick was sent, `None` while no reply is due.
+    waiting_since: Option<Instant>,
+    ready: bool,
+    action: Action,
+}
+
+impl Bot {
+    // `command` is a program and its arguments, split on whitespace.
+    pub fn start(
+        command: &str,
+        paddle: usize,
+        arena: &Arena,
+        timeout: Duration,
+    ) -> Result<Bot, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("no command")?;
+
+        let mut process = Command::new(program);
+        process.args(words);
+
+        Bot::launch(command, process, paddle, arena, timeout)
+    }
+
+    fn launch(
+        command: &str,
+        mut process: Command,
+        paddle: usize,
+        arena: &Arena,
+        timeout: Duration,
+    ) -> Result<Bot, String> {
+        // Stderr is left alone so bots can print their own logs there.
+        let mut child = process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not start: {}", e))?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut bot = Bot {
+            command: command.to_string(),
+            child,
+            stdin,
+            lines,
+            timeout,
+            waiting_since: None,
+            ready: false,
+            action: Action::Stay,
+        };
+
+        bot.send(&greeting(paddle, arena))?;
+        bot.waiting_since = Some(Instant::now());
+
+        Ok(bot)
+    }
+
+    // Whether the bot answered the greeting yet, without waiting for it.
+    pub fn poll_ready(&mut self) -> Result<bool, String> {
+        if self.ready {
+            return Ok(true);
+        }
+
+        match self.receive(Instant::now(), STARTUP_TIMEOUT)? {
+            Some(reply) if reply.trim() == "ready" => {
+                self.ready = true;
+                Ok(true)
+            }
+            Some(reply) => Err(format!("expected \"ready\", got \"{}\"", reply.trim())),
+            None => Ok(false),
+        }
+    }
+
+    // Sends the state of `arena` unless the last tick is still unanswered,
+    // and returns the bot's latest move. Waits for the reply until
+    // `deadline` at most. Call once `poll_ready` is true.
+    pub fn tick(&mut self, arena: &Arena, deadline: Instant) -> Result<Action, String> {
+        if self.waiting_since.is_none() {
+            self.send(&tick_line(arena))?;
+            self.waiting_since = Some(Instant::now());
+        }
+
+        if let Some(reply) = self.receive(deadline, self.timeout)? {
+            self.action = parse_reply(&reply)?;
+        }
+
+        Ok(self.action)
+    }
+
+    // Tells the bot who won, it is stopped when dropped.
+    pub fn end(&mut self, winner: Option<usize>) {
+        let winner = match winner {
+            Some(1) => "left",
+            Some(_) => "right",
+            None => "none",
+        };
+
+        let _ = self.send(&format!("end {}", winner));
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| String::from("exited"))
+    }
+
+    // The reply if it arrives before `deadline`, an error once it is more
+    // than `timeout` late.
+    fn receive(&mut self, deadline: Instant, timeout: Duration) -> Result<Option<String>, String> {
+        let wait = deadline.saturating_duration_since(Instant::now());
+
+        match self.lines.recv_timeout(wait) {
+            Ok(line) => {
+                self.waiting_since = None;
+                Ok(Some(line))
+            }
+            Err(RecvTimeoutError::Timeout) => match self.waiting_since {
+                Some(since) if since.elapsed() > timeout => {
+                    Err(format!("no reply within {} ms", timeout.as_millis()))
+                }
+                _ => Ok(None),
+            },
+            Err(RecvTimeoutError::Disconnected) => Err(String::from("exited")),
+        }
+    }
+}
+
+impl Drop for Bot {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// `pong <version> <side> <width> <height> <paddle height> <ball radius>
+// <winning score>`
+pub fn greeting(paddle: usize, arena: &Arena) -> String {
+    let side = match paddle {
+        0 => "left",
+        _ => "right",
+    };
+
+    format!(
+        "pong {} {} {} {} {} {} {}",
+        PROTOCOL_VERSION,
+        side,
+        arena.width,
+        arena.height,
+        arena.tuning.paddle_height,
+        arena.tuning.ball_radius,
+        arena.tuning.winning_score
+    )
+}
+
+// `tick <time> <ball x> <ball y> <ball dx> <ball dy> <left paddle y> <right
+// paddle y> <left score> <right score>`, paddle positions are their centers.
+pub fn tick_line(arena: &Arena) -> String {
+    let ball = &arena.ball;
+    let center = |paddle: usize| {
+        let rect = arena.paddles[paddle].rect;
+        rect.y + rect.h / 2.0
+    };
+
+    format!(
+        "tick {:.3} {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} {} {}",
+        arena.stats.play_time,
+        ball.x,
+        ball.y,
+        ball.dx,
+        ball.dy,
+        center(0),
+        center(1),
+        arena.player1_score,
+        arena.player2_score
+    )
+}
+
+// `UP`, `DOWN` or `STAY`, in any case.
+pub fn parse_reply(reply: &str) -> Result<Action, String> {
+    Action::from_name(&reply.trim().to_lowercase())
+        .ok_or_else(|| format!("unknown command \"{}\"", reply.trim()))
+}
+
+// A player that gave up, or whose bot broke the protocol.
+pub struct Forfeit {
+    // 1 or 2, like `Arena::winner`.
+    pub player: usize,
+    pub reason: String,
+}
+
+// A player that runs inside the game instead of as a program.
+#[derive(Clone)]
+pub enum Builtin {
+    Ai(Box<Ai>),
+    Network(NeuralAi),
+}
+
+impl Builtin {
+    // The player for an "ai:" or "nn:" command, None for other commands,
+    // which are programs.
+    pub fn from_command(
+        command: &str,
+        paddle: usize,
+        seed: u64,
+    ) -> Option<Result<Builtin, String>> {
+        if let Some(name) = command.strip_prefix(AI_PREFIX) {
+            let builtin = match ai::preset(name) {
+                Some(personality) => Ok(Builtin::Ai(Box::new(Ai::new(personality, paddle, seed)))),
+                None => Err(format!("no AI called \"{}\"", name)),
+            };
+            return Some(builtin);
+        }
+
+        let name = command.strip_prefix(NETWORK_PREFIX)?;
+        Some(NeuralAi::load(&mlp::agent_path(name), paddle).map(Builtin::Network))
+    }
+
+    // The move for the next `dt` seconds.
+    pub fn act(&mut self, arena: &Arena, dt: f64) -> Action {
+        match self {
+            Builtin::Ai(ai) => ai.act(arena, dt),
+            Builtin::Network(network) => network.act(arena),
+        }
+    }
+}
+
+impl GameState {
+    // Starts the bots and AIs chosen for each side, replacing running ones.
+    // A bot that doesn't start forfeits the match.
+    pub fn start_bots(&mut self) {
+        self.stop_bots();
+        self.forfeit = None;
+
+        let timeout = Duration::from_millis(self.config.bots.timeout_ms);
+        for paddle in 0..2 {
+            let command = self.bot_commands[paddle].clone();
+            if command.trim().is_empty() {
+                continue;
+            }
+
+            match Builtin::from_command(&command, paddle, thread_rng().gen()) {
+                Some(Ok(builtin)) => {
+                    self.ais[paddle] = Some(builtin);
+                    continue;
+                }
+                Some(Err(reason)) => {
+                    self.forfeit(paddle, &command, reason);
+                    break;
+                }
+                None => {}
+            }
+
+            match Bot::start(&command, paddle, &self.arena, timeout) {
+                Ok(bot) => self.bots[paddle] = Some(bot),
+                Err(reason) => {
+                    self.forfeit(paddle, &command, reason);
+                    break;
+                }
+            }
+        }
+
+        self.difficulty.changed = None;
+        self.apply_difficulty();
+    }
+
+    pub fn stop_bots(&mut self) {
+        let winner = self.match_winner();
+        if self.difficulty_opponent().is_some() {
+            self.remember_difficulty();
+        }
+        self.ais = [None, None];
+
+        for bot in self.bots.iter_mut() {
+            if let Some(mut bot) = bot.take() {
+                bot.end(winner);
+            }
+        }
+    }
+
+    // Whether every bot answered its greeting, checked without waiting so
+    // slow starting bots don't freeze the window. A bot that fails forfeits.
+    pub fn bots_ready(&mut self) -> bool {
+        let mut ready = true;
+
+        for paddle in 0..2 {
+            let bot = match self.bots[paddle].as_mut() {
+                Some(bot) if self.forfeit.is_none() => bot,
+                _ => continue,
+            };
+
+            match bot.poll_ready() {
+                Ok(bot_ready) => ready &= bot_ready,
+                Err(reason) => {
+                    let command = bot.command.clone();
+                    self.bots[paddle] = None;
+                    self.forfeit(paddle, &command, reason);
+                }
+            }
+        }
+
+        ready
+    }
+
+    // Asks each bot and AI for its move for the next `time` seconds and
+    // holds its paddle's keys for it.
+    pub fn drive_bots(&mut self, time: f64) {
+        let deadline = Instant::now() + TICK_BUDGET;
+
+        for paddle in 0..2 {
+            if let Some(ai) = self.ais[paddle].as_mut() {
+                let action = ai.act(&self.arena, time);
+                let paddle = &mut self.arena.paddles[paddle];
+                paddle.is_up_holding = action == Action::Up;
+                paddle.is_down_holding = action == Action::Down;
+            }
+
+            let bot = match self.bots[paddle].as_mut() {
+                Some(bot) if self.forfeit.is_none() => bot,
+                _ => continue,
+            };
+
+            match bot.tick(&self.arena, deadline) {
+                Ok(action) => {
+                    let paddle = &mut self.arena.paddles[paddle];
+                    paddle.is_up_holding = action == Action::Up;
+                    paddle.is_down_holding = action == Action::Down;
+                }
+                Err(reason) => {
+                    let command = bot.command.clone();
+                    self.bots[paddle] = None;
+                    self.forfeit(paddle, &command, reason);
+                }
+            }
+        }
+    }
+
+    pub fn is_bot(&self, paddle: usize) -> bool {
+        self.bots[paddle].is_some() || self.ais[paddle].is_some()
+    }
+
+    // The other player wins, the game over screen shows the reason.
+    fn forfeit(&mut self, paddle: usize, command: &str, reason: String) {
+        let player = paddle + 1;
+        eprintln!(
+            "{} ({}) forfeits: {}",
+            self.player_name(player),
+            command,
+            reason
+        );
+
+        self.toasts.push(Toast::error(format!(
+            "{} forfeits: {}",
+            self.player_name(player),
+            reason
+        )));
+        self.forfeit = Some(Forfeit { player, reason });
+    }
+
+    // Like `Arena::winner`, but a forfeit also ends the match.
+    pub fn match_winner(&self) -> Option<usize> {
+        match &self.forfeit {
+            Some(forfeit) => Some(3 - forfeit.player),
+            None => self.arena.winner(),
+        }
+    }
+
+    // What can play `paddle`: a human (empty), the AI presets, the networks
+    // in the agent directory, the configured bots, and the current command
+    // if it came from the command line.
+    pub fn bot_options(&self, paddle: usize) -> Vec<String> {
+        let mut options = vec![String::new()];
+        options.extend(
+            PRESETS
+                .iter()
+                .map(|(name, _)| format!("{}{}", AI_PREFIX, name)),
+        );
+        options.extend(
+            mlp::agent_names()
+                .iter()
+                .map(|name| format!("{}{}", NETWORK_PREFIX, name)),
+        );
+        options.extend(self.config.bots.commands.iter().cloned());
+
+        let current = &self.bot_commands[paddle];
+        if !options.contains(current) {
+            options.push(current.clone());
+        }
+
+        options
+    }
+
+    // `index` into `bot_options`, used from the next match on.
+    pub fn choose_bot(&mut self, paddle: usize, index: usize) {
+        if let Some(command) = self.bot_options(paddle).get(index) {
+            self.set_bot_command(paddle, command.clone());
+        }
+    }
+
+    // Empty for a human, otherwise the bot's command line.
+    pub fn set_bot_command(&mut self, paddle: usize, command: String) {
+        match paddle {
+            0 => self.config.bots.left = command.clone(),
+            _ => self.config.bots.right = command.clone(),
+        }
+        self.bot_commands[paddle] = command;
+
+        self.save_config();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Tuning;
+
+    fn arena() -> Arena {
+        Arena::seeded(1280.0, 720.0, Tuning::default(), 1)
+    }
+
+    #[test]
+    fn greeting_and_ticks_describe_the_arena() {
+        let mut arena = arena();
+        arena.ball.x = 100.0;
+        arena.ball.y = 200.5;
+        arena.ball.dx = -500.0;
+        arena.ball.dy = 250.0;
+        arena.paddles[0].rect.y = 10.0;
+        arena.player2_score = 3;
+
+        assert_eq!(greeting(1, &arena), "pong 1 right 1280 720 100 8 10");
+        assert_eq!(
+            tick_line(&arena),
+            "tick 0.000 100.00 200.50 -500.00 250.00 60.00 360.00 0 3"
+        );
+    }
+
+    #[test]
+    fn replies_are_moves() {
+        assert_eq!(parse_reply("UP"), Ok(Action::Up));
+        assert_eq!(parse_reply("down\r"), Ok(Action::Down));
+        assert_eq!(parse_reply(" Stay "), Ok(Action::Stay));
+        assert_eq!(
+            parse_reply("JUMP"),
+            Err(String::from("unknown command \"JUMP\""))
+        );
+    }
+
+    #[cfg(unix)]
+    fn shell_bot(script: &str) -> Result<Bot, String> {
+        let mut process = Command::new("sh");
+        process.args(["-c", script]);
+
+        let mut bot = Bot::launch(script, process, 0, &arena(), Duration::from_millis(200))?;
+        while !bot.poll_ready()? {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        Ok(bot)
+    }
+
+    fn in_ms(ms: u64) -> Instant {
+        Instant::now() + Duration::from_millis(ms)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bots_play_over_stdin_and_stdout() {
+        let script = "read greeting; echo ready; while read line; do echo DOWN; done";
+        let mut bot = shell_bot(script).unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(bot.tick(&arena(), in_ms(1000)), Ok(Action::Down));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn slow_bots_keep_their_last_move() {
+        let script = "read greeting; echo ready; while read line; do sleep 0.1; echo UP; done";
+        let mut bot = shell_bot(script).unwrap();
+
+        let start = Instant::now();
+        assert_eq!(bot.tick(&arena(), Instant::now()), Ok(Action::Stay));
+        assert_eq!(bot.tick(&arena(), Instant::now()), Ok(Action::Stay));
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        assert_eq!(bot.tick(&arena(), in_ms(1000)), Ok(Action::Up));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn misbehaving_bots_give_a_reason() {
+        let reason = |script| {
+            let mut bot = shell_bot(script)?;
+            bot.tick(&arena(), in_ms(300)).map(|_| ())
+        };
+
+        assert_eq!(
+            reason("read greeting; echo hello"),
+            Err(String::from("expected \"ready\", got \"hello\""))
+        );
+        assert_eq!(
+            reason("read greeting; echo ready; read tick; echo JUMP"),
+            Err(String::from("unknown command \"JUMP\""))
+        );
+        assert_eq!(
+            reason("read greeting; echo ready; sleep 5"),
+            Err(String::from("no reply within 200 ms"))
+        );
+        assert_eq!(reason("exit 0"), Err(String::from("exited")));
+        assert!(Bot::start("./no-such-bot", 0, &arena(), Duration::from_millis(1)).is_err());
+    }
+}
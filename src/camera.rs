@@ -0,0 +1,138 @@
+use ggez::{
+    graphics::{self, Rect},
+    Context, GameResult,
+};
+
+// Maps the fixed logical arena onto the window. The arena is scaled to fit
+// and centered, the rest of the window is letterboxed.
+pub struct Camera {
+    arena_width: f32,
+    arena_height: f32,
+    window_width: f32,
+    window_height: f32,
+}
+
+impl Camera {
+    pub fn new(arena_width: f32, arena_height: f32) -> Camera {
+        Camera {
+            arena_width,
+            arena_height,
+            window_width: arena_width,
+            window_height: arena_height,
+        }
+    }
+
+    pub fn resize(&mut self, window_width: f32, window_height: f32) {
+        // Minimized windows report a zero size.
+        if window_width > 0.0 && window_height > 0.0 {
+            self.window_width = window_width;
+            self.window_height = window_height;
+        }
+    }
+
+    // Window pixels per arena unit.
+    pub fn scale(&self) -> f32 {
+        (self.window_width / self.arena_width).min(self.window_height / self.arena_height)
+    }
+
+    // The part of the logical space that covers the whole window.
+    pub fn screen_rect(&self) -> Rect {
+        let scale = self.scale();
+        let w = self.window_width / scale;
+        let h = self.window_height / scale;
+
+        Rect::new(
+            (self.arena_width - w) / 2.0,
+            (self.arena_height - h) / 2.0,
+            w,
+            h,
+        )
+    }
+
+    // Letterbox bars in logical coordinates, empty when the aspect ratios match.
+    pub fn bars(&self) -> Vec<Rect> {
+        let screen = self.screen_rect();
+        let mut bars = Vec::new();
+
+        if screen.x < 0.0 {
+            bars.push(Rect::new(screen.x, screen.y, -screen.x, screen.h));
+            bars.push(Rect::new(self.arena_width, screen.y, -screen.x, screen.h));
+        }
+        if screen.y < 0.0 {
+            bars.push(Rect::new(screen.x, screen.y, screen.w, -screen.y));
+            bars.push(Rect::new(screen.x, self.arena_height, screen.w, -screen.y));
+        }
+
+        bars
+    }
+
+    // Converts a window position, e.g. the mouse, to arena coordinates.
+    pub fn to_arena(&self, x: f32, y: f32) -> (f32, f32) {
+        let screen = self.screen_rect();
+        let scale = self.scale();
+
+        (screen.x + x / scale, screen.y + y / scale)
+    }
+
+    pub fn apply(&self, ctx: &mut Context) -> GameResult {
+        graphics::set_screen_coordinates(ctx, self.screen_rect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_aspect_ratio_has_no_bars() {
+        let mut camera = Camera::new(1280.0, 720.0);
+        camera.resize(1920.0, 1080.0);
+
+        assert_eq!(camera.scale(), 1.5);
+        assert_eq!(camera.screen_rect(), Rect::new(0.0, 0.0, 1280.0, 720.0));
+        assert!(camera.bars().is_empty());
+    }
+
+    #[test]
+    fn wide_windows_are_pillarboxed() {
+        let mut camera = Camera::new(1280.0, 720.0);
+        camera.resize(1440.0, 360.0);
+
+        assert_eq!(camera.scale(), 0.5);
+        assert_eq!(camera.screen_rect(), Rect::new(-800.0, 0.0, 2880.0, 720.0));
+        assert_eq!(
+            camera.bars(),
+            vec![
+                Rect::new(-800.0, 0.0, 800.0, 720.0),
+                Rect::new(1280.0, 0.0, 800.0, 720.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn tall_windows_are_letterboxed() {
+        let mut camera = Camera::new(1280.0, 720.0);
+        camera.resize(640.0, 720.0);
+
+        assert_eq!(camera.screen_rect(), Rect::new(0.0, -360.0, 1280.0, 1440.0));
+        assert_eq!(camera.bars().len(), 2);
+    }
+
+    #[test]
+    fn window_positions_map_into_the_arena() {
+        let mut camera = Camera::new(1280.0, 720.0);
+        camera.resize(1440.0, 360.0);
+
+        // The arena is 640 pixels wide, centered in the window.
+        assert_eq!(camera.to_arena(400.0, 0.0), (0.0, 0.0));
+        assert_eq!(camera.to_arena(1040.0, 360.0), (1280.0, 720.0));
+    }
+
+    #[test]
+    fn zero_sized_windows_are_ignored() {
+        let mut camera = Camera::new(1280.0, 720.0);
+        camera.resize(0.0, 0.0);
+
+        assert_eq!(camera.scale(), 1.0);
+    }
+}
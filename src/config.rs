@@ -0,0 +1,216 @@
+use std::{collections::BTreeMap, fs};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accessibility::Accessibility, audio::MixerSettings, difficulty::DifficultySettings,
+    keys::Controls, locale::DEFAULT_LANGUAGE, synth::SoundSettings,
+};
+
+pub const RESOURCE_DIR: &str = "./resources";
+pub const CONFIG_PATH: &str = "./config.toml";
+// Where a config file that fails to parse is copied before the defaults are
+// used, since changing any setting saves over it.
+pub const CONFIG_BACKUP_PATH: &str = "./config.toml.bak";
+
+// Gameplay constants that can be tweaked from the config file while the game runs.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tuning {
+    pub ball_radius: f32,
+    pub ball_speed: f32,
+    pub paddle_width: f32,
+    pub paddle_height: f32,
+    pub paddle_speed: f32,
+    pub winning_score: usize,
+    // Steepest angle, in degrees, a paddle sends the ball back at when hit
+    // with its edge. 0 keeps the classic bounce that only reverses the ball.
+    pub bounce_angle: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning {
+            ball_radius: 8.0,
+            ball_speed: 500.0,
+            paddle_width: 12.0,
+            paddle_height: 100.0,
+            paddle_speed: 450.0,
+            winning_score: 10,
+            bounce_angle: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    Windowed,
+    Fullscreen,
+}
+
+// Window options. The arena is always the same size, the window only changes
+// how big it is drawn.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub width: f32,
+    pub height: f32,
+    pub window_mode: WindowMode,
+    // Only read at startup.
+    pub vsync: bool,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> DisplaySettings {
+        DisplaySettings {
+            width: 1280.0,
+            height: 720.0,
+            window_mode: WindowMode::Windowed,
+            vsync: true,
+        }
+    }
+}
+
+// Longest name the Controls menu accepts.
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Players {
+    pub left_name: String,
+    pub right_name: String,
+}
+
+impl Default for Players {
+    fn default() -> Players {
+        Players {
+            left_name: String::from("Player 1"),
+            right_name: String::from("Player 2"),
+        }
+    }
+}
+
+// Programs that can play a paddle, see `bot.rs`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotSettings {
+    // Command lines of the bots playing each side, empty for a human.
+    pub left: String,
+    pub right: String,
+    // How long a bot may think about each tick.
+    pub timeout_ms: u64,
+    // Bots offered in the Controls menu.
+    pub commands: Vec<String>,
+}
+
+impl Default for BotSettings {
+    fn default() -> BotSettings {
+        BotSettings {
+            left: String::new(),
+            right: String::new(),
+            timeout_ms: 50,
+            commands: vec![String::from("python3 bots/follow_bot.py")],
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Locale code, e.g. "en" or "de", see `resources/locales`.
+    pub language: String,
+    // Name of the theme picked in the menus, see `theme.rs`.
+    pub theme: String,
+    pub tuning: Tuning,
+    pub audio: MixerSettings,
+    pub display: DisplaySettings,
+    pub controls: Controls,
+    pub players: Players,
+    pub bots: BotSettings,
+    pub difficulty: DifficultySettings,
+    pub accessibility: Accessibility,
+    // Keyed by sound ID: "goal", "pad" or "wall".
+    pub sounds: BTreeMap<String, SoundSettings>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            language: String::from(DEFAULT_LANGUAGE),
+            theme: String::from("Classic"),
+            tuning: Tuning::default(),
+            audio: MixerSettings::default(),
+            display: DisplaySettings::default(),
+            controls: Controls::default(),
+            players: Players::default(),
+            bots: BotSettings::default(),
+            difficulty: DifficultySettings::default(),
+            accessibility: Accessibility::default(),
+            sounds: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    // `tuning` with the accessibility size options applied, what the ball
+    // and paddles actually use.
+    pub fn effective_tuning(&self) -> Tuning {
+        self.accessibility.resize(self.tuning)
+    }
+
+    pub fn read() -> Result<Config, String> {
+        let contents = fs::read_to_string(CONFIG_PATH).map_err(|e| e.to_string())?;
+        Config::parse(&contents)
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let contents = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(CONFIG_PATH, contents).map_err(|e| e.to_string())
+    }
+
+    // A missing config file is normal on first run. A broken one is backed up
+    // and reported, see `LoadError`.
+    pub fn load() -> (Config, Option<LoadError>) {
+        if fs::metadata(CONFIG_PATH).is_err() {
+            return (Config::default(), None);
+        }
+
+        let e = match Config::read() {
+            Ok(config) => return (config, None),
+            Err(e) => e,
+        };
+
+        let error = match fs::copy(CONFIG_PATH, CONFIG_BACKUP_PATH) {
+            Ok(_) => LoadError {
+                message: format!(
+                    "Invalid {} ({}), using defaults. It was copied to {}",
+                    CONFIG_PATH, e, CONFIG_BACKUP_PATH
+                ),
+                read_only: false,
+            },
+            Err(backup) => LoadError {
+                message: format!(
+                    "Invalid {} ({}), using defaults. Settings won't be saved until it is fixed, \
+                     it couldn't be copied to {} ({})",
+                    CONFIG_PATH, e, CONFIG_BACKUP_PATH, backup
+                ),
+                read_only: true,
+            },
+        };
+        eprintln!("warning: {}", error.message);
+
+        (Config::default(), Some(error))
+    }
+}
+
+// Why `Config::load` used the defaults.
+pub struct LoadError {
+    pub message: String,
+    // The file couldn't be backed up, so it must not be saved over.
+    pub read_only: bool,
+}
@@ -0,0 +1,682 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use rand::SeedableRng;
+
+use crate::{
+    arena::ArenaRng,
+    ball::Ball,
+    config::{Tuning, RESOURCE_DIR},
+    game_state::{GameState, ARENA_HEIGHT, ARENA_WIDTH},
+    scene::SceneStack,
+    scenes::GameScene,
+    scripting::SCRIPT_DIR,
+};
+
+// Lines kept in the console log, older ones scroll away.
+pub const LOG_LINES: usize = 200;
+
+// Level scripts, `load level foo` runs `resources/levels/foo.txt`.
+pub const LEVEL_DIR: &str = "levels";
+
+// Scripts can load levels, which can load levels, up to this depth.
+const MAX_SCRIPT_DEPTH: u32 = 4;
+
+// A console command. `run` gets the words after the command name and
+// returns what to print.
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub run: fn(&mut GameState, &[&str]) -> Result<String, String>,
+}
+
+// Every console command. Adding one only means adding it here.
+pub const COMMANDS: [Command; 11] = [
+    Command {
+        name: "help",
+        usage: "help [command]",
+        help: "Lists the commands, or explains one",
+        run: help,
+    },
+    Command {
+        name: "set",
+        usage: "set <variable> [value]",
+        help: "Sets a variable, or shows it without a value. Not saved to config.toml",
+        run: set,
+    },
+    Command {
+        name: "spawn",
+        usage: "spawn ball",
+        help: "Serves a new ball from the center",
+        run: spawn,
+    },
+    Command {
+        name: "score",
+        usage: "score <left> <right>",
+        help: "Sets both scores",
+        run: score,
+    },
+    Command {
+        name: "seed",
+        usage: "seed <number>",
+        help: "Seeds the serve directions and serves again, for repeatable matches",
+        run: seed,
+    },
+    Command {
+        name: "timescale",
+        usage: "timescale <scale>",
+        help: "Sets the game speed, 1 is normal. Turns on debug mode, where it applies",
+        run: timescale,
+    },
+    Command {
+        name: "load",
+        usage: "load level <name>",
+        help: "Runs the commands in resources/levels/<name>.txt",
+        run: load,
+    },
+    Command {
+        name: "god",
+        usage: "god <left|right|both|off>",
+        help: "Makes paddles follow the ball so they never miss",
+        run: god,
+    },
+    Command {
+        name: "clear",
+        usage: "clear",
+        help: "Clears the console",
+        run: clear,
+    },
+    Command {
+        name: "exec",
+        usage: "exec <path>",
+        help: "Runs the commands in a script file, one per line",
+        run: exec,
+    },
+    Command {
+        name: "rhai",
+        usage: "rhai",
+        help: "Lists the Rhai scripts in resources/scripts and their hooks",
+        run: rhai,
+    },
+];
+
+// A number that `set` can change.
+pub struct Variable {
+    pub name: &'static str,
+    pub get: fn(&GameState) -> f64,
+    pub set: fn(&mut GameState, f64),
+    // Values outside are clamped.
+    pub range: (f64, f64),
+}
+
+impl Variable {
+    pub fn check(&self, value: f64) -> Result<f64, String> {
+        if !value.is_finite() {
+            return Err(format!("{} must be a finite number", self.name));
+        }
+
+        Ok(value.clamp(self.range.0, self.range.1))
+    }
+}
+
+pub const VARIABLES: [Variable; 11] = [
+    Variable {
+        name: "ball.speed",
+        range: (10.0, 5000.0),
+        get: |game| game.tuning().ball_speed as f64,
+        set: |game, value| game.retune(|tuning| tuning.ball_speed = value as f32),
+    },
+    Variable {
+        name: "ball.radius",
+        range: (1.0, 100.0),
+        get: |game| game.tuning().ball_radius as f64,
+        set: |game, value| game.retune(|tuning| tuning.ball_radius = value as f32),
+    },
+    Variable {
+        name: "ball.x",
+        range: (0.0, ARENA_WIDTH as f64),
+        get: |game| game.arena.ball.x as f64,
+        set: |game, value| game.arena.ball.x = value as f32,
+    },
+    Variable {
+        name: "ball.y",
+        range: (0.0, ARENA_HEIGHT as f64),
+        get: |game| game.arena.ball.y as f64,
+        set: |game, value| game.arena.ball.y = value as f32,
+    },
+    Variable {
+        name: "ball.dx",
+        range: (-3000.0, 3000.0),
+        get: |game| game.arena.ball.dx as f64,
+        set: |game, value| game.arena.ball.dx = value as f32,
+    },
+    Variable {
+        name: "ball.dy",
+        range: (-3000.0, 3000.0),
+        get: |game| game.arena.ball.dy as f64,
+        set: |game, value| game.arena.ball.dy = value as f32,
+    },
+    Variable {
+        name: "paddle.speed",
+        range: (0.0, 5000.0),
+        get: |game| game.tuning().paddle_speed as f64,
+        set: |game, value| game.retune(|tuning| tuning.paddle_speed = value as f32),
+    },
+    Variable {
+        name: "paddle.width",
+        range: (1.0, 200.0),
+        get: |game| game.tuning().paddle_width as f64,
+        set: |game, value| game.retune(|tuning| tuning.paddle_width = value as f32),
+    },
+    Variable {
+        name: "paddle.height",
+        range: (1.0, ARENA_HEIGHT as f64),
+        get: |game| game.tuning().paddle_height as f64,
+        set: |game, value| game.retune(|tuning| tuning.paddle_height = value as f32),
+    },
+    Variable {
+        name: "bounce_angle",
+        range: (0.0, 89.0),
+        get: |game| game.tuning().bounce_angle as f64,
+        set: |game, value| game.retune(|tuning| tuning.bounce_angle = value as f32),
+    },
+    Variable {
+        name: "winning_score",
+        range: (1.0, 999.0),
+        get: |game| game.tuning().winning_score as f64,
+        set: |game, value| game.retune(|tuning| tuning.winning_score = value as usize),
+    },
+];
+
+pub struct ConsoleLine {
+    pub text: String,
+    pub is_error: bool,
+}
+
+// What the console shows and remembers between openings. Commands run on
+// the `GameState`, see `GameState::run_command`.
+#[derive(Default)]
+pub struct Console {
+    pub input: String,
+    pub log: VecDeque<ConsoleLine>,
+    history: Vec<String>,
+    // Position while going through the history with Up and Down.
+    history_index: Option<usize>,
+    script_depth: u32,
+}
+
+impl Console {
+    pub fn print(&mut self, text: &str, is_error: bool) {
+        for line in text.lines() {
+            if self.log.len() == LOG_LINES {
+                self.log.pop_front();
+            }
+            self.log.push_back(ConsoleLine {
+                text: line.to_string(),
+                is_error,
+            });
+        }
+    }
+
+    pub fn type_char(&mut self, character: char) {
+        if !character.is_control() {
+            self.input.push(character);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    // The typed line, which is added to the history.
+    pub fn take_input(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        line
+    }
+
+    pub fn history_back(&mut self) {
+        let index = match self.history_index {
+            Some(0) => 0,
+            Some(index) => index - 1,
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    pub fn history_forward(&mut self) {
+        match self.history_index {
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.input = self.history[index + 1].clone();
+            }
+            Some(_) => {
+                self.history_index = None;
+                self.input.clear();
+            }
+            None => (),
+        }
+    }
+
+    // Tab: completes the last word as far as it is unambiguous, and lists
+    // the options when there are several.
+    pub fn complete(&mut self) {
+        let (done, word) = match self.input.rfind(' ') {
+            Some(space) => self.input.split_at(space + 1),
+            None => ("", self.input.as_str()),
+        };
+
+        let candidates: Vec<String> = completions(done)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .collect();
+
+        let completed = match candidates.as_slice() {
+            [] => return,
+            [only] => format!("{}{} ", done, only),
+            _ => format!("{}{}", done, common_prefix(&candidates)),
+        };
+
+        if candidates.len() > 1 {
+            self.print(&candidates.join("  "), false);
+        }
+        self.input = completed;
+    }
+}
+
+// Words that can follow `done`, the part of the line before the word being
+// completed.
+fn completions(done: &str) -> Vec<String> {
+    let words: Vec<&str> = done.split_whitespace().collect();
+    let names = |names: &mut dyn Iterator<Item = &str>| names.map(String::from).collect();
+
+    match words.as_slice() {
+        [] | ["help"] => names(&mut COMMANDS.iter().map(|c| c.name)),
+        ["set"] => names(&mut VARIABLES.iter().map(|v| v.name)),
+        ["spawn"] => names(&mut ["ball"].iter().copied()),
+        ["god"] => names(&mut ["left", "right", "both", "off"].iter().copied()),
+        ["load"] => names(&mut ["level"].iter().copied()),
+        ["load", "level"] => level_names(),
+        _ => Vec::new(),
+    }
+}
+
+fn level_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(Path::new(RESOURCE_DIR).join(LEVEL_DIR))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+
+    names
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let mut prefix = words[0].clone();
+    for word in words.iter().skip(1) {
+        while !word.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+
+    prefix
+}
+
+fn parse<T: std::str::FromStr>(word: Option<&&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("missing {}", what))?;
+    word.parse()
+        .map_err(|_| format!("\"{}\" is not a valid {}", word, what))
+}
+
+fn help(_: &mut GameState, args: &[&str]) -> Result<String, String> {
+    match args.first() {
+        Some(name) => {
+            let command = find_command(name)?;
+            Ok(format!("{}\n  {}", command.usage, command.help))
+        }
+        None => {
+            let mut text = String::from("Commands, Tab completes and Up/Down go through history:");
+            for command in COMMANDS.iter() {
+                text.push_str(&format!("\n  {:<26} {}", command.usage, command.help));
+            }
+            Ok(text)
+        }
+    }
+}
+
+fn set(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    let name = args
+        .first()
+        .ok_or("missing variable, e.g. set ball.speed 900")?;
+    let variable = VARIABLES
+        .iter()
+        .find(|v| v.name == *name)
+        .ok_or_else(|| format!("unknown variable \"{}\"", name))?;
+
+    if args.len() > 1 {
+        let value = variable.check(parse(args.get(1), "number")?)?;
+        (variable.set)(game, value);
+    }
+
+    Ok(format!("{} = {}", variable.name, (variable.get)(game)))
+}
+
+fn spawn(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    match args.first() {
+        Some(&"ball") => {
+            let arena = &mut game.arena;
+            arena.ball = Ball::new(arena.width, arena.height, &arena.tuning, &mut arena.rng);
+            arena.rally = 0;
+            Ok(String::from("Served a new ball"))
+        }
+        Some(thing) => Err(format!("can't spawn \"{}\", only ball", thing)),
+        None => Err(String::from("missing what to spawn, e.g. spawn ball")),
+    }
+}
+
+fn score(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    let left = parse(args.first(), "left score")?;
+    let right = parse(args.get(1), "right score")?;
+    game.arena.player1_score = left;
+    game.arena.player2_score = right;
+
+    Ok(format!(
+        "Score {} {}",
+        game.arena.player1_score, game.arena.player2_score
+    ))
+}
+
+fn seed(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    let seed: u64 = parse(args.first(), "seed")?;
+    game.arena.rng = ArenaRng::seed_from_u64(seed);
+    game.arena.reset(false);
+
+    Ok(format!("Seeded with {}", seed))
+}
+
+fn timescale(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    let scale: f64 = parse(args.first(), "scale")?;
+    if !(scale > 0.0 && scale <= 10.0) {
+        return Err(String::from("the scale must be above 0 and at most 10"));
+    }
+
+    game.debug.time_scale = scale;
+    game.debug.frozen = false;
+    game.debug.enabled = true;
+
+    Ok(format!("Time scale x{}", scale))
+}
+
+fn load(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    match args {
+        ["level", name] => {
+            let path = Path::new(RESOURCE_DIR)
+                .join(LEVEL_DIR)
+                .join(format!("{}.txt", name));
+            game.run_script(&path)?;
+            Ok(format!("Loaded level {}", name))
+        }
+        _ => Err(String::from("usage: load level <name>")),
+    }
+}
+
+fn god(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    game.arena.god_paddles = match args.first() {
+        Some(&"left") => [!game.arena.god_paddles[0], game.arena.god_paddles[1]],
+        Some(&"right") => [game.arena.god_paddles[0], !game.arena.god_paddles[1]],
+        Some(&"both") => [true, true],
+        Some(&"off") => [false, false],
+        _ => return Err(String::from("usage: god <left|right|both|off>")),
+    };
+
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    Ok(format!(
+        "God mode left {}, right {}",
+        on_off(game.arena.god_paddles[0]),
+        on_off(game.arena.god_paddles[1])
+    ))
+}
+
+fn clear(game: &mut GameState, _: &[&str]) -> Result<String, String> {
+    game.console.log.clear();
+    Ok(String::new())
+}
+
+fn exec(game: &mut GameState, args: &[&str]) -> Result<String, String> {
+    let path = args.first().ok_or("missing script path")?;
+    let count = game.run_script(Path::new(path))?;
+
+    Ok(format!("Ran {} command(s) from {}", count, path))
+}
+
+fn rhai(game: &mut GameState, _: &[&str]) -> Result<String, String> {
+    if game.scripts.is_empty() {
+        return Ok(format!("No scripts in {}{}", RESOURCE_DIR, SCRIPT_DIR));
+    }
+
+    let lines: Vec<String> = game
+        .scripts
+        .scripts
+        .iter()
+        .map(|script| match &script.error {
+            Some(error) => format!("{}: off, {}", script.name, error),
+            None => format!("{}: {}", script.name, script.hooks().join(", ")),
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+fn find_command(name: &str) -> Result<&'static Command, String> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name == name)
+        .ok_or_else(|| format!("unknown command \"{}\", try help", name))
+}
+
+impl GameState {
+    // Runs one console line. Empty lines and `#` comments do nothing.
+    pub fn run_command(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.split_first() {
+            None => Ok(String::new()),
+            Some((name, _)) if name.starts_with('#') => Ok(String::new()),
+            Some((name, args)) => (find_command(name)?.run)(self, args),
+        }
+    }
+
+    // Runs a command typed into the console and prints the result.
+    pub fn submit_console_line(&mut self) {
+        let line = self.console.take_input();
+        self.console.print(&format!("> {}", line), false);
+
+        match self.run_command(&line) {
+            Ok(output) => self.console.print(&output, false),
+            Err(e) => self.console.print(&e, true),
+        }
+    }
+
+    // Runs every line of a script, stopping at the first error. Returns the
+    // number of commands run.
+    pub fn run_script(&mut self, path: &Path) -> Result<usize, String> {
+        if self.console.script_depth >= MAX_SCRIPT_DEPTH {
+            return Err(format!("{}: scripts nested too deep", path.display()));
+        }
+
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        self.console.script_depth += 1;
+        let result = self.run_script_lines(path, &contents);
+        self.console.script_depth -= 1;
+
+        result
+    }
+
+    fn run_script_lines(&mut self, path: &Path, contents: &str) -> Result<usize, String> {
+        let mut count = 0;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let output = self
+                .run_command(line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+            self.console.print(&output, false);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // `--script <path>`: skips the title screen and sets up a match from a
+    // script, so test setups can be repeated exactly.
+    pub fn start_scripted_match(&mut self, path: &Path) -> Result<(), String> {
+        self.scenes = SceneStack::new(Box::new(GameScene::continue_match()));
+        self.arena.reset(true);
+        self.play_state_changed();
+
+        // Bots are greeted with the arena the script set up.
+        self.run_script(path)?;
+        self.start_bots();
+
+        Ok(())
+    }
+
+    // Changes the tuning for this session only.
+    fn retune(&mut self, change: impl FnOnce(&mut Tuning)) {
+        let mut tuning = self.tuning();
+        change(&mut tuning);
+        self.session_tuning = Some(tuning);
+        self.apply_tuning();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn console(history: &[&str]) -> Console {
+        let mut console = Console::default();
+        for line in history {
+            console.input = line.to_string();
+            console.take_input();
+        }
+        console
+    }
+
+    fn typed(input: &str) -> Console {
+        Console {
+            input: input.to_string(),
+            ..Console::default()
+        }
+    }
+
+    #[test]
+    fn set_rejects_and_clamps_values() {
+        let variable = |name| VARIABLES.iter().find(|v| v.name == name).unwrap();
+
+        assert!(variable("ball.speed").check(f64::NAN).is_err());
+        assert!(variable("ball.speed").check(f64::INFINITY).is_err());
+        assert_eq!(variable("ball.speed").check(900.0), Ok(900.0));
+        assert_eq!(variable("ball.radius").check(-5.0), Ok(1.0));
+        assert_eq!(variable("paddle.height").check(1e9), Ok(720.0));
+        assert_eq!(variable("winning_score").check(0.0), Ok(1.0));
+
+        // "inf" and "nan" parse as numbers, so only `check` stops them.
+        assert!(parse::<f64>(Some(&"inf"), "number").is_ok());
+
+        for variable in VARIABLES.iter() {
+            assert!(variable.range.0 < variable.range.1, "{}", variable.name);
+        }
+    }
+
+    #[test]
+    fn history_goes_back_and_forward() {
+        let mut console = console(&["seed 1", "score 1 2", "score 1 2"]);
+
+        console.history_back();
+        assert_eq!(console.input, "score 1 2");
+        console.history_back();
+        assert_eq!(console.input, "seed 1");
+        console.history_back();
+        assert_eq!(console.input, "seed 1");
+
+        console.history_forward();
+        assert_eq!(console.input, "score 1 2");
+        console.history_forward();
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn tab_completes_commands_and_arguments() {
+        let mut console = typed("ti");
+        console.complete();
+        assert_eq!(console.input, "timescale ");
+
+        console.input = String::from("set ball.s");
+        console.complete();
+        assert_eq!(console.input, "set ball.speed ");
+    }
+
+    #[test]
+    fn ambiguous_completions_stop_at_the_common_prefix() {
+        let mut console = typed("set paddle");
+        console.complete();
+        assert_eq!(console.input, "set paddle.");
+        assert_eq!(
+            console.log.back().map(|line| line.text.as_str()),
+            Some("paddle.speed  paddle.width  paddle.height")
+        );
+    }
+
+    #[test]
+    fn every_command_has_unique_name_and_usage() {
+        for (i, command) in COMMANDS.iter().enumerate() {
+            assert!(command.usage.starts_with(command.name));
+            assert!(COMMANDS[i + 1..].iter().all(|c| c.name != command.name));
+        }
+    }
+
+    #[test]
+    fn seeded_serves_repeat() {
+        let tuning = crate::config::Tuning::default();
+        let serves = |seed| {
+            let mut rng = ArenaRng::seed_from_u64(seed);
+            (0..8)
+                .map(|_| Ball::new(1280.0, 720.0, &tuning, &mut rng).dx)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(serves(42), serves(42));
+    }
+
+    #[test]
+    fn log_is_bounded() {
+        let mut console = Console::default();
+        for i in 0..LOG_LINES + 10 {
+            console.print(&i.to_string(), false);
+        }
+
+        assert_eq!(console.log.len(), LOG_LINES);
+        assert_eq!(console.log.front().unwrap().text, "10");
+    }
+}
@@ -0,0 +1,296 @@
+use std::{collections::VecDeque, time::Duration};
+
+use ggez::{input::keyboard::KeyCode, nalgebra::Point2};
+
+use crate::{ball::Ball, game_state::DEFAULT_TIME_SCALE};
+
+// Frame times kept for the frame-time graph.
+pub const FRAME_HISTORY: usize = 120;
+
+// Steps of the slower and faster keys.
+pub const TIME_SCALES: [f64; 8] = [0.1, 0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0];
+
+// Wall bounces followed by the predicted ball path before it gives up.
+const MAX_PREDICTED_BOUNCES: usize = 16;
+
+// Parts of the debug overlay, each toggled by its own key.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Overlay {
+    // FPS, frame time, particle count and time scale.
+    Info,
+    Hitboxes,
+    Vectors,
+    Path,
+    FrameGraph,
+}
+
+impl Overlay {
+    pub const ALL: [Overlay; 5] = [
+        Overlay::Info,
+        Overlay::Hitboxes,
+        Overlay::Vectors,
+        Overlay::Path,
+        Overlay::FrameGraph,
+    ];
+
+    pub fn key(self) -> KeyCode {
+        match self {
+            Overlay::Info => KeyCode::F3,
+            Overlay::Hitboxes => KeyCode::F4,
+            Overlay::Vectors => KeyCode::F5,
+            Overlay::Path => KeyCode::F6,
+            Overlay::FrameGraph => KeyCode::F7,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Overlay::Info => "info",
+            Overlay::Hitboxes => "hitboxes",
+            Overlay::Vectors => "vectors",
+            Overlay::Path => "path",
+            Overlay::FrameGraph => "frame graph",
+        }
+    }
+}
+
+// Everything F1 turns on. The time scale only applies while it is on, so
+// a frozen game can't be left behind by hiding the overlay.
+pub struct DebugState {
+    pub enabled: bool,
+    shown: [bool; Overlay::ALL.len()],
+    pub time_scale: f64,
+    pub frozen: bool,
+    // Ticks still to run while frozen.
+    steps: u32,
+    frame_times: VecDeque<f32>,
+}
+
+impl Default for DebugState {
+    fn default() -> DebugState {
+        DebugState {
+            enabled: false,
+            shown: [true; Overlay::ALL.len()],
+            time_scale: DEFAULT_TIME_SCALE,
+            frozen: false,
+            steps: 0,
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+}
+
+impl DebugState {
+    pub fn is_shown(&self, overlay: Overlay) -> bool {
+        self.enabled && self.shown[overlay as usize]
+    }
+
+    pub fn toggle(&mut self, overlay: Overlay) {
+        self.shown[overlay as usize] = !self.shown[overlay as usize];
+    }
+
+    // Debug keys, only while debug mode is on. Returns whether the key was
+    // used.
+    pub fn key_down(&mut self, keycode: KeyCode) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if let Some(overlay) = Overlay::ALL.iter().find(|o| o.key() == keycode) {
+            self.toggle(*overlay);
+            return true;
+        }
+
+        match keycode {
+            KeyCode::F8 => self.slower(),
+            KeyCode::F9 => self.faster(),
+            KeyCode::F10 => self.frozen = !self.frozen,
+            KeyCode::F12 => self.step(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    pub fn slower(&mut self) {
+        if let Some(scale) = TIME_SCALES.iter().rev().find(|s| **s < self.time_scale) {
+            self.time_scale = *scale;
+        }
+    }
+
+    pub fn faster(&mut self) {
+        if let Some(scale) = TIME_SCALES.iter().find(|s| **s > self.time_scale) {
+            self.time_scale = *scale;
+        }
+    }
+
+    // Freezes the game and runs a single tick.
+    pub fn step(&mut self) {
+        self.frozen = true;
+        self.steps += 1;
+    }
+
+    // Game time for a frame of `frame_time` seconds: scaled, nothing while
+    // frozen, or exactly one tick of `dt` per requested step.
+    pub fn scaled_time(&mut self, frame_time: f64, dt: f64) -> f64 {
+        if !self.enabled {
+            return frame_time;
+        }
+
+        if !self.frozen {
+            return frame_time * self.time_scale;
+        }
+
+        if self.steps > 0 {
+            self.steps -= 1;
+            return dt;
+        }
+
+        0.0
+    }
+
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time.as_secs_f32());
+    }
+
+    // Oldest first, in seconds.
+    pub fn frame_times(&self) -> impl Iterator<Item = f32> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    pub fn time_text(&self) -> String {
+        match self.frozen {
+            true => format!("x{} (frozen, F12 steps)", self.time_scale),
+            false => format!("x{}", self.time_scale),
+        }
+    }
+}
+
+// Where the ball goes until it reaches a paddle's face at `left_x` or
+// `right_x`, bouncing off the top and bottom walls on the way. Starts with
+// the ball's position and has a point for every bounce.
+pub fn predict_path(ball: &Ball, left_x: f32, right_x: f32, game_height: f32) -> Vec<Point2<f32>> {
+    let r = ball.radius;
+    let (mut x, mut y, mut dy) = (ball.x, ball.y, ball.dy);
+    let mut path = vec![Point2::new(x, y)];
+
+    if ball.dx == 0.0 {
+        return path;
+    }
+
+    let target_x = if ball.dx > 0.0 {
+        right_x - r
+    } else {
+        left_x + r
+    };
+
+    for _ in 0..MAX_PREDICTED_BOUNCES {
+        let to_paddle = (target_x - x) / ball.dx;
+        let to_wall = match dy {
+            dy if dy > 0.0 => (game_height - r - y) / dy,
+            dy if dy < 0.0 => (r - y) / dy,
+            _ => f32::INFINITY,
+        };
+
+        if to_paddle <= to_wall {
+            path.push(Point2::new(target_x, y + dy * to_paddle.max(0.0)));
+            break;
+        }
+
+        x += ball.dx * to_wall;
+        y += dy * to_wall;
+        dy = -dy;
+        path.push(Point2::new(x, y));
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ball(x: f32, y: f32, dx: f32, dy: f32) -> Ball {
+        Ball {
+            x,
+            y,
+            dx,
+            dy,
+            radius: 10.0,
+        }
+    }
+
+    #[test]
+    fn path_bounces_off_both_walls() {
+        let path = predict_path(&ball(100.0, 600.0, 100.0, 100.0), 0.0, 1000.0, 720.0);
+
+        assert_eq!(
+            path,
+            vec![
+                Point2::new(100.0, 600.0),
+                Point2::new(210.0, 710.0),
+                Point2::new(910.0, 10.0),
+                Point2::new(990.0, 90.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn straight_paths_end_at_the_paddle() {
+        let path = predict_path(&ball(500.0, 300.0, -200.0, 0.0), 12.0, 1268.0, 720.0);
+
+        assert_eq!(
+            path,
+            vec![Point2::new(500.0, 300.0), Point2::new(22.0, 300.0)]
+        );
+    }
+
+    #[test]
+    fn time_scale_steps_through_the_presets() {
+        let mut debug = DebugState {
+            enabled: true,
+            ..DebugState::default()
+        };
+
+        debug.slower();
+        assert_eq!(debug.scaled_time(1.0, 0.1), 0.5);
+
+        debug.time_scale = 0.2;
+        debug.faster();
+        assert_eq!(debug.time_scale, 0.25);
+
+        debug.enabled = false;
+        assert_eq!(debug.scaled_time(1.0, 0.1), 1.0);
+    }
+
+    #[test]
+    fn frozen_games_only_run_requested_steps() {
+        let mut debug = DebugState {
+            enabled: true,
+            ..DebugState::default()
+        };
+
+        debug.step();
+        debug.step();
+        assert_eq!(debug.scaled_time(0.5, 0.1), 0.1);
+        assert_eq!(debug.scaled_time(0.5, 0.1), 0.1);
+        assert_eq!(debug.scaled_time(0.5, 0.1), 0.0);
+
+        assert!(debug.key_down(KeyCode::F10));
+        assert_eq!(debug.scaled_time(0.5, 0.1), 0.5);
+    }
+
+    #[test]
+    fn frame_history_is_bounded() {
+        let mut debug = DebugState::default();
+
+        for i in 0..FRAME_HISTORY + 5 {
+            debug.record_frame(Duration::from_millis(i as u64));
+        }
+
+        assert_eq!(debug.frame_times().count(), FRAME_HISTORY);
+        assert_eq!(debug.frame_times().next(), Some(0.005));
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ai::Personality, arena::Arena, bot::Builtin, game_state::GameState};
+
+// Every point played against an adaptive AI is appended here.
+pub const DIFFICULTY_LOG: &str = "./difficulty.csv";
+
+pub const LOG_HEADER: &str =
+    "time,human_score,ai_score,human_scored,rally,miss,level,reaction_time,error";
+
+// Rally length, in paddle hits, that counts as an even point.
+pub const TARGET_RALLY: f32 = 6.0;
+
+// Seconds of play the indicator shows which way the level last moved.
+pub const TREND_TIME: f64 = 3.0;
+
+// The `[difficulty]` table of the config file. Against a human, the level sets
+// the AI's reaction time and error between these bounds, and its preset still
+// sets how it aims and moves.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DifficultySettings {
+    // Adjusts the level after every point, otherwise it stays locked.
+    pub adaptive: bool,
+    // 0.0 is the easiest, 1.0 the hardest. Kept between matches.
+    pub level: f32,
+    // Most the level moves after one point.
+    pub step: f32,
+    // At the easiest and the hardest level.
+    pub reaction_time: [f32; 2],
+    pub error: [f32; 2],
+    // Appends every point to `DIFFICULTY_LOG`, for tuning the bounds.
+    pub log: bool,
+}
+
+impl Default for DifficultySettings {
+    fn default() -> DifficultySettings {
+        DifficultySettings {
+            adaptive: true,
+            level: 0.5,
+            step: 0.08,
+            reaction_time: [0.45, 0.05],
+            error: [90.0, 4.0],
+            log: false,
+        }
+    }
+}
+
+impl DifficultySettings {
+    pub fn reaction_time(&self, level: f32) -> f32 {
+        lerp(self.reaction_time, level)
+    }
+
+    pub fn error(&self, level: f32) -> f32 {
+        lerp(self.error, level)
+    }
+}
+
+fn lerp(bounds: [f32; 2], level: f32) -> f32 {
+    bounds[0] + (bounds[1] - bounds[0]) * level.clamp(0.0, 1.0)
+}
+
+// How a point between the human and the AI went.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Point {
+    pub human_scored: bool,
+    // Human minus AI, after the point.
+    pub score_difference: i32,
+    // Paddle hits before the ball got past.
+    pub rally: u32,
+    // How far the paddle that missed was from the ball, in paddle heights.
+    pub miss: f32,
+}
+
+// The level after `point`. Each term is -1.0 to 1.0 and positive when the
+// human does well: who won the point and how clearly, the score, and how
+// long the rally was.
+pub fn adjust(level: f32, point: &Point, settings: &DifficultySettings) -> f32 {
+    let clearly = 0.5 + 0.5 * point.miss.clamp(0.0, 1.0);
+    let point_term = if point.human_scored {
+        clearly
+    } else {
+        -clearly
+    };
+    let score_term = (point.score_difference as f32 / 3.0).clamp(-1.0, 1.0);
+    let rally_term = ((point.rally as f32 - TARGET_RALLY) / TARGET_RALLY).clamp(-1.0, 1.0);
+
+    let change = (2.0 * point_term + score_term + rally_term) / 4.0;
+    (level + settings.step * change).clamp(0.0, 1.0)
+}
+
+// One line of the log.
+#[derive(Clone, Copy, Debug)]
+pub struct Adjustment {
+    // Seconds of play in the match.
+    pub time: f64,
+    pub scores: [usize; 2],
+    pub point: Point,
+    // The level after the point, and what it gives the AI.
+    pub level: f32,
+    pub reaction_time: f32,
+    pub error: f32,
+}
+
+impl Adjustment {
+    // See `LOG_HEADER`, scores are the human's then the AI's.
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{:.2},{},{},{},{},{:.3},{:.3},{:.3},{:.1}",
+            self.time,
+            self.scores[0],
+            self.scores[1],
+            self.point.human_scored,
+            self.point.rally,
+            self.point.miss,
+            self.level,
+            self.reaction_time,
+            self.error
+        )
+    }
+}
+
+// Watches matches between a human and an `Ai` and makes the AI easier or
+// harder to keep them close.
+pub struct Difficulty {
+    pub settings: DifficultySettings,
+    pub level: f32,
+    pub history: Vec<Adjustment>,
+    // Play time of the last change, and which way it went.
+    pub changed: Option<(f64, f32)>,
+    // The rally and how far each paddle is from the ball, as of the last
+    // step, since a goal resets them.
+    rally: u32,
+    gaps: [f32; 2],
+    log: Option<File>,
+}
+
+impl Difficulty {
+    pub fn new(settings: DifficultySettings) -> Difficulty {
+        Difficulty {
+            settings,
+            level: settings.level.clamp(0.0, 1.0),
+            history: Vec::new(),
+            changed: None,
+            rally: 0,
+            gaps: [0.0; 2],
+            log: None,
+        }
+    }
+
+    pub fn apply(&self, personality: &mut Personality) {
+        personality.reaction_time = self.settings.reaction_time(self.level);
+        personality.error = self.settings.error(self.level);
+    }
+
+    // Call before every step.
+    pub fn watch(&mut self, arena: &Arena) {
+        if arena.paused.is_some() {
+            return;
+        }
+
+        self.rally = arena.rally;
+        for (gap, paddle) in self.gaps.iter_mut().zip(arena.paddles.iter()) {
+            let rect = paddle.rect;
+            let distance = (arena.ball.y - (rect.y + rect.h / 2.0)).abs() - rect.h / 2.0;
+            *gap = distance.max(0.0) / rect.h.max(1.0);
+        }
+    }
+
+    // Records a goal by `scorer` (player 1 or 2) with the human on `human`'s
+    // paddle, and moves the level unless it is locked.
+    pub fn score(&mut self, arena: &Arena, scorer: usize, human: usize) -> Adjustment {
+        let scores = [arena.player1_score, arena.player2_score];
+        let human_scored = scorer == human + 1;
+        let point = Point {
+            human_scored,
+            score_difference: scores[human] as i32 - scores[1 - human] as i32,
+            rally: self.rally,
+            miss: self.gaps[if human_scored { 1 - human } else { human }],
+        };
+
+        if self.settings.adaptive {
+            let level = adjust(self.level, &point, &self.settings);
+            if level != self.level {
+                self.changed = Some((arena.stats.play_time, level - self.level));
+            }
+            self.level = level;
+        }
+
+        let adjustment = Adjustment {
+            time: arena.stats.play_time,
+            scores: [scores[human], scores[1 - human]],
+            point,
+            level: self.level,
+            reaction_time: self.settings.reaction_time(self.level),
+            error: self.settings.error(self.level),
+        };
+        self.history.push(adjustment);
+        self.write_log(&adjustment);
+
+        adjustment
+    }
+
+    fn write_log(&mut self, adjustment: &Adjustment) {
+        if !self.settings.log {
+            return;
+        }
+
+        if self.log.is_none() {
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(DIFFICULTY_LOG)
+                .and_then(|mut file| {
+                    if file.metadata()?.len() == 0 {
+                        writeln!(file, "{}", LOG_HEADER)?;
+                    }
+                    Ok(file)
+                });
+
+            match opened {
+                Ok(file) => self.log = Some(file),
+                Err(e) => {
+                    eprintln!("warning: could not open {}: {}", DIFFICULTY_LOG, e);
+                    self.settings.log = false;
+                    return;
+                }
+            }
+        }
+
+        if let Some(file) = self.log.as_mut() {
+            if let Err(e) = writeln!(file, "{}", adjustment.csv_row()) {
+                eprintln!("warning: could not write {}: {}", DIFFICULTY_LOG, e);
+                self.settings.log = false;
+            }
+        }
+    }
+}
+
+impl GameState {
+    // The paddle of an AI preset playing a human, which the difficulty
+    // applies to.
+    pub fn difficulty_opponent(&self) -> Option<usize> {
+        (0..2).find(|&paddle| {
+            matches!(self.ais[paddle], Some(Builtin::Ai(_))) && !self.is_bot(1 - paddle)
+        })
+    }
+
+    // Gives the AI the reaction time and error of the current level.
+    pub fn apply_difficulty(&mut self) {
+        if let Some(paddle) = self.difficulty_opponent() {
+            if let Some(Builtin::Ai(ai)) = self.ais[paddle].as_mut() {
+                self.difficulty.apply(&mut ai.personality);
+            }
+        }
+    }
+
+    pub fn on_goal(&mut self, scorer: usize) {
+        if let Some(paddle) = self.difficulty_opponent() {
+            self.difficulty.score(&self.arena, scorer, 1 - paddle);
+            self.apply_difficulty();
+        }
+    }
+
+    pub fn toggle_adaptive_difficulty(&mut self) {
+        let adaptive = !self.difficulty.settings.adaptive;
+        self.difficulty.settings.adaptive = adaptive;
+        self.config.difficulty.adaptive = adaptive;
+        self.save_config();
+    }
+
+    // Keeps the level for the next session.
+    pub fn remember_difficulty(&mut self) {
+        if self.config.difficulty.level != self.difficulty.level {
+            self.config.difficulty.level = self.difficulty.level;
+            self.save_config();
+        }
+    }
+
+    // "AI level 55%", with + or - for a while after it changed.
+    pub fn difficulty_text(&self) -> String {
+        let level = format!("{:.0}", self.difficulty.level * 100.0);
+        let key = match self.difficulty.settings.adaptive {
+            true => "difficulty",
+            false => "difficulty-locked",
+        };
+        let mut text = self.locale.format(key, &[("level", &level)]);
+
+        if let Some((time, change)) = self.difficulty.changed {
+            if self.arena.stats.play_time - time < TREND_TIME {
+                text += if change > 0.0 { " +" } else { " -" };
+            }
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(human_scored: bool, score_difference: i32, rally: u32, miss: f32) -> Point {
+        Point {
+            human_scored,
+            score_difference,
+            rally,
+            miss,
+        }
+    }
+
+    #[test]
+    fn losing_makes_it_easier_and_winning_harder() {
+        let settings = DifficultySettings::default();
+
+        let easier = adjust(0.5, &point(false, -3, 0, 1.0), &settings);
+        assert!((easier - (0.5 - settings.step)).abs() < 1e-6);
+
+        let harder = adjust(0.5, &point(true, 2, 12, 0.5), &settings);
+        assert!(harder > 0.5 && harder <= 0.5 + settings.step);
+
+        // A close point in an even match barely moves it.
+        let even = adjust(0.5, &point(true, 0, 6, 0.0), &settings);
+        assert!((even - 0.5).abs() <= settings.step / 4.0 + 1e-6);
+
+        assert_eq!(adjust(1.0, &point(true, 5, 20, 1.0), &settings), 1.0);
+        assert_eq!(adjust(0.0, &point(false, -5, 0, 1.0), &settings), 0.0);
+    }
+
+    #[test]
+    fn levels_stay_within_the_bounds() {
+        let settings = DifficultySettings::default();
+
+        assert_eq!(settings.reaction_time(0.0), 0.45);
+        assert_eq!(settings.error(1.0), 4.0);
+        assert_eq!(settings.error(7.0), 4.0);
+        assert!((settings.reaction_time(0.5) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn locked_difficulty_records_points_without_changing() {
+        let mut arena = Arena::seeded(1280.0, 720.0, Default::default(), 1);
+        arena.player2_score = 1;
+
+        let mut difficulty = Difficulty::new(DifficultySettings {
+            adaptive: false,
+            log: false,
+            ..DifficultySettings::default()
+        });
+        let adjustment = difficulty.score(&arena, 2, 0);
+
+        assert_eq!(difficulty.level, 0.5);
+        assert_eq!(difficulty.changed, None);
+        assert_eq!(adjustment.scores, [0, 1]);
+        assert!(!adjustment.point.human_scored);
+        assert_eq!(difficulty.history.len(), 1);
+        assert_eq!(
+            adjustment.csv_row().split(',').count(),
+            LOG_HEADER.split(',').count()
+        );
+
+        difficulty.settings.adaptive = true;
+        difficulty.score(&arena, 2, 0);
+        assert!(difficulty.level < 0.5);
+        assert!(difficulty.changed.unwrap().1 < 0.0);
+    }
+}
@@ -0,0 +1,125 @@
+use ggez::{
+    conf::{self, FullscreenType},
+    graphics, Context,
+};
+
+use crate::{
+    config::{DisplaySettings, WindowMode},
+    game_state::GameState,
+};
+
+// Window sizes offered in the Display menu, all 16:9 like the arena.
+pub const RESOLUTIONS: [(f32, f32); 5] = [
+    (960.0, 540.0),
+    (1280.0, 720.0),
+    (1600.0, 900.0),
+    (1920.0, 1080.0),
+    (2560.0, 1440.0),
+];
+
+pub fn window_setup(settings: &DisplaySettings) -> conf::WindowSetup {
+    conf::WindowSetup::default().vsync(settings.vsync)
+}
+
+pub fn window_mode(settings: &DisplaySettings) -> conf::WindowMode {
+    conf::WindowMode::default()
+        .dimensions(settings.width, settings.height)
+        .resizable(true)
+        .fullscreen_type(fullscreen_type(settings.window_mode))
+}
+
+fn fullscreen_type(mode: WindowMode) -> FullscreenType {
+    match mode {
+        WindowMode::Windowed => FullscreenType::Windowed,
+        WindowMode::Fullscreen => FullscreenType::Desktop,
+    }
+}
+
+// Index of the size in `RESOLUTIONS` closest to `current`, e.g. after the
+// window was resized by hand.
+pub fn closest_resolution(current: (f32, f32)) -> usize {
+    RESOLUTIONS
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, (w, h))| ((w - current.0).abs() + (h - current.1).abs()) as u32)
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+impl GameState {
+    pub fn toggle_fullscreen(&mut self, ctx: &mut Context) {
+        let mode = match self.config.display.window_mode {
+            WindowMode::Windowed => WindowMode::Fullscreen,
+            WindowMode::Fullscreen => WindowMode::Windowed,
+        };
+
+        self.set_window_mode(ctx, mode);
+    }
+
+    pub fn set_window_mode(&mut self, ctx: &mut Context, mode: WindowMode) {
+        self.config.display.window_mode = mode;
+        self.apply_display(ctx);
+    }
+
+    // `index` into `RESOLUTIONS`.
+    pub fn set_resolution(&mut self, ctx: &mut Context, index: usize) {
+        let display = &mut self.config.display;
+        let (width, height) = RESOLUTIONS[index.min(RESOLUTIONS.len() - 1)];
+        display.width = width;
+        display.height = height;
+
+        self.apply_display(ctx);
+    }
+
+    pub fn toggle_vsync(&mut self) {
+        self.config.display.vsync = !self.config.display.vsync;
+        self.save_config();
+    }
+
+    // Vsync can only be set when the window is created.
+    pub fn vsync_needs_restart(&self) -> bool {
+        self.config.display.vsync != self.vsync_at_start
+    }
+
+    fn apply_display(&mut self, ctx: &mut Context) {
+        let display = self.config.display;
+
+        let result =
+            graphics::set_fullscreen(ctx, fullscreen_type(display.window_mode)).and_then(|_| {
+                match display.window_mode {
+                    WindowMode::Windowed => {
+                        graphics::set_drawable_size(ctx, display.width, display.height)
+                    }
+                    WindowMode::Fullscreen => Ok(()),
+                }
+            });
+
+        if let Err(e) = result {
+            eprintln!("warning: could not change the window: {}", e);
+        }
+
+        let (width, height) = graphics::drawable_size(ctx);
+        self.camera.resize(width, height);
+
+        self.save_config();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed_sizes_find_themselves() {
+        for (i, resolution) in RESOLUTIONS.iter().enumerate() {
+            assert_eq!(closest_resolution(*resolution), i);
+        }
+    }
+
+    #[test]
+    fn custom_sizes_snap_to_the_closest_resolution() {
+        assert_eq!(closest_resolution((1300.0, 700.0)), 1);
+        assert_eq!(closest_resolution((100.0, 100.0)), 0);
+        assert_eq!(closest_resolution((4000.0, 3000.0)), 4);
+    }
+}
@@ -0,0 +1,439 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    arena::{Arena, ArenaEvent, ArenaState},
+    config::Tuning,
+    game_state::{ARENA_HEIGHT, ARENA_WIDTH},
+};
+
+// Number of features in an `Observation`.
+pub const OBSERVATION_SIZE: usize = 8;
+
+// What each feature of an `Observation` is, in order.
+pub const FEATURE_NAMES: [&str; OBSERVATION_SIZE] = [
+    "ball.x",
+    "ball.y",
+    "ball.dx",
+    "ball.dy",
+    "left_paddle.y",
+    "right_paddle.y",
+    "left_score",
+    "right_score",
+];
+
+// Positions are divided by the arena size, velocities by the serve speed,
+// paddle positions are their centers and scores are divided by the winning
+// score, so every feature is roughly within -1.0..=1.0.
+pub type Observation = [f32; OBSERVATION_SIZE];
+
+// What a paddle does during one step, like holding a key for that long.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Stay,
+    Up,
+    Down,
+}
+
+impl Action {
+    pub const ALL: [Action; 3] = [Action::Stay, Action::Up, Action::Down];
+
+    // For agents that pick an index, see `ALL`.
+    pub fn from_index(index: usize) -> Option<Action> {
+        Action::ALL.get(index).copied()
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        match name {
+            "stay" => Some(Action::Stay),
+            "up" => Some(Action::Up),
+            "down" => Some(Action::Down),
+            _ => None,
+        }
+    }
+}
+
+// Reward shaping. Scoring gives `goal` and conceding takes it away, hitting
+// the ball gives `hit_bonus`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rewards {
+    pub goal: f32,
+    pub hit_bonus: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Rewards {
+        Rewards {
+            goal: 1.0,
+            hit_bonus: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    pub width: f32,
+    pub height: f32,
+    pub tuning: Tuning,
+    pub rewards: Rewards,
+    // Seconds simulated per step.
+    pub dt: f64,
+    // Episodes are cut off after this many steps, 0 for never.
+    pub max_steps: u32,
+}
+
+impl Default for EnvConfig {
+    fn default() -> EnvConfig {
+        EnvConfig {
+            width: ARENA_WIDTH,
+            height: ARENA_HEIGHT,
+            tuning: Tuning::default(),
+            rewards: Rewards::default(),
+            dt: 1.0 / 60.0,
+            max_steps: 60 * 60 * 5,
+        }
+    }
+}
+
+// Extra facts about a step that aren't part of the reward.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct StepInfo {
+    // Player 1 or 2 scored during the step.
+    pub scorer: Option<usize>,
+    // Paddle hits during the step, left then right.
+    pub hits: [u32; 2],
+    pub wall_bounces: u32,
+    // Steps since the last reset.
+    pub steps: u32,
+    // The episode ended because of `max_steps`, not because someone won.
+    pub truncated: bool,
+}
+
+// A gym-style environment around `Arena`, the same rules the game runs,
+// without a window. Rewards are given to both paddles, left then right, so it
+// works for self-play as well as against a scripted opponent.
+pub struct Env {
+    pub config: EnvConfig,
+    pub arena: Arena,
+    steps: u32,
+    // The episode so far.
+    replay: Replay,
+}
+
+// An episode as its seed and the actions of every step, replaying it gives
+// the same match again.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub config: EnvConfig,
+    pub seed: u64,
+    pub actions: Vec<[Action; 2]>,
+}
+
+impl Replay {
+    // The environment at the end of the replay.
+    pub fn play(&self) -> Env {
+        let mut env = Env::new(self.config);
+        env.reset(self.seed);
+
+        for actions in self.actions.iter() {
+            env.step(*actions);
+        }
+
+        env
+    }
+}
+
+// A saved `Env`, see `Env::state`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnvState {
+    pub arena: ArenaState,
+    pub steps: u32,
+    pub replay: Replay,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Env {
+        Env {
+            arena: Arena::seeded(config.width, config.height, config.tuning, 0),
+            config,
+            steps: 0,
+            replay: Replay {
+                config,
+                seed: 0,
+                actions: Vec::new(),
+            },
+        }
+    }
+
+    // Starts a new match. The same seed and actions always play out the same.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        let config = &self.config;
+        self.arena = Arena::seeded(config.width, config.height, config.tuning, seed);
+        self.steps = 0;
+        self.replay = Replay {
+            config: self.config,
+            seed,
+            actions: Vec::new(),
+        };
+
+        self.observe()
+    }
+
+    // Moves the paddles as told for one step of `dt` seconds.
+    pub fn step(&mut self, actions: [Action; 2]) -> (Observation, [f32; 2], bool, StepInfo) {
+        for (paddle, action) in self.arena.paddles.iter_mut().zip(actions.iter()) {
+            paddle.is_up_holding = *action == Action::Up;
+            paddle.is_down_holding = *action == Action::Down;
+        }
+
+        self.arena.events.clear();
+        self.arena.simulate(self.config.dt);
+        self.steps += 1;
+        self.replay.actions.push(actions);
+
+        let rewards = self.config.rewards;
+        let mut reward = [0.0; 2];
+        let mut info = StepInfo {
+            steps: self.steps,
+            ..StepInfo::default()
+        };
+
+        for event in self.arena.events.iter() {
+            match *event {
+                ArenaEvent::Goal { scorer } => {
+                    let (scored, conceded) = if scorer == 1 { (0, 1) } else { (1, 0) };
+                    reward[scored] += rewards.goal;
+                    reward[conceded] -= rewards.goal;
+                    info.scorer = Some(scorer);
+                }
+                ArenaEvent::PaddleHit { paddle } => {
+                    reward[paddle] += rewards.hit_bonus;
+                    info.hits[paddle] += 1;
+                }
+                ArenaEvent::WallBounce => info.wall_bounces += 1,
+            }
+        }
+
+        info.truncated = self.config.max_steps > 0 && self.steps >= self.config.max_steps;
+        let done = self.arena.winner().is_some() || info.truncated;
+
+        (self.observe(), reward, done, info)
+    }
+
+    // What happened during the last step.
+    pub fn events(&self) -> &[ArenaEvent] {
+        &self.arena.events
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn state(&self) -> EnvState {
+        EnvState {
+            arena: self.arena.state(),
+            steps: self.steps,
+            replay: self.replay.clone(),
+        }
+    }
+
+    // Continues from a saved state, with the state's config.
+    pub fn restore(&mut self, state: &EnvState) {
+        self.config = state.replay.config;
+        self.arena = Arena::restore(&state.arena);
+        self.steps = state.steps;
+        self.replay = state.replay.clone();
+    }
+
+    pub fn observe(&self) -> Observation {
+        observe(&self.arena)
+    }
+}
+
+// The observation of any arena, e.g. the one being played in the game.
+pub fn observe(arena: &Arena) -> Observation {
+    let ball = &arena.ball;
+    let center = |i: usize| {
+        let rect = arena.paddles[i].rect;
+        rect.y + rect.h / 2.0
+    };
+
+    features(
+        (arena.width, arena.height),
+        &arena.tuning,
+        [ball.x, ball.y, ball.dx, ball.dy],
+        [center(0), center(1)],
+        [arena.player1_score, arena.player2_score],
+    )
+}
+
+// The observation of an arena of `size`, from the ball's position and
+// velocity, the paddle centers and the scores. Shared with `BatchSim`.
+pub fn features(
+    size: (f32, f32),
+    tuning: &Tuning,
+    ball: [f32; 4],
+    paddle_centers: [f32; 2],
+    scores: [usize; 2],
+) -> Observation {
+    let (width, height) = size;
+    let speed = tuning.ball_speed.max(f32::EPSILON);
+    let winning_score = tuning.winning_score.max(1) as f32;
+
+    [
+        ball[0] / width,
+        ball[1] / height,
+        ball[2] / speed,
+        ball[3] / speed,
+        paddle_centers[0] / height,
+        paddle_centers[1] / height,
+        scores[0] as f32 / winning_score,
+        scores[1] as f32 / winning_score,
+    ]
+}
+
+// The observation as the right player sees it when playing on the left, so
+// one agent can play either side.
+pub fn mirror(observation: &Observation) -> Observation {
+    let o = observation;
+
+    [1.0 - o[0], o[1], -o[2], o[3], o[5], o[4], o[7], o[6]]
+}
+
+// A paddle that follows the ball, used by the benchmark and as a baseline
+// opponent. `paddle` is 0 for left, 1 for right.
+pub fn follow_ball(observation: &Observation, paddle: usize) -> Action {
+    let offset = observation[1] - observation[4 + paddle];
+
+    if offset < -0.02 {
+        Action::Up
+    } else if offset > 0.02 {
+        Action::Down
+    } else {
+        Action::Stay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(seed: u64, steps: usize) -> Vec<(Observation, [f32; 2], bool)> {
+        let mut env = Env::new(EnvConfig::default());
+        let mut observation = env.reset(seed);
+
+        (0..steps)
+            .map(|_| {
+                let actions = [follow_ball(&observation, 0), Action::Stay];
+                let (next, reward, done, _) = env.step(actions);
+                observation = next;
+                (next, reward, done)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_episode() {
+        assert_eq!(play(3, 2000), play(3, 2000));
+    }
+
+    #[test]
+    fn goals_reward_the_scorer_and_punish_the_other() {
+        let mut env = Env::new(EnvConfig {
+            rewards: Rewards {
+                goal: 1.0,
+                hit_bonus: 0.25,
+            },
+            ..EnvConfig::default()
+        });
+        env.reset(1);
+        let mut total = [0.0; 2];
+        let mut hits = 0;
+
+        // The left paddle never misses and the right one never moves.
+        env.arena.god_paddles[0] = true;
+        loop {
+            let (_, reward, done, info) = env.step([Action::Stay; 2]);
+            total[0] += reward[0];
+            total[1] += reward[1];
+            hits += info.hits[0];
+
+            if info.scorer.is_some() {
+                assert_eq!(info.scorer, Some(1));
+                assert!(reward[0] >= 1.0 && reward[1] == -1.0);
+            }
+            if done {
+                assert!(!info.truncated);
+                break;
+            }
+        }
+
+        let winning_score = env.config.tuning.winning_score as f32;
+        assert_eq!(total[1], -winning_score);
+        assert_eq!(total[0], winning_score + 0.25 * hits as f32);
+    }
+
+    #[test]
+    fn episodes_are_cut_off_at_max_steps() {
+        let mut env = Env::new(EnvConfig {
+            max_steps: 10,
+            ..EnvConfig::default()
+        });
+        env.reset(0);
+
+        for _ in 0..9 {
+            assert!(!env.step([Action::Stay; 2]).2);
+        }
+
+        let (_, _, done, info) = env.step([Action::Stay; 2]);
+        assert!(done && info.truncated);
+    }
+
+    #[test]
+    fn observations_are_normalized() {
+        let mut env = Env::new(EnvConfig::default());
+        let observation = env.reset(0);
+
+        assert_eq!(observation[0], 0.5);
+        assert_eq!(observation[1], 0.5);
+        assert_eq!(observation[2].abs(), 1.0);
+        assert_eq!(observation[4], 0.5);
+        assert_eq!(observation[6], 0.0);
+    }
+
+    #[test]
+    fn restored_states_continue_the_same_way() {
+        let mut env = Env::new(EnvConfig::default());
+        let mut observation = env.reset(9);
+        for _ in 0..700 {
+            observation = env.step([follow_ball(&observation, 0), Action::Down]).0;
+        }
+
+        let saved = env.state();
+        let mut restored = Env::new(EnvConfig::default());
+        restored.restore(&saved);
+
+        for _ in 0..2000 {
+            let actions = [follow_ball(&observation, 0), Action::Up];
+            let step = env.step(actions);
+            assert_eq!(restored.step(actions), step);
+            observation = step.0;
+        }
+    }
+
+    #[test]
+    fn replays_end_where_the_episode_did() {
+        let mut env = Env::new(EnvConfig::default());
+        let mut observation = env.reset(4);
+        for _ in 0..3000 {
+            observation = env.step([follow_ball(&observation, 0), Action::Stay]).0;
+        }
+
+        let replayed = env.replay().play();
+
+        assert_eq!(replayed.observe(), observation);
+        assert_eq!(replayed.replay().actions.len(), 3000);
+    }
+}
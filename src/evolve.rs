@@ -0,0 +1,438 @@
+use std::{fs, path::Path};
+
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::gaussian,
+    arena::ArenaRng,
+    bot::Builtin,
+    config::Tuning,
+    env::{Action, Env, EnvConfig, Rewards, OBSERVATION_SIZE},
+    mlp::{Activation, Layer, Mlp, NeuralAi},
+};
+
+// First line of the fitness log `pong-train` writes.
+pub const CSV_HEADER: &str = "generation,best,mean,worst,seconds";
+
+// Settings for `pong-train`, read from a TOML file.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainConfig {
+    pub seed: u64,
+    pub population: usize,
+    pub generations: usize,
+    // Sizes of the hidden layers, which use tanh.
+    pub hidden: Vec<usize>,
+    // Best genomes copied unchanged into the next generation.
+    pub elite: usize,
+    // Genomes compared to pick each parent.
+    pub tournament_size: usize,
+    // Chance for each weight of a child to change, and the standard deviation
+    // of the change.
+    pub mutation_rate: f32,
+    pub mutation_strength: f32,
+    // Matches each genome plays per generation, against the opponents in
+    // turn and changing sides. Every genome gets the same serves.
+    pub matches: usize,
+    // Who to train against, as in the Controls menu: "ai:<preset>" or
+    // "nn:<name or path>".
+    pub opponents: Vec<String>,
+    // Matches are cut off after this many steps of 1/60 s.
+    pub max_steps: u32,
+    // Fitness is 1 per point won, -1 per point lost and this per hit.
+    pub hit_bonus: f32,
+    // Generations between checkpoints, 0 for only at the end.
+    pub checkpoint_every: usize,
+    pub tuning: Tuning,
+}
+
+impl Default for TrainConfig {
+    fn default() -> TrainConfig {
+        TrainConfig {
+            seed: 0,
+            population: 64,
+            generations: 100,
+            hidden: vec![16],
+            elite: 4,
+            tournament_size: 3,
+            mutation_rate: 0.1,
+            mutation_strength: 0.3,
+            matches: 4,
+            opponents: vec!["nn:follower".to_string(), "ai:steady".to_string()],
+            max_steps: 60 * 60 * 2,
+            hit_bonus: 0.1,
+            checkpoint_every: 10,
+            tuning: Tuning::default(),
+        }
+    }
+}
+
+impl TrainConfig {
+    // Layer sizes from the observation to the action scores.
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![OBSERVATION_SIZE];
+        sizes.extend(self.hidden.iter().copied());
+        sizes.push(Action::ALL.len());
+        sizes
+    }
+
+    pub fn genome_len(&self) -> usize {
+        self.sizes().windows(2).map(|w| w[0] * w[1] + w[1]).sum()
+    }
+
+    // The network a genome encodes: each layer's weights, then its biases.
+    pub fn network(&self, genome: &[f32]) -> Mlp {
+        let sizes = self.sizes();
+        let mut rest = genome;
+        let mut layers = Vec::new();
+
+        for (i, w) in sizes.windows(2).enumerate() {
+            let (weights, after) = rest.split_at(w[0] * w[1]);
+            let (biases, after) = after.split_at(w[1]);
+            rest = after;
+
+            layers.push(Layer {
+                weights: weights.to_vec(),
+                biases: biases.to_vec(),
+                activation: match i + 2 == sizes.len() {
+                    true => Activation::Linear,
+                    false => Activation::Tanh,
+                },
+            });
+        }
+
+        Mlp {
+            inputs: OBSERVATION_SIZE,
+            layers,
+        }
+    }
+
+    // Weights scaled to each layer's inputs so tanh doesn't saturate, zero
+    // biases.
+    fn random_genome(&self, rng: &mut ArenaRng) -> Vec<f32> {
+        let mut genome = Vec::with_capacity(self.genome_len());
+
+        for w in self.sizes().windows(2) {
+            let scale = 1.0 / (w[0] as f32).sqrt();
+            genome.extend((0..w[0] * w[1]).map(|_| gaussian(rng) * scale));
+            genome.extend((0..w[1]).map(|_| 0.0));
+        }
+
+        genome
+    }
+
+    // The opponents, for the left paddle then the right.
+    fn opponents(&self) -> Result<Vec<[Builtin; 2]>, String> {
+        self.opponents
+            .iter()
+            .map(|command| {
+                let side = |paddle| match Builtin::from_command(command, paddle, self.seed) {
+                    Some(builtin) => builtin,
+                    None => Err(format!(
+                        "\"{}\" isn't an \"ai:\" or \"nn:\" player",
+                        command
+                    )),
+                };
+                Ok([side(0)?, side(1)?])
+            })
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.population == 0 || self.elite >= self.population {
+            return Err("population must be larger than elite".to_string());
+        }
+        if self.tournament_size == 0 || self.matches == 0 || self.opponents.is_empty() {
+            return Err("tournament_size, matches and opponents can't be empty".to_string());
+        }
+        if self.hidden.contains(&0) {
+            return Err("hidden layers can't be empty".to_string());
+        }
+
+        self.opponents().map(|_| ())
+    }
+}
+
+pub struct GenerationStats {
+    // Generations evaluated so far, counting this one.
+    pub generation: usize,
+    pub best: f32,
+    pub mean: f32,
+    pub worst: f32,
+    // The best network of this generation.
+    pub champion: Mlp,
+}
+
+impl GenerationStats {
+    // A line of the fitness log, see `CSV_HEADER`.
+    pub fn csv_row(&self, seconds: f64) -> String {
+        format!(
+            "{},{},{},{},{:.3}",
+            self.generation, self.best, self.mean, self.worst, seconds
+        )
+    }
+}
+
+// The genomes of the next generation to evaluate, saved as a checkpoint that
+// training continues from.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Population {
+    // Generations evaluated so far.
+    pub generation: usize,
+    pub genomes: Vec<Vec<f32>>,
+    pub config: TrainConfig,
+}
+
+impl Population {
+    pub fn new(config: TrainConfig) -> Result<Population, String> {
+        config.validate()?;
+
+        let mut rng = ArenaRng::seed_from_u64(config.seed);
+        let genomes = (0..config.population)
+            .map(|_| config.random_genome(&mut rng))
+            .collect();
+
+        Ok(Population {
+            generation: 0,
+            genomes,
+            config,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Population, String> {
+        let population: Population = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| toml::from_str(&text).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        population.config.validate()?;
+        let len = population.config.genome_len();
+        if population.genomes.iter().any(|genome| genome.len() != len) {
+            return Err(format!(
+                "{}: genomes don't match the hidden layers",
+                path.display()
+            ));
+        }
+
+        Ok(population)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Plays every genome in parallel, then breeds the next generation from
+    // the best ones.
+    pub fn evolve(&mut self) -> GenerationStats {
+        let config = &self.config;
+        let opponents = config
+            .opponents()
+            .expect("opponents were checked when training started");
+        let seed = generation_seed(config.seed, self.generation);
+
+        let fitness: Vec<f32> = self
+            .genomes
+            .par_iter()
+            .map(|genome| fitness(config, &config.network(genome), &opponents, seed))
+            .collect();
+
+        let mut order: Vec<usize> = (0..self.genomes.len()).collect();
+        order.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+
+        self.generation += 1;
+        let stats = GenerationStats {
+            generation: self.generation,
+            best: fitness[order[0]],
+            mean: fitness.iter().sum::<f32>() / fitness.len() as f32,
+            worst: fitness[order[order.len() - 1]],
+            champion: config.network(&self.genomes[order[0]]),
+        };
+
+        let mut rng = ArenaRng::seed_from_u64(seed);
+        let mut next: Vec<Vec<f32>> = order
+            .iter()
+            .take(config.elite)
+            .map(|&i| self.genomes[i].clone())
+            .collect();
+
+        while next.len() < config.population {
+            let mut pick = || {
+                (0..config.tournament_size)
+                    .map(|_| rng.gen_range(0..order.len()))
+                    .min()
+                    .map_or(order[0], |rank| order[rank])
+            };
+            let (a, b) = (pick(), pick());
+
+            // Uniform crossover, then gaussian mutation.
+            let child = self.genomes[a]
+                .iter()
+                .zip(self.genomes[b].iter())
+                .map(|(&x, &y)| {
+                    let gene = if rng.gen::<bool>() { x } else { y };
+                    match rng.gen::<f32>() < config.mutation_rate {
+                        true => gene + gaussian(&mut rng) * config.mutation_strength,
+                        false => gene,
+                    }
+                })
+                .collect();
+            next.push(child);
+        }
+
+        self.genomes = next;
+        stats
+    }
+}
+
+fn generation_seed(seed: u64, generation: usize) -> u64 {
+    seed.wrapping_add(generation as u64)
+        .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+// Total reward of `network` over `config.matches` matches against
+// `opponents` in turn, on the left in the first round and then changing
+// sides.
+pub fn fitness(config: &TrainConfig, network: &Mlp, opponents: &[[Builtin; 2]], seed: u64) -> f32 {
+    (0..config.matches)
+        .map(|game| {
+            let side = (game / opponents.len()) % 2;
+            let opponent = opponents[game % opponents.len()][1 - side].clone();
+            play(
+                config,
+                network,
+                side,
+                opponent,
+                seed.wrapping_add(game as u64),
+            )
+        })
+        .sum()
+}
+
+fn play(config: &TrainConfig, network: &Mlp, side: usize, mut opponent: Builtin, seed: u64) -> f32 {
+    let mut env = Env::new(EnvConfig {
+        tuning: config.tuning,
+        rewards: Rewards {
+            goal: 1.0,
+            hit_bonus: config.hit_bonus,
+        },
+        max_steps: config.max_steps,
+        ..EnvConfig::default()
+    });
+    let dt = env.config.dt;
+    env.reset(seed);
+
+    let mut player = NeuralAi::new(network.clone(), side)
+        .expect("trained networks take an observation and score the actions");
+    let mut total = 0.0;
+
+    loop {
+        let mut actions = [Action::Stay; 2];
+        actions[side] = player.act(&env.arena);
+        actions[1 - side] = opponent.act(&env.arena, dt);
+
+        let (_, rewards, done, _) = env.step(actions);
+        total += rewards[side];
+        if done {
+            return total;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small() -> TrainConfig {
+        TrainConfig {
+            population: 8,
+            hidden: vec![4],
+            elite: 2,
+            matches: 2,
+            max_steps: 60 * 20,
+            ..TrainConfig::default()
+        }
+    }
+
+    #[test]
+    fn genomes_encode_networks_of_the_configured_shape() {
+        let config = TrainConfig {
+            hidden: vec![5, 4],
+            ..TrainConfig::default()
+        };
+        let population = Population::new(config.clone()).unwrap();
+        let genome = &population.genomes[0];
+        assert_eq!(genome.len(), 8 * 5 + 5 + 5 * 4 + 4 + 4 * 3 + 3);
+
+        let network = config.network(genome);
+        assert_eq!(network.layers.len(), 3);
+        assert_eq!(network.outputs(), 3);
+        assert_eq!(network.layers[2].activation, Activation::Linear);
+        assert!(NeuralAi::new(network, 1).is_ok());
+    }
+
+    #[test]
+    fn training_resumes_from_checkpoints_exactly() {
+        let mut straight = Population::new(small()).unwrap();
+        let mut resumed = Population::new(small()).unwrap();
+
+        straight.evolve();
+        resumed.evolve();
+        let text = toml::to_string(&resumed).unwrap();
+        let mut resumed: Population = toml::from_str(&text).unwrap();
+
+        let a = straight.evolve();
+        let b = resumed.evolve();
+        assert_eq!(a.generation, 2);
+        assert_eq!((a.best, a.mean), (b.best, b.mean));
+        assert_eq!(a.champion, b.champion);
+        assert_eq!(straight.genomes, resumed.genomes);
+    }
+
+    #[test]
+    fn evolution_beats_random_networks() {
+        let config = TrainConfig {
+            population: 16,
+            ..small()
+        };
+        let opponents = config.opponents().unwrap();
+        let mut population = Population::new(config.clone()).unwrap();
+
+        let first = population.evolve().champion;
+        let mut last = first.clone();
+        for _ in 0..6 {
+            last = population.evolve().champion;
+        }
+
+        // On serves none of them trained on.
+        let score = |network: &Mlp| fitness(&config, network, &opponents, 12345);
+        assert!(
+            score(&last) > score(&first),
+            "{} vs {}",
+            score(&last),
+            score(&first)
+        );
+    }
+
+    #[test]
+    fn bad_configs_are_refused() {
+        for config in [
+            TrainConfig {
+                elite: 64,
+                ..TrainConfig::default()
+            },
+            TrainConfig {
+                opponents: vec!["python3 bots/follow_bot.py".to_string()],
+                ..TrainConfig::default()
+            },
+            TrainConfig {
+                opponents: vec!["ai:nobody".to_string()],
+                ..TrainConfig::default()
+            },
+        ] {
+            assert!(Population::new(config).is_err());
+        }
+    }
+}
@@ -1,119 +1,173 @@
-use std::time::Duration;
-
-use crate::{assets::Assets, ball::Ball, paddle::Paddle, particle::Particle, pong::Wall};
-
-use ggez::{
-    graphics::{drawable_size, Rect},
-    Context, GameResult,
+use crate::{
+    arena::{Arena, ArenaRng},
+    assets::Assets,
+    audio::{AudioBackend, Mixer, NullBackend, RodioBackend},
+    bot::{Bot, Builtin, Forfeit},
+    camera::Camera,
+    config::{Config, Tuning},
+    console::Console,
+    debug::DebugState,
+    difficulty::Difficulty,
+    hot_reload::FileWatcher,
+    locale::Localizer,
+    music::MusicPlayer,
+    particle::Particle,
+    render_cache::RenderCache,
+    scene::SceneStack,
+    scenes::MenuScene,
+    scripting::ScriptEngine,
+    theme::Theme,
+    toast::Toast,
 };
 
-use crate::pong::Side;
+use std::time::SystemTime;
+
+use ggez::{graphics::drawable_size, Context, GameResult};
+use rand::{thread_rng, SeedableRng};
 
-use crate::menu::Menu;
+use crate::menu::MenuPage;
 
 pub const DEFAULT_TIME_SCALE: f64 = 1.0;
 
-pub enum GameMode {
-    Menu,
-    Game,
-}
+// Size of the playing field in logical units, whatever the window size.
+pub const ARENA_WIDTH: f32 = 1280.0;
+pub const ARENA_HEIGHT: f32 = 720.0;
 
 pub struct GameState {
     pub dt: f64,
     pub mouse_x: f32,
     pub mouse_y: f32,
-    pub debug_mode: bool,
-    pub play_sounds: bool,
+    pub debug: DebugState,
+    pub console: Console,
     pub show_particles: bool,
     pub game_width: f32,
     pub game_height: f32,
-    pub game_mode: GameMode,
-    pub ball: Ball,
-    pub walls: Vec<Wall>,
-    pub paddles: Vec<Paddle>,
-    pub paused: Option<Duration>,
-    pub player1_score: usize,
-    pub player2_score: usize,
+    pub scenes: SceneStack,
+    pub arena: Arena,
+    // Bots playing the left and right paddle, see `bot.rs`.
+    pub bots: [Option<Bot>; 2],
+    // Built-in AIs and networks playing a paddle, see `ai.rs` and `mlp.rs`.
+    pub ais: [Option<Builtin>; 2],
+    // Command lines for the next match's bots, empty for a human.
+    pub bot_commands: [String; 2],
+    pub forfeit: Option<Forfeit>,
+    // Rhai scripts, see `scripting.rs`.
+    pub scripts: ScriptEngine,
+    // Adapts AIs to human players, see `difficulty.rs`.
+    pub difficulty: Difficulty,
     pub particles: Vec<Particle>,
-    pub menu: Menu,
     pub assets: Assets,
+    pub mixer: Mixer,
+    pub music: MusicPlayer,
+    pub themes: Vec<Theme>,
+    pub locale: Localizer,
+    pub theme_index: usize,
+    pub config: Config,
+    // Tuning changed from the console, kept out of `config` so it isn't saved.
+    pub session_tuning: Option<Tuning>,
+    // Set when the config file is broken and couldn't be backed up, see
+    // `Config::load`.
+    pub config_read_only: bool,
+    // Modification time of the config file after the game last saved it, so
+    // the file watcher only reloads edits made by someone else.
+    pub config_saved_at: Option<SystemTime>,
+    pub toasts: Vec<Toast>,
+    pub file_watcher: FileWatcher,
+    pub render_cache: RenderCache,
+    pub camera: Camera,
+    pub vsync_at_start: bool,
 }
 
 impl GameState {
-    pub fn new(ctx: &mut Context) -> GameResult<GameState> {
+    pub fn new(ctx: &mut Context, config: Config) -> GameResult<GameState> {
         let dt = 1.0 / 60.0;
 
-        let (game_width, game_height) = drawable_size(ctx);
-
-        // Create the paddles
-        let left_paddle = Paddle::new(game_width, game_height, Side::Left);
-        let right_paddle = Paddle::new(game_width, game_height, Side::Right);
+        let (game_width, game_height) = (ARENA_WIDTH, ARENA_HEIGHT);
 
-        // Vector of paddles to easily iterate over each paddle and check collisions with one loop.
-        let paddles = vec![left_paddle, right_paddle];
+        let mut camera = Camera::new(game_width, game_height);
+        let (window_width, window_height) = drawable_size(ctx);
+        camera.resize(window_width, window_height);
 
-        // Vec of walls for the same reason.
-        let walls = vec![
-            Wall::new(Rect::new(0.0, 0.0, game_width, 0.0), Side::Top),
-            Wall::new(Rect::new(0.0, 0.0, 0.0, game_height), Side::Left),
-            Wall::new(Rect::new(game_width, 0.0, 0.0, game_height), Side::Right),
-            Wall::new(Rect::new(0.0, game_height, game_width, 0.0), Side::Bottom),
-        ];
-
-        // Create the ball.
-        let ball = Ball::new(game_width, game_height);
-
-        // Initialize the menu
-        let menu = Menu::new(0);
+        // Create the ball, paddles and walls.
+        let tuning = config.effective_tuning();
+        let arena = Arena::new(game_width, game_height, tuning, ArenaRng::from_entropy());
 
         // Initialize particles
-        let assets = Assets::new(ctx)?;
-
-        let particles: Vec<Particle> = vec![Particle::new(ball.x, ball.y, false); 12];
+        let mut assets = Assets::new(ctx)?;
+        assets.synthesize(&config.sounds);
+
+        let particles: Vec<Particle> =
+            vec![Particle::new(arena.ball.x, arena.ball.y, false, &mut thread_rng()); 12];
+
+        let themes = Theme::load_all(ctx);
+        let theme_index = themes
+            .iter()
+            .position(|theme| theme.name == config.theme)
+            .unwrap_or(0);
+        let locale = Localizer::load(ctx, &config.language);
+
+        let backend: Box<dyn AudioBackend> = match RodioBackend::new() {
+            Some(backend) => Box::new(backend),
+            None => {
+                eprintln!("warning: no audio output device found, sound is disabled");
+                Box::new(NullBackend)
+            }
+        };
+        let mixer = Mixer::new(config.audio, backend);
+        let music = MusicPlayer::load(config.audio.shuffle_music);
 
         // Initialize the state
-        let s = GameState {
+        let mut s = GameState {
             dt,
             mouse_x: 0.0,
             mouse_y: 0.0,
-            debug_mode: false,
-            play_sounds: true,
+            debug: DebugState::default(),
+            console: Console::default(),
             show_particles: true,
             game_width,
             game_height,
-            game_mode: GameMode::Game,
-            ball,
-            walls,
-            paddles,
-            paused: None,
-            player1_score: 0,
-            player2_score: 0,
+            scenes: SceneStack::new(Box::new(MenuScene::new(MenuPage::Title))),
+            arena,
+            bots: [None, None],
+            ais: [None, None],
+            bot_commands: [config.bots.left.clone(), config.bots.right.clone()],
+            forfeit: None,
+            scripts: ScriptEngine::new(),
+            difficulty: Difficulty::new(config.difficulty),
             particles,
-            menu,
             assets,
+            mixer,
+            music,
+            themes,
+            locale,
+            theme_index,
+            vsync_at_start: config.display.vsync,
+            config,
+            session_tuning: None,
+            config_read_only: false,
+            config_saved_at: None,
+            toasts: Vec::new(),
+            file_watcher: GameState::watch_files(),
+            render_cache: RenderCache::default(),
+            camera,
         };
+        s.reload_scripts();
 
         Ok(s)
     }
 
-    pub fn toggle_menu(&mut self) {
-        match self.game_mode {
-            GameMode::Game => {
-                self.menu = Menu::new(self.menu.current_menu_choice);
-                self.game_mode = GameMode::Menu;
-            }
-            GameMode::Menu => {
-                self.menu = Menu::new(self.menu.current_menu_choice);
-                self.game_mode = GameMode::Game;
-            }
+    pub fn stop_particles(&mut self) {
+        for particle in self.particles.iter_mut() {
+            particle.is_dead = true;
         }
+    }
 
-        self.stop_particles();
+    // Translated text for `key`, see `locale.rs`.
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        self.locale.get(key)
     }
 
-    pub fn stop_particles(&mut self) {
-        for particle in self.particles.iter_mut() {
-            (*particle).is_dead = true;
-        }
+    pub fn theme(&self) -> &Theme {
+        &self.themes[self.theme_index]
     }
 }
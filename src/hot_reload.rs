@@ -0,0 +1,325 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use ggez::Context;
+
+use crate::{
+    config::{Config, Tuning, CONFIG_PATH, RESOURCE_DIR},
+    game_state::GameState,
+    locale::{Localizer, LOCALE_DIR},
+    scripting::SCRIPT_DIR,
+    theme::{Theme, THEME_DIR},
+    toast::Toast,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Polls modification times on a background thread and reports changed files.
+// Polling keeps this dependency free and works the same on every platform.
+pub struct FileWatcher {
+    changes: Receiver<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new(roots: Vec<PathBuf>) -> FileWatcher {
+        let (sender, changes) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut known = HashMap::new();
+            for root in roots.iter() {
+                scan(root, &mut known);
+            }
+
+            loop {
+                thread::sleep(POLL_INTERVAL);
+
+                let mut current = HashMap::new();
+                for root in roots.iter() {
+                    scan(root, &mut current);
+                }
+
+                for (path, modified) in current.iter() {
+                    if known.get(path) != Some(modified) && sender.send(path.clone()).is_err() {
+                        // The game is gone.
+                        return;
+                    }
+                }
+
+                known = current;
+            }
+        });
+
+        FileWatcher { changes }
+    }
+
+    // Changed files since the last call, without duplicates.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        let mut changed: Vec<PathBuf> = self.changes.try_iter().collect();
+        changed.sort();
+        changed.dedup();
+
+        changed
+    }
+}
+
+fn scan(path: &Path, files: &mut HashMap<PathBuf, SystemTime>) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+    };
+
+    if metadata.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                scan(&entry.path(), files);
+            }
+        }
+    } else if let Ok(modified) = metadata.modified() {
+        files.insert(path.to_path_buf(), modified);
+    }
+}
+
+// Turns a file under the resource directory into a ggez resource path.
+fn resource_path(path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(RESOURCE_DIR).ok()?;
+    let relative = relative.to_str()?.replace('\\', "/");
+
+    Some(format!("/{}", relative))
+}
+
+pub fn config_modified() -> Option<SystemTime> {
+    fs::metadata(CONFIG_PATH)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Swaps in a reloaded value, or keeps the previous one if it failed to load.
+pub fn keep_previous<T>(current: &mut T, reloaded: Result<T, String>) -> Result<(), String> {
+    *current = reloaded?;
+    Ok(())
+}
+
+// Puts a reloaded theme file in place of its previous version, so the
+// selection stays on it, or after the others if the file is new. Returns the
+// theme's index.
+pub fn replace_theme(
+    themes: &mut Vec<Theme>,
+    path: &str,
+    reloaded: Result<Theme, String>,
+) -> Result<usize, String> {
+    let theme = reloaded?;
+
+    match themes
+        .iter()
+        .position(|theme| theme.path.as_deref() == Some(path))
+    {
+        Some(index) => {
+            themes[index] = theme;
+            Ok(index)
+        }
+        None => {
+            themes.push(theme);
+            Ok(themes.len() - 1)
+        }
+    }
+}
+
+impl GameState {
+    pub fn watch_files() -> FileWatcher {
+        FileWatcher::new(vec![
+            PathBuf::from(RESOURCE_DIR),
+            PathBuf::from(CONFIG_PATH),
+        ])
+    }
+
+    // Called from `update` before simulating, so nothing is mid-frame.
+    pub fn hot_reload(&mut self, ctx: &mut Context) {
+        let mut theme_files = Vec::new();
+        let mut sprites_changed = false;
+        let mut locales_changed = false;
+        let mut scripts_changed = false;
+
+        for path in self.file_watcher.changed() {
+            if path == Path::new(CONFIG_PATH) {
+                if self.config_saved_at.is_none() || config_modified() != self.config_saved_at {
+                    self.reload_config();
+                }
+                continue;
+            }
+
+            let resource = match resource_path(&path) {
+                Some(resource) => resource,
+                None => continue,
+            };
+
+            if resource.starts_with(THEME_DIR) {
+                if resource.ends_with(".toml") {
+                    theme_files.push(resource);
+                }
+                continue;
+            }
+
+            if self.is_theme_sprite(&resource) {
+                sprites_changed = true;
+                continue;
+            }
+
+            if resource.starts_with(LOCALE_DIR) {
+                locales_changed = true;
+                continue;
+            }
+
+            if resource.starts_with(SCRIPT_DIR) {
+                scripts_changed = true;
+                continue;
+            }
+
+            match self.assets.reload(ctx, &resource) {
+                Ok(ids) if ids.is_empty() => (),
+                Ok(ids) => self
+                    .toasts
+                    .push(Toast::info(format!("Reloaded {}", ids.join(", ")))),
+                Err(e) => self
+                    .toasts
+                    .push(Toast::error(format!("Reload failed: {}", e))),
+            }
+        }
+
+        for resource in theme_files {
+            let reloaded = Theme::from_file(ctx, &resource);
+            match replace_theme(&mut self.themes, &resource, reloaded) {
+                Ok(index) => self.toasts.push(Toast::info(format!(
+                    "Reloaded theme {}",
+                    self.themes[index].name
+                ))),
+                Err(e) => self.toasts.push(Toast::error(format!(
+                    "Reload of {} failed: {}",
+                    resource, e
+                ))),
+            }
+        }
+
+        if sprites_changed {
+            for theme in self.themes.iter_mut() {
+                theme.load_sprites(ctx);
+            }
+
+            self.toasts
+                .push(Toast::info(String::from("Reloaded theme sprites")));
+        }
+
+        if locales_changed {
+            self.locale = Localizer::load(ctx, &self.config.language);
+
+            self.toasts
+                .push(Toast::info(String::from("Reloaded languages")));
+        }
+
+        // Scripts that fail to compile report it and keep running their
+        // previous version.
+        if scripts_changed {
+            self.toasts
+                .push(Toast::info(String::from("Reloaded scripts")));
+            self.reload_scripts();
+        }
+    }
+
+    fn is_theme_sprite(&self, resource: &str) -> bool {
+        self.themes.iter().any(|theme| {
+            [
+                &theme.background_image,
+                &theme.paddle_sprite,
+                &theme.ball_sprite,
+            ]
+            .iter()
+            .any(|sprite| sprite.as_deref() == Some(resource))
+        })
+    }
+
+    fn reload_config(&mut self) {
+        if let Err(e) = keep_previous(&mut self.config, Config::read()) {
+            self.toasts.push(Toast::error(format!(
+                "Reload of {} failed: {}",
+                CONFIG_PATH, e
+            )));
+            return;
+        }
+
+        // Fixed, so it can be saved over again.
+        self.config_read_only = false;
+        self.mixer.settings = self.config.audio;
+        self.locale.set_language(&self.config.language);
+        self.assets.synthesize(&self.config.sounds);
+        self.apply_tuning();
+        self.difficulty.settings = self.config.difficulty;
+        self.apply_difficulty();
+
+        self.toasts
+            .push(Toast::info(format!("Reloaded {}", CONFIG_PATH)));
+    }
+
+    // The config's tuning, or the console's changes to it.
+    pub fn tuning(&self) -> Tuning {
+        self.session_tuning.unwrap_or(self.config.tuning)
+    }
+
+    pub fn apply_tuning(&mut self) {
+        let tuning = self.config.accessibility.resize(self.tuning());
+        self.arena.retune(tuning);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMBER: &str = include_str!("../resources/themes/amber.toml");
+
+    fn amber(name: &str) -> Result<Theme, String> {
+        let mut theme = Theme::parse(&AMBER.replace("\"Amber\"", &format!("\"{}\"", name)))?;
+        theme.path = Some(String::from("/themes/amber.toml"));
+        Ok(theme)
+    }
+
+    #[test]
+    fn broken_theme_keeps_the_previous_version() {
+        let mut themes = vec![Theme::classic(), amber("Amber").unwrap(), Theme::neon()];
+
+        let broken = Theme::parse("name = \"Amber\"\nbackground = [1, 2]");
+        assert!(replace_theme(&mut themes, "/themes/amber.toml", broken).is_err());
+        assert_eq!(themes.len(), 3);
+        assert_eq!(themes[1].name, "Amber");
+
+        let fixed = amber("Amber 2");
+        assert_eq!(
+            replace_theme(&mut themes, "/themes/amber.toml", fixed),
+            Ok(1)
+        );
+        assert_eq!(themes[1].name, "Amber 2");
+        assert_eq!(themes[2].name, "Neon");
+
+        let mut new = amber("Copper").unwrap();
+        new.path = Some(String::from("/themes/copper.toml"));
+        assert_eq!(
+            replace_theme(&mut themes, "/themes/copper.toml", Ok(new)),
+            Ok(3)
+        );
+    }
+
+    #[test]
+    fn broken_config_keeps_the_previous_version() {
+        let mut config = Config::parse("language = \"de\"").unwrap();
+
+        assert!(keep_previous(&mut config, Config::parse("language = ")).is_err());
+        assert_eq!(config.language, "de");
+
+        assert!(keep_previous(&mut config, Config::parse("language = \"fr\"")).is_ok());
+        assert_eq!(config.language, "fr");
+    }
+}
@@ -0,0 +1,246 @@
+use ggez::input::keyboard::KeyCode;
+use serde::{Deserialize, Serialize};
+
+// Keys that can be bound to paddle controls, with the names used in the
+// config file and the Controls menu.
+const KEY_NAMES: [(KeyCode, &str); 56] = [
+    (KeyCode::A, "A"),
+    (KeyCode::B, "B"),
+    (KeyCode::C, "C"),
+    (KeyCode::D, "D"),
+    (KeyCode::E, "E"),
+    (KeyCode::F, "F"),
+    (KeyCode::G, "G"),
+    (KeyCode::H, "H"),
+    (KeyCode::I, "I"),
+    (KeyCode::J, "J"),
+    (KeyCode::K, "K"),
+    (KeyCode::L, "L"),
+    (KeyCode::M, "M"),
+    (KeyCode::N, "N"),
+    (KeyCode::O, "O"),
+    (KeyCode::P, "P"),
+    (KeyCode::Q, "Q"),
+    (KeyCode::R, "R"),
+    (KeyCode::S, "S"),
+    (KeyCode::T, "T"),
+    (KeyCode::U, "U"),
+    (KeyCode::V, "V"),
+    (KeyCode::W, "W"),
+    (KeyCode::X, "X"),
+    (KeyCode::Y, "Y"),
+    (KeyCode::Z, "Z"),
+    (KeyCode::Key0, "0"),
+    (KeyCode::Key1, "1"),
+    (KeyCode::Key2, "2"),
+    (KeyCode::Key3, "3"),
+    (KeyCode::Key4, "4"),
+    (KeyCode::Key5, "5"),
+    (KeyCode::Key6, "6"),
+    (KeyCode::Key7, "7"),
+    (KeyCode::Key8, "8"),
+    (KeyCode::Key9, "9"),
+    (KeyCode::Numpad0, "Numpad0"),
+    (KeyCode::Numpad1, "Numpad1"),
+    (KeyCode::Numpad2, "Numpad2"),
+    (KeyCode::Numpad3, "Numpad3"),
+    (KeyCode::Numpad4, "Numpad4"),
+    (KeyCode::Numpad5, "Numpad5"),
+    (KeyCode::Numpad6, "Numpad6"),
+    (KeyCode::Numpad7, "Numpad7"),
+    (KeyCode::Numpad8, "Numpad8"),
+    (KeyCode::Numpad9, "Numpad9"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Space, "Space"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::LShift, "LShift"),
+    (KeyCode::RShift, "RShift"),
+    (KeyCode::LControl, "LControl"),
+    (KeyCode::RControl, "RControl"),
+];
+
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(code, _)| *code == key)
+        .map(|(_, name)| *name)
+}
+
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    KEY_NAMES
+        .iter()
+        .find(|(_, key_name)| key_name.eq_ignore_ascii_case(name))
+        .map(|(code, _)| *code)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PaddleAction {
+    LeftUp,
+    LeftDown,
+    RightUp,
+    RightDown,
+}
+
+impl PaddleAction {
+    pub const ALL: [PaddleAction; 4] = [
+        PaddleAction::LeftUp,
+        PaddleAction::LeftDown,
+        PaddleAction::RightUp,
+        PaddleAction::RightDown,
+    ];
+
+    pub fn paddle_index(self) -> usize {
+        match self {
+            PaddleAction::LeftUp | PaddleAction::LeftDown => 0,
+            PaddleAction::RightUp | PaddleAction::RightDown => 1,
+        }
+    }
+
+    pub fn is_up(self) -> bool {
+        matches!(self, PaddleAction::LeftUp | PaddleAction::RightUp)
+    }
+}
+
+// Paddle key bindings, the `[controls]` table of the config file.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Controls {
+    #[serde(with = "serde_key")]
+    pub left_up: KeyCode,
+    #[serde(with = "serde_key")]
+    pub left_down: KeyCode,
+    #[serde(with = "serde_key")]
+    pub right_up: KeyCode,
+    #[serde(with = "serde_key")]
+    pub right_down: KeyCode,
+}
+
+impl Default for Controls {
+    fn default() -> Controls {
+        Controls {
+            left_up: KeyCode::W,
+            left_down: KeyCode::S,
+            right_up: KeyCode::Up,
+            right_down: KeyCode::Down,
+        }
+    }
+}
+
+impl Controls {
+    pub fn key(&self, action: PaddleAction) -> KeyCode {
+        match action {
+            PaddleAction::LeftUp => self.left_up,
+            PaddleAction::LeftDown => self.left_down,
+            PaddleAction::RightUp => self.right_up,
+            PaddleAction::RightDown => self.right_down,
+        }
+    }
+
+    pub fn action(&self, key: KeyCode) -> Option<PaddleAction> {
+        PaddleAction::ALL
+            .iter()
+            .copied()
+            .find(|action| self.key(*action) == key)
+    }
+
+    // Binding a key that is already used swaps the two bindings, so no key
+    // does two things. Returns false for keys that can't be bound.
+    pub fn bind(&mut self, action: PaddleAction, key: KeyCode) -> bool {
+        if key_name(key).is_none() {
+            return false;
+        }
+
+        let previous = self.key(action);
+        if let Some(other) = self.action(key) {
+            *self.key_mut(other) = previous;
+        }
+        *self.key_mut(action) = key;
+
+        true
+    }
+
+    fn key_mut(&mut self, action: PaddleAction) -> &mut KeyCode {
+        match action {
+            PaddleAction::LeftUp => &mut self.left_up,
+            PaddleAction::LeftDown => &mut self.left_down,
+            PaddleAction::RightUp => &mut self.right_up,
+            PaddleAction::RightDown => &mut self.right_down,
+        }
+    }
+}
+
+// `#[serde(with = "keys::serde_key")]` for `KeyCode` fields.
+pub mod serde_key {
+    use ggez::input::keyboard::KeyCode;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(key: &KeyCode, serializer: S) -> Result<S::Ok, S::Error> {
+        match super::key_name(*key) {
+            Some(name) => serializer.serialize_str(name),
+            None => Err(serde::ser::Error::custom(format!(
+                "{:?} can't be bound",
+                key
+            ))),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KeyCode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        super::parse_key(&name)
+            .ok_or_else(|| de::Error::custom(format!("unknown key \"{}\"", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for (key, name) in KEY_NAMES.iter() {
+            assert_eq!(key_name(*key), Some(*name));
+            assert_eq!(parse_key(name), Some(*key));
+        }
+    }
+
+    #[test]
+    fn parsing_ignores_case() {
+        assert_eq!(parse_key("up"), Some(KeyCode::Up));
+        assert_eq!(parse_key("w"), Some(KeyCode::W));
+        assert_eq!(parse_key("F11"), None);
+    }
+
+    #[test]
+    fn binding_a_used_key_swaps_bindings() {
+        let mut controls = Controls::default();
+
+        assert!(controls.bind(PaddleAction::LeftUp, KeyCode::S));
+        assert_eq!(controls.left_up, KeyCode::S);
+        assert_eq!(controls.left_down, KeyCode::W);
+        assert_eq!(controls.action(KeyCode::W), Some(PaddleAction::LeftDown));
+    }
+
+    #[test]
+    fn menu_keys_cant_be_bound() {
+        let mut controls = Controls::default();
+
+        assert!(!controls.bind(PaddleAction::RightUp, KeyCode::Escape));
+        assert_eq!(controls.right_up, KeyCode::Up);
+    }
+
+    #[test]
+    fn controls_round_trip_through_toml() {
+        let mut controls = Controls::default();
+        controls.bind(PaddleAction::RightDown, KeyCode::Numpad2);
+
+        let text = toml::to_string(&controls).unwrap();
+        assert!(text.contains("right_down = \"Numpad2\""));
+
+        let parsed: Controls = toml::from_str(&text).unwrap();
+        assert_eq!(parsed.right_down, KeyCode::Numpad2);
+        assert_eq!(parsed.left_up, KeyCode::W);
+    }
+}
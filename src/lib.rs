@@ -0,0 +1,44 @@
+// The game's modules, shared by the game binary and the Python bindings in
+// `python/`.
+pub mod accessibility;
+pub mod ai;
+pub mod alloc_counter;
+pub mod arena;
+pub mod assets;
+pub mod audio;
+pub mod ball;
+pub mod batch;
+pub mod bench;
+pub mod bot;
+pub mod camera;
+pub mod config;
+pub mod console;
+pub mod debug;
+pub mod difficulty;
+pub mod display;
+pub mod env;
+pub mod evolve;
+pub mod game_state;
+pub mod hot_reload;
+pub mod keys;
+pub mod locale;
+pub mod manifest;
+pub mod menu;
+pub mod menus;
+pub mod mlp;
+pub mod music;
+pub mod paddle;
+pub mod particle;
+pub mod player_control;
+pub mod pong;
+pub mod raster;
+pub mod render;
+pub mod render_cache;
+pub mod scene;
+pub mod scenes;
+pub mod scripting;
+pub mod stats;
+pub mod synth;
+pub mod theme;
+pub mod toast;
+pub mod tournament;
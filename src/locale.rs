@@ -0,0 +1,238 @@
+use std::{collections::BTreeMap, io::Read, path::Path};
+
+use ggez::{filesystem, Context};
+use serde::Deserialize;
+
+pub const LOCALE_DIR: &str = "/locales";
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+// English is built in, so a missing or broken locale directory still leaves
+// every key with a translation.
+const ENGLISH: &str = include_str!("../resources/locales/en.toml");
+
+// One string table, `resources/locales/<code>.toml`. Values can contain
+// `{placeholders}` that are filled in by `Localizer::format`.
+#[derive(Clone, Deserialize)]
+pub struct Locale {
+    #[serde(skip)]
+    pub code: String,
+    // Shown in the language selector, in the language itself.
+    pub name: String,
+    // Font ID from the asset manifest for scripts the theme font can't draw.
+    #[serde(default)]
+    pub font: Option<String>,
+    pub strings: BTreeMap<String, String>,
+}
+
+impl Locale {
+    pub fn parse(code: &str, contents: &str) -> Result<Locale, String> {
+        let mut locale: Locale = toml::from_str(contents).map_err(|e| e.to_string())?;
+        locale.code = code.to_string();
+
+        Ok(locale)
+    }
+
+    pub fn english() -> Locale {
+        Locale::parse(DEFAULT_LANGUAGE, ENGLISH).expect("built-in English strings are invalid")
+    }
+}
+
+pub struct Localizer {
+    // English first, it is the fallback for missing keys.
+    locales: Vec<Locale>,
+    current: usize,
+}
+
+impl Localizer {
+    pub fn new(locales: Vec<Locale>, language: &str) -> Localizer {
+        let mut localizer = Localizer {
+            locales,
+            current: 0,
+        };
+
+        if !localizer.locales.iter().any(|l| l.code == DEFAULT_LANGUAGE) {
+            localizer.locales.insert(0, Locale::english());
+        }
+        localizer.set_language(language);
+
+        localizer
+    }
+
+    pub fn load(ctx: &mut Context, language: &str) -> Localizer {
+        let mut locales = vec![Locale::english()];
+
+        let mut paths: Vec<_> = match filesystem::read_dir(ctx, LOCALE_DIR) {
+            Ok(paths) => paths
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+
+        for path in paths {
+            match read_locale(ctx, &path) {
+                Ok(locale) if locale.code == DEFAULT_LANGUAGE => locales[0] = locale,
+                Ok(locale) => locales.push(locale),
+                Err(e) => eprintln!("warning: skipping locale {}: {}", path.display(), e),
+            }
+        }
+
+        Localizer::new(locales, language)
+    }
+
+    // Unknown languages fall back to English. Returns false in that case.
+    pub fn set_language(&mut self, code: &str) -> bool {
+        let index = self.locales.iter().position(|l| l.code == code);
+        self.current = index.or_else(|| self.english_index()).unwrap_or(0);
+
+        index.is_some()
+    }
+
+    pub fn current(&self) -> &Locale {
+        &self.locales[self.current]
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn locales(&self) -> &[Locale] {
+        &self.locales
+    }
+
+    // The current language, then English, then the key itself so a missing
+    // string is visible rather than blank.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.current()
+            .strings
+            .get(key)
+            .or_else(|| {
+                self.english_index()
+                    .and_then(|i| self.locales[i].strings.get(key))
+            })
+            .map(String::as_str)
+            .unwrap_or(key)
+    }
+
+    // `get` with every `{name}` replaced by its value.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut text = self.get(key).to_string();
+
+        for (name, value) in args {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+
+        text
+    }
+
+    fn english_index(&self) -> Option<usize> {
+        self.locales.iter().position(|l| l.code == DEFAULT_LANGUAGE)
+    }
+}
+
+fn read_locale(ctx: &mut Context, path: &Path) -> Result<Locale, String> {
+    let code = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or("invalid file name")?;
+
+    let mut contents = String::new();
+    filesystem::open(ctx, path)
+        .and_then(|mut file| Ok(file.read_to_string(&mut contents)?))
+        .map_err(|e| e.to_string())?;
+
+    Locale::parse(code, &contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{collections::BTreeSet, fs};
+
+    fn shipped_locales() -> Vec<Locale> {
+        let mut locales: Vec<Locale> = fs::read_dir("resources/locales")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .map(|path| {
+                let code = path.file_stem().unwrap().to_str().unwrap().to_string();
+                let contents = fs::read_to_string(&path).unwrap();
+
+                Locale::parse(&code, &contents)
+                    .unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+            })
+            .collect();
+        locales.sort_by(|a, b| a.code.cmp(&b.code));
+
+        locales
+    }
+
+    fn placeholders(text: &str) -> BTreeSet<&str> {
+        text.split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split('}').next())
+            .collect()
+    }
+
+    #[test]
+    fn every_key_exists_in_every_shipped_locale() {
+        let english = Locale::english();
+        let locales = shipped_locales();
+        assert!(locales.len() > 1);
+
+        for locale in locales.iter() {
+            for (key, text) in english.strings.iter() {
+                let translated = locale
+                    .strings
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{}: missing \"{}\"", locale.code, key));
+
+                assert_eq!(
+                    placeholders(translated),
+                    placeholders(text),
+                    "{}: placeholders of \"{}\" differ",
+                    locale.code,
+                    key
+                );
+            }
+
+            for key in locale.strings.keys() {
+                assert!(
+                    english.strings.contains_key(key),
+                    "{}: unknown key \"{}\"",
+                    locale.code,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn missing_keys_fall_back_to_english() {
+        let german = Locale::parse(
+            "de",
+            "name = \"Deutsch\"\n[strings]\nmenu-quit = \"Beenden\"\n",
+        )
+        .unwrap();
+        let localizer = Localizer::new(vec![Locale::english(), german], "de");
+
+        assert_eq!(localizer.get("menu-quit"), "Beenden");
+        assert_eq!(localizer.get("menu-resume"), "Resume");
+        assert_eq!(localizer.get("no-such-key"), "no-such-key");
+    }
+
+    #[test]
+    fn unknown_languages_use_english() {
+        let mut localizer = Localizer::new(vec![Locale::english()], "xx");
+        assert_eq!(localizer.current().code, "en");
+        assert!(!localizer.set_language("xx"));
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let localizer = Localizer::new(vec![Locale::english()], "en");
+
+        assert_eq!(localizer.format("winner", &[("name", "ADA")]), "ADA WINS");
+    }
+}
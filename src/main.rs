@@ -1,31 +1,162 @@
 use ggez::{conf, event, GameResult};
 
-pub mod assets;
-pub mod ball;
-pub mod game_state;
-pub mod menu;
-pub mod paddle;
-pub mod particle;
-pub mod player_control;
-pub mod pong;
-pub mod render;
+use ggezgame::{
+    bench, config, display, game_state::GameState, manifest, raster, toast, tournament,
+};
 
-use crate::game_state::GameState;
-
-const GAME_WIDTH: f32 = 1280.0;
-const GAME_HEIGHT: f32 = 720.0;
+#[cfg(feature = "alloc-bench")]
+#[global_allocator]
+static ALLOCATOR: ggezgame::alloc_counter::CountingAllocator =
+    ggezgame::alloc_counter::CountingAllocator;
 
 fn main() -> GameResult {
-    let resource_dir = std::path::PathBuf::from("./resources");
+    let resource_dir = std::path::PathBuf::from(config::RESOURCE_DIR);
+    let args: Vec<String> = std::env::args().collect();
+
+    if args.iter().any(|arg| arg == "--check-assets") {
+        let problems = manifest::check_assets(&resource_dir);
+        for problem in problems.iter() {
+            eprintln!("{}", problem);
+        }
+
+        if !problems.is_empty() {
+            eprintln!("{} asset problem(s) found", problems.len());
+            std::process::exit(1);
+        }
+
+        println!("All assets OK");
+        return Ok(());
+    }
+
+    // `--bench-env [N]` steps the headless environment N times and exits.
+    if let Some(i) = args.iter().position(|arg| arg == "--bench-env") {
+        let steps = args
+            .get(i + 1)
+            .and_then(|steps| steps.parse().ok())
+            .unwrap_or(1_000_000);
+
+        bench::run_env_benchmark(steps);
+        return Ok(());
+    }
+
+    // `--bench-batch [N]` runs N arena steps per batch size and exits.
+    if let Some(i) = args.iter().position(|arg| arg == "--bench-batch") {
+        let steps = args
+            .get(i + 1)
+            .and_then(|steps| steps.parse().ok())
+            .unwrap_or(10_000_000);
+
+        bench::run_batch_benchmark(steps);
+        return Ok(());
+    }
+
+    // `--tournament [path]` plays the AIs in a TOML file, or the presets,
+    // against each other and prints the win rates.
+    if let Some(i) = args.iter().position(|arg| arg == "--tournament") {
+        let config = match args.get(i + 1) {
+            Some(path) => match std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| toml::from_str(&text).map_err(|e| e.to_string()))
+            {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Couldn't read {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            },
+            None => tournament::TournamentConfig::default(),
+        };
+
+        print!(
+            "{}",
+            tournament::report(&tournament::run_tournament(&config))
+        );
+        return Ok(());
+    }
+
+    // `--dump-frames <dir> [N]` saves N software-rendered frames and exits.
+    if let Some(i) = args.iter().position(|arg| arg == "--dump-frames") {
+        let dir = match args.get(i + 1) {
+            Some(dir) => std::path::Path::new(dir),
+            None => {
+                eprintln!("--dump-frames needs a directory");
+                std::process::exit(2);
+            }
+        };
+        let frames = args
+            .get(i + 2)
+            .and_then(|frames| frames.parse().ok())
+            .unwrap_or(120);
+
+        if let Err(e) = raster::dump_frames(dir, frames) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Checked before a window opens.
+    if !cfg!(feature = "alloc-bench") && args.iter().any(|arg| arg == "--bench-frames") {
+        eprintln!("--bench-frames needs the alloc-bench feature, see \"Benchmark\" in the README");
+        std::process::exit(2);
+    }
+
+    let (config, config_error) = config::Config::load();
 
     let cb = ggez::ContextBuilder::new("name", "author")
-        .window_setup(conf::WindowSetup::default().vsync(true))
-        .window_mode(conf::WindowMode::default().dimensions(GAME_WIDTH as f32, GAME_HEIGHT as f32))
+        .window_setup(display::window_setup(&config.display))
+        // Sound goes through our own mixer, see `audio.rs`.
+        .modules(conf::ModuleConf::default().audio(false))
+        .window_mode(display::window_mode(&config.display))
         .add_resource_path(resource_dir);
 
     let (mut ctx, mut events_loop) = cb.build()?;
 
-    let mut game = GameState::new(&mut ctx)?;
+    let mut game = GameState::new(&mut ctx, config)?;
+    if let Some(error) = config_error {
+        game.config_read_only = error.read_only;
+        game.toasts.push(toast::Toast::error(error.message));
+    }
+
+    // `--left-bot <command>` and `--right-bot <command>` let a program play
+    // that paddle for this session, see `bot.rs`.
+    for (paddle, flag) in ["--left-bot", "--right-bot"].iter().enumerate() {
+        if let Some(i) = args.iter().position(|arg| arg == flag) {
+            match args.get(i + 1) {
+                Some(command) => game.bot_commands[paddle] = command.clone(),
+                None => {
+                    eprintln!("{} needs a command", flag);
+                    std::process::exit(2);
+                }
+            }
+        }
+    }
+
+    // `--script <path>` starts a match set up by console commands.
+    if let Some(i) = args.iter().position(|arg| arg == "--script") {
+        let path = match args.get(i + 1) {
+            Some(path) => std::path::Path::new(path),
+            None => {
+                eprintln!("--script needs a file");
+                std::process::exit(2);
+            }
+        };
+
+        if let Err(e) = game.start_scripted_match(path) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    // `--bench-frames [N]` measures allocations per drawn frame and exits.
+    if let Some(i) = args.iter().position(|arg| arg == "--bench-frames") {
+        let frames = args
+            .get(i + 1)
+            .and_then(|frames| frames.parse().ok())
+            .unwrap_or(300);
+
+        return bench::run_frame_benchmark(&mut ctx, &mut game, frames);
+    }
 
     event::run(&mut ctx, &mut events_loop, &mut game)
 }
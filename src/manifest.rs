@@ -0,0 +1,366 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::theme::Theme;
+
+pub const MANIFEST_PATH: &str = "/assets.toml";
+
+// Built-in copy of the manifest, used when the file is missing and to fill in
+// any ID the file on disk leaves out.
+const DEFAULT_MANIFEST: &str = include_str!("../resources/assets.toml");
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AssetKind {
+    Sound,
+    Image,
+    Font,
+}
+
+#[derive(Deserialize, Default)]
+pub struct AssetManifest {
+    #[serde(default)]
+    pub sounds: BTreeMap<String, String>,
+    #[serde(default)]
+    pub images: BTreeMap<String, String>,
+    #[serde(default)]
+    pub fonts: BTreeMap<String, String>,
+}
+
+impl AssetManifest {
+    pub fn builtin() -> AssetManifest {
+        toml::from_str(DEFAULT_MANIFEST).expect("built-in asset manifest is valid")
+    }
+
+    // Parses a manifest and layers it over the built-in one.
+    pub fn parse(contents: &str) -> Result<AssetManifest, String> {
+        let overrides: AssetManifest = toml::from_str(contents).map_err(|e| e.to_string())?;
+
+        let mut manifest = AssetManifest::builtin();
+        manifest.sounds.extend(overrides.sounds);
+        manifest.images.extend(overrides.images);
+        manifest.fonts.extend(overrides.fonts);
+
+        Ok(manifest)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (AssetKind, &String, &String)> {
+        let sounds = self
+            .sounds
+            .iter()
+            .map(|(id, path)| (AssetKind::Sound, id, path));
+        let images = self
+            .images
+            .iter()
+            .map(|(id, path)| (AssetKind::Image, id, path));
+        let fonts = self
+            .fonts
+            .iter()
+            .map(|(id, path)| (AssetKind::Font, id, path));
+
+        sounds.chain(images).chain(fonts)
+    }
+}
+
+// Checks that `bytes` decode as the given kind of asset.
+pub fn validate(kind: AssetKind, bytes: &[u8]) -> Result<(), String> {
+    match kind {
+        AssetKind::Sound => validate_sound(bytes),
+        AssetKind::Image => image::load_from_memory(bytes)
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        AssetKind::Font => {
+            validate_font_tables(bytes)?;
+
+            match rusttype::Font::from_bytes(bytes) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    }
+}
+
+// Only WAV and Ogg Vorbis are accepted. Their headers are checked here rather
+// than by probing with rodio, whose MP3 fallback misbehaves on garbage input.
+fn validate_sound(bytes: &[u8]) -> Result<(), String> {
+    validate_sound_stream(Cursor::new(bytes))
+}
+
+// Like `validate` for a sound, but only reads the headers, so a long music
+// file can be checked without loading it.
+pub fn validate_sound_stream<R: Read + Seek>(mut reader: R) -> Result<(), String> {
+    let mut header = [0; 12];
+    let read = read_up_to(&mut reader, &mut header)?;
+
+    if read >= 4 && &header[0..4] == b"OggS" {
+        reader.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
+        return lewton::inside_ogg::OggStreamReader::new(reader)
+            .map(|_| ())
+            .map_err(|e| e.to_string());
+    }
+
+    if read < 12 || &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Err(String::from("not a WAV or Ogg Vorbis file"));
+    }
+
+    let len = reader.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    let mut has_format = false;
+    let mut offset = 12;
+    while offset + 8 <= len {
+        let mut chunk = [0; 8];
+        reader
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| reader.read_exact(&mut chunk))
+            .map_err(|e| e.to_string())?;
+        let id = &chunk[0..4];
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+        let body = offset + 8;
+
+        if body + size > len {
+            return Err(format!(
+                "WAV chunk \"{}\" is truncated",
+                String::from_utf8_lossy(id)
+            ));
+        }
+
+        match id {
+            b"fmt " => {
+                if size < 16 {
+                    return Err(String::from("WAV format chunk is too short"));
+                }
+
+                let mut format = [0; 2];
+                reader.read_exact(&mut format).map_err(|e| e.to_string())?;
+                let format = u16::from_le_bytes(format);
+                if format != 1 && format != 3 {
+                    return Err(format!("unsupported WAV encoding {}", format));
+                }
+
+                has_format = true;
+            }
+            b"data" if has_format => return Ok(()),
+            b"data" => return Err(String::from("WAV data chunk before format chunk")),
+            _ => (),
+        }
+
+        // Chunks are padded to an even size.
+        offset = body + size + size % 2;
+    }
+
+    Err(String::from("WAV file has no data chunk"))
+}
+
+// Fills as much of `buffer` as the reader has, returns how much that was.
+fn read_up_to(reader: &mut impl Read, buffer: &mut [u8]) -> Result<usize, String> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+
+    Ok(read)
+}
+
+// rusttype panics on truncated fonts, so make sure every table listed in the
+// font's table directory lies inside the file first.
+fn validate_font_tables(bytes: &[u8]) -> Result<(), String> {
+    let read_u32 = |at: usize| -> Option<usize> {
+        let b = bytes.get(at..at + 4)?;
+        Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+
+    let version = read_u32(0).ok_or("font file is too short")?;
+    if version != 0x0001_0000 && &bytes[0..4] != b"OTTO" && &bytes[0..4] != b"true" {
+        return Err(String::from("not a TrueType or OpenType font"));
+    }
+
+    let table_count = match bytes.get(4..6) {
+        Some(count) => u16::from_be_bytes([count[0], count[1]]) as usize,
+        None => return Err(String::from("font file is too short")),
+    };
+    for i in 0..table_count {
+        let record = 12 + i * 16;
+        let offset = read_u32(record + 8).ok_or("font table directory is truncated")?;
+        let length = read_u32(record + 12).ok_or("font table directory is truncated")?;
+
+        if offset + length > bytes.len() {
+            return Err(String::from("font file is truncated"));
+        }
+    }
+
+    Ok(())
+}
+
+// Resource paths start with a slash, relative to the resource directory.
+fn resolve(resource_dir: &Path, path: &str) -> std::path::PathBuf {
+    resource_dir.join(path.trim_start_matches('/'))
+}
+
+fn check_file(resource_dir: &Path, kind: AssetKind, path: &str) -> Result<(), String> {
+    let bytes = fs::read(resolve(resource_dir, path)).map_err(|e| e.to_string())?;
+    validate(kind, &bytes)
+}
+
+// Validates the manifest, every asset it lists and every theme file. Returns
+// one line per problem found.
+pub fn check_assets(resource_dir: &Path) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let manifest = match fs::read_to_string(resolve(resource_dir, MANIFEST_PATH)) {
+        Ok(contents) => match AssetManifest::parse(&contents) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                problems.push(format!("{}: {}", MANIFEST_PATH, e));
+                AssetManifest::builtin()
+            }
+        },
+        Err(e) => {
+            problems.push(format!("{}: {}", MANIFEST_PATH, e));
+            AssetManifest::builtin()
+        }
+    };
+
+    for (kind, id, path) in manifest.entries() {
+        if let Err(e) = check_file(resource_dir, kind, path) {
+            problems.push(format!("{} ({}): {}", id, path, e));
+        }
+    }
+
+    let theme_dir = resolve(resource_dir, crate::theme::THEME_DIR);
+    let mut theme_paths: Vec<_> = match fs::read_dir(&theme_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    theme_paths.sort();
+
+    for theme_path in theme_paths {
+        let name = theme_path.display();
+
+        let theme = match fs::read_to_string(&theme_path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| Theme::parse(&contents))
+        {
+            Ok(theme) => theme,
+            Err(e) => {
+                problems.push(format!("{}: {}", name, e));
+                continue;
+            }
+        };
+
+        if let Some(font) = &theme.font {
+            if !manifest.fonts.contains_key(font) {
+                problems.push(format!("{}: unknown font ID \"{}\"", name, font));
+            }
+        }
+
+        let sprites = [
+            &theme.background_image,
+            &theme.paddle_sprite,
+            &theme.ball_sprite,
+        ];
+        for sprite in sprites.iter().filter_map(|sprite| sprite.as_ref()) {
+            if let Err(e) = check_file(resource_dir, AssetKind::Image, sprite) {
+                problems.push(format!("{} ({}): {}", name, sprite, e));
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAV: &[u8] = include_bytes!("../resources/goal.wav");
+    const FONT: &[u8] = include_bytes!("../resources/joystix_mono.ttf");
+
+    #[test]
+    fn broken_sounds_are_rejected() {
+        assert!(validate_sound(WAV).is_ok());
+
+        assert!(validate_sound(b"").is_err());
+        assert!(validate_sound(b"not a sound at all").is_err());
+        assert!(validate_sound(&WAV[..WAV.len() / 2]).is_err());
+        assert!(validate_sound(&WAV[..20]).is_err());
+
+        // The format chunk is cut off, then listed after the data.
+        let mut bytes = WAV[..12].to_vec();
+        bytes.extend_from_slice(b"data\x00\x00\x00\x00fmt ");
+        assert!(validate_sound(&bytes).is_err());
+
+        assert!(validate_sound(b"OggS").is_err());
+        assert!(validate_sound(b"OggS\x00\x02\x00\x00\x00\x00").is_err());
+        assert!(validate_sound(b"OggS garbage that is not a Vorbis stream").is_err());
+    }
+
+    #[test]
+    fn broken_fonts_are_rejected() {
+        assert!(validate_font_tables(FONT).is_ok());
+        assert!(validate(AssetKind::Font, FONT).is_ok());
+
+        assert!(validate_font_tables(b"").is_err());
+        assert!(validate_font_tables(&FONT[..3]).is_err());
+        assert!(validate_font_tables(&FONT[..40]).is_err());
+        assert!(validate(AssetKind::Font, &FONT[..FONT.len() / 2]).is_err());
+        assert!(validate(AssetKind::Font, b"garbage bytes, not a font").is_err());
+    }
+
+    #[test]
+    fn user_manifest_overrides_single_entries() {
+        let manifest = AssetManifest::parse("[sounds]\ngoal = \"/other.wav\"").unwrap();
+        let builtin = AssetManifest::builtin();
+
+        assert_eq!(manifest.sounds["goal"], "/other.wav");
+        assert_eq!(manifest.sounds["pad"], builtin.sounds["pad"]);
+        assert_eq!(manifest.images, builtin.images);
+        assert_eq!(manifest.fonts, builtin.fonts);
+
+        assert!(AssetManifest::parse("[sounds\ngoal = 1").is_err());
+    }
+
+    #[test]
+    fn check_assets_reports_every_problem() {
+        let dir =
+            std::env::temp_dir().join(format!("ggezgame-check-assets-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("themes")).unwrap();
+
+        // A copy of the shipped assets, with one sound swapped for a broken
+        // file and a font that doesn't exist.
+        let resources = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources");
+        for (_, _, path) in AssetManifest::builtin().entries() {
+            fs::copy(resolve(&resources, path), resolve(&dir, path)).unwrap();
+        }
+        fs::write(
+            dir.join("assets.toml"),
+            "[sounds]\npad = \"/broken.wav\"\n[fonts]\nmissing = \"/missing.ttf\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.wav"), "RIFF").unwrap();
+
+        let amber = include_str!("../resources/themes/amber.toml");
+        fs::write(dir.join("themes/amber.toml"), amber).unwrap();
+        fs::write(dir.join("themes/broken.toml"), "name = ").unwrap();
+
+        let problems = check_assets(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].starts_with("pad (/broken.wav)"));
+        assert!(problems[1].starts_with("missing (/missing.ttf)"));
+        assert!(problems[2].contains("broken.toml"));
+    }
+}
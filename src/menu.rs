@@ -1,25 +1,325 @@
-pub const TOTAL_ITEMS: isize = 5;
+use ggez::{input::keyboard::KeyCode, Context};
+
+use crate::{game_state::GameState, scene::Transition, scenes::MenuScene};
+
+// Step of sliders for each Left or Right press.
+pub const SLIDER_STEP: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuPage {
+    Title,
+    Pause,
+    Settings,
+    Audio,
+    Display,
+    Controls,
+    Accessibility,
+    GameOver,
+}
+
+impl MenuPage {
+    // Title and game over have nothing to go back to.
+    pub fn can_go_back(self) -> bool {
+        !matches!(self, MenuPage::Title | MenuPage::GameOver)
+    }
+}
+
+// What an item shows and does. Callbacks are plain functions so a page is
+// just a list built from the current state, see `menus.rs`.
+pub enum Widget {
+    Action(fn(&mut GameState, &mut Context) -> Transition),
+    Submenu(MenuPage),
+    Back,
+    // Return and Left/Right flip it.
+    Toggle {
+        on: bool,
+        toggle: fn(&mut GameState, &mut Context),
+    },
+    // Left/Right adjust it by `SLIDER_STEP`, Return mutes it.
+    Slider {
+        level: f32,
+        muted: bool,
+        adjust: fn(&mut GameState, f32),
+        toggle_mute: fn(&mut GameState),
+    },
+    // Left/Right cycle through the options.
+    Choice {
+        options: Vec<String>,
+        selected: usize,
+        select: fn(&mut GameState, &mut Context, usize),
+    },
+    // Return waits for the next key press.
+    KeyBinding {
+        key: KeyCode,
+        bind: fn(&mut GameState, KeyCode),
+    },
+    // Return starts editing, Return again saves and Escape cancels.
+    TextEntry {
+        text: String,
+        max_length: usize,
+        set: fn(&mut GameState, String),
+    },
+}
+
+// Labels and tooltips are locale keys, translated when drawn.
+pub struct MenuItem {
+    pub label: &'static str,
+    pub widget: Widget,
+    pub enabled: bool,
+    // Shown at the bottom of the screen while the item is selected.
+    pub tooltip: Option<&'static str>,
+}
+
+impl MenuItem {
+    pub fn new(label: &'static str, widget: Widget) -> MenuItem {
+        MenuItem {
+            label,
+            widget,
+            enabled: true,
+            tooltip: None,
+        }
+    }
+
+    pub fn action(
+        label: &'static str,
+        run: fn(&mut GameState, &mut Context) -> Transition,
+    ) -> MenuItem {
+        MenuItem::new(label, Widget::Action(run))
+    }
+
+    pub fn submenu(label: &'static str, page: MenuPage) -> MenuItem {
+        MenuItem::new(label, Widget::Submenu(page))
+    }
+
+    pub fn back() -> MenuItem {
+        MenuItem::new("menu-back", Widget::Back)
+    }
+
+    pub fn toggle(
+        label: &'static str,
+        on: bool,
+        toggle: fn(&mut GameState, &mut Context),
+    ) -> MenuItem {
+        MenuItem::new(label, Widget::Toggle { on, toggle })
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> MenuItem {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn tooltip(mut self, tooltip: &'static str) -> MenuItem {
+        self.tooltip = Some(tooltip);
+        self
+    }
+
+    // Items with a value are drawn as a label column and a value column.
+    pub fn has_value(&self) -> bool {
+        !matches!(
+            self.widget,
+            Widget::Action(_) | Widget::Submenu(_) | Widget::Back
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum MenuMode {
+    Browsing,
+    // Waiting for the key to bind to the selected item.
+    Binding,
+    // Text typed so far into the selected item.
+    Editing(String),
+}
 
 pub struct Menu {
-    pub current_menu_choice: isize,
+    pub page: MenuPage,
+    pub selected: usize,
+    pub mode: MenuMode,
 }
 
 impl Menu {
-    pub fn new(current_menu_choice: isize) -> Menu {
+    pub fn new(page: MenuPage) -> Menu {
         Menu {
-            current_menu_choice,
+            page,
+            selected: 0,
+            mode: MenuMode::Browsing,
         }
     }
 
-    pub fn advance_menu_choice(&mut self, delta: isize) {
-        self.current_menu_choice -= delta;
+    // Moves the selection by `delta`, wrapping around and skipping disabled
+    // items.
+    pub fn advance(&mut self, items: &[MenuItem], delta: isize) {
+        let len = items.len() as isize;
+
+        for step in 1..=len {
+            let index = (self.selected as isize + delta * step).rem_euclid(len) as usize;
 
-        if self.current_menu_choice < 0 {
-            self.current_menu_choice += TOTAL_ITEMS;
+            if items[index].enabled {
+                self.selected = index;
+                return;
+            }
         }
+    }
+
+    pub fn key_down(
+        &mut self,
+        game: &mut GameState,
+        ctx: &mut Context,
+        keycode: KeyCode,
+    ) -> Transition {
+        let items = game.menu_items(self.page);
 
-        if self.current_menu_choice >= TOTAL_ITEMS {
-            self.current_menu_choice -= TOTAL_ITEMS;
+        // The selected item can become disabled, e.g. the window size once
+        // fullscreen is turned on.
+        if !items.get(self.selected).is_some_and(|item| item.enabled) {
+            self.advance(&items, 1);
         }
+
+        let item = match items.get(self.selected) {
+            Some(item) => item,
+            None => return Transition::None,
+        };
+
+        match &self.mode {
+            MenuMode::Binding => {
+                if let Widget::KeyBinding { bind, .. } = item.widget {
+                    if keycode != KeyCode::Escape {
+                        bind(game, keycode);
+                    }
+                }
+                self.mode = MenuMode::Browsing;
+
+                return Transition::None;
+            }
+            MenuMode::Editing(text) => {
+                match (keycode, &item.widget) {
+                    (KeyCode::Return, Widget::TextEntry { set, .. }) => {
+                        set(game, text.trim().to_string());
+                        self.mode = MenuMode::Browsing;
+                    }
+                    (KeyCode::Escape, _) => self.mode = MenuMode::Browsing,
+                    (KeyCode::Back, _) => {
+                        let mut text = text.clone();
+                        text.pop();
+                        self.mode = MenuMode::Editing(text);
+                    }
+                    _ => (),
+                }
+
+                return Transition::None;
+            }
+            MenuMode::Browsing => (),
+        }
+
+        match keycode {
+            KeyCode::Up => self.advance(&items, -1),
+            KeyCode::Down => self.advance(&items, 1),
+            KeyCode::Left => adjust(game, ctx, item, -1),
+            KeyCode::Right => adjust(game, ctx, item, 1),
+            KeyCode::Return => return self.activate(game, ctx, item),
+            KeyCode::Escape if self.page.can_go_back() => return Transition::Pop,
+            _ => (),
+        }
+
+        Transition::None
+    }
+
+    // Typed characters, only used while editing text.
+    pub fn text_input(&mut self, game: &mut GameState, character: char) {
+        let items = game.menu_items(self.page);
+
+        if let (MenuMode::Editing(text), Some(item)) = (&mut self.mode, items.get(self.selected)) {
+            if let Widget::TextEntry { max_length, .. } = item.widget {
+                if !character.is_control() && text.chars().count() < max_length {
+                    text.push(character);
+                }
+            }
+        }
+    }
+
+    fn activate(&mut self, game: &mut GameState, ctx: &mut Context, item: &MenuItem) -> Transition {
+        match &item.widget {
+            Widget::Action(run) => return run(game, ctx),
+            Widget::Submenu(page) => {
+                return Transition::Push(Box::new(MenuScene::new(*page)));
+            }
+            Widget::Back => return Transition::Pop,
+            Widget::Toggle { toggle, .. } => toggle(game, ctx),
+            Widget::Slider { toggle_mute, .. } => toggle_mute(game),
+            Widget::Choice { .. } => adjust(game, ctx, item, 1),
+            Widget::KeyBinding { .. } => self.mode = MenuMode::Binding,
+            Widget::TextEntry { text, .. } => self.mode = MenuMode::Editing(text.clone()),
+        }
+
+        Transition::None
+    }
+}
+
+fn adjust(game: &mut GameState, ctx: &mut Context, item: &MenuItem, direction: isize) {
+    match &item.widget {
+        Widget::Toggle { toggle, .. } => toggle(game, ctx),
+        Widget::Slider { adjust, .. } => adjust(game, direction as f32 * SLIDER_STEP),
+        Widget::Choice {
+            options,
+            selected,
+            select,
+        } if !options.is_empty() => {
+            let len = options.len() as isize;
+            select(
+                game,
+                ctx,
+                (*selected as isize + direction).rem_euclid(len) as usize,
+            );
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(enabled: bool) -> MenuItem {
+        MenuItem::back().enabled(enabled)
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let items = vec![item(true), item(true), item(true)];
+        let mut menu = Menu::new(MenuPage::Pause);
+
+        menu.advance(&items, -1);
+        assert_eq!(menu.selected, 2);
+
+        menu.advance(&items, 1);
+        assert_eq!(menu.selected, 0);
+    }
+
+    #[test]
+    fn disabled_items_are_skipped() {
+        let items = vec![item(true), item(false), item(false), item(true)];
+        let mut menu = Menu::new(MenuPage::Display);
+
+        menu.advance(&items, 1);
+        assert_eq!(menu.selected, 3);
+
+        menu.advance(&items, -1);
+        assert_eq!(menu.selected, 0);
+    }
+
+    #[test]
+    fn selection_stays_when_nothing_else_is_enabled() {
+        let items = vec![item(true), item(false)];
+        let mut menu = Menu::new(MenuPage::Display);
+
+        menu.advance(&items, 1);
+        assert_eq!(menu.selected, 0);
+    }
+
+    #[test]
+    fn only_nested_pages_go_back() {
+        assert!(!MenuPage::Title.can_go_back());
+        assert!(!MenuPage::GameOver.can_go_back());
+        assert!(MenuPage::Audio.can_go_back());
     }
 }
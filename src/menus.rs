@@ -0,0 +1,355 @@
+use ggez::{input::keyboard::KeyCode, Context};
+
+use crate::{
+    accessibility::{ParticlePalette, TEXT_SCALES},
+    audio::Channel,
+    bot::{AI_PREFIX, NETWORK_PREFIX},
+    config::{WindowMode, MAX_NAME_LENGTH},
+    display::{closest_resolution, RESOLUTIONS},
+    game_state::GameState,
+    keys::PaddleAction,
+    menu::{MenuItem, MenuPage, Widget},
+    scene::Transition,
+    scenes::{GameScene, MenuScene, StatsScene},
+};
+
+// Every menu page. Items are rebuilt from the game state each time they are
+// drawn or used, so adding an option only means adding it here.
+impl GameState {
+    pub fn menu_items(&self, page: MenuPage) -> Vec<MenuItem> {
+        match page {
+            MenuPage::Title => self.title_items(),
+            MenuPage::Pause => self.pause_items(),
+            MenuPage::Settings => self.settings_items(),
+            MenuPage::Audio => self.audio_items(),
+            MenuPage::Display => self.display_items(),
+            MenuPage::Controls => self.controls_items(),
+            MenuPage::Accessibility => self.accessibility_items(),
+            MenuPage::GameOver => self.game_over_items(),
+        }
+    }
+
+    fn title_items(&self) -> Vec<MenuItem> {
+        vec![
+            MenuItem::action("menu-start", |_, _| new_match()),
+            MenuItem::submenu("menu-settings", MenuPage::Settings),
+            MenuItem::action("menu-quit", |_, _| Transition::Quit),
+        ]
+    }
+
+    fn pause_items(&self) -> Vec<MenuItem> {
+        vec![
+            MenuItem::action("menu-resume", |_, _| Transition::Pop),
+            MenuItem::submenu("menu-settings", MenuPage::Settings),
+            MenuItem::toggle("menu-particles", self.particles_visible(), |game, _| {
+                game.toggle_particles()
+            })
+            .enabled(!self.config.accessibility.reduced_motion),
+            self.theme_item(),
+            MenuItem::action("menu-restart", |game, _| {
+                game.arena.reset(true);
+                game.start_bots();
+                Transition::Pop
+            }),
+            MenuItem::action("menu-quit", |_, _| Transition::Quit),
+        ]
+    }
+
+    fn settings_items(&self) -> Vec<MenuItem> {
+        vec![
+            MenuItem::submenu("menu-audio", MenuPage::Audio),
+            MenuItem::submenu("menu-display", MenuPage::Display),
+            MenuItem::submenu("menu-controls", MenuPage::Controls),
+            MenuItem::submenu("menu-accessibility", MenuPage::Accessibility),
+            MenuItem::new(
+                "menu-language",
+                Widget::Choice {
+                    options: self
+                        .locale
+                        .locales()
+                        .iter()
+                        .map(|l| l.name.clone())
+                        .collect(),
+                    selected: self.locale.current_index(),
+                    select: |game, _, index| game.set_language(index),
+                },
+            )
+            .tooltip("tip-language"),
+            self.theme_item(),
+            MenuItem::back(),
+        ]
+    }
+
+    fn theme_item(&self) -> MenuItem {
+        MenuItem::new(
+            "menu-theme",
+            Widget::Choice {
+                options: self.themes.iter().map(|theme| theme.name.clone()).collect(),
+                selected: self.theme_index,
+                select: |game, _, index| game.set_theme(index),
+            },
+        )
+    }
+
+    fn audio_items(&self) -> Vec<MenuItem> {
+        let slider = |label: &'static str,
+                      channel: Channel,
+                      adjust: fn(&mut GameState, f32),
+                      toggle_mute: fn(&mut GameState)| {
+            let (level, muted) = self.mixer.level(channel);
+            let widget = Widget::Slider {
+                level,
+                muted,
+                adjust,
+                toggle_mute,
+            };
+
+            MenuItem::new(label, widget).tooltip("tip-mute")
+        };
+
+        vec![
+            slider(
+                "menu-master",
+                Channel::Master,
+                |game, delta| game.adjust_volume(Channel::Master, delta),
+                |game| game.toggle_channel_mute(Channel::Master),
+            ),
+            slider(
+                "menu-sfx",
+                Channel::Sfx,
+                |game, delta| game.adjust_volume(Channel::Sfx, delta),
+                |game| game.toggle_channel_mute(Channel::Sfx),
+            ),
+            slider(
+                "menu-music",
+                Channel::Music,
+                |game, delta| game.adjust_volume(Channel::Music, delta),
+                |game| game.toggle_channel_mute(Channel::Music),
+            ),
+            MenuItem::toggle("menu-cue", self.mixer.settings.ball_cue, |game, _| {
+                game.toggle_ball_cue()
+            })
+            .tooltip("tip-cue"),
+            MenuItem::toggle(
+                "menu-shuffle",
+                self.mixer.settings.shuffle_music,
+                |game, _| game.toggle_shuffle(),
+            )
+            .enabled(self.music.track_count() > 1)
+            .tooltip("tip-shuffle"),
+            MenuItem::back(),
+        ]
+    }
+
+    fn display_items(&self) -> Vec<MenuItem> {
+        let display = &self.config.display;
+        let windowed = display.window_mode == WindowMode::Windowed;
+
+        let vsync = MenuItem::toggle("menu-vsync", display.vsync, |game, _| game.toggle_vsync());
+        let vsync = if self.vsync_needs_restart() {
+            vsync.tooltip("tip-vsync-restart")
+        } else {
+            vsync
+        };
+
+        vec![
+            MenuItem::new(
+                "menu-size",
+                Widget::Choice {
+                    options: RESOLUTIONS
+                        .iter()
+                        .map(|(w, h)| format!("{}x{}", w, h))
+                        .collect(),
+                    selected: closest_resolution((display.width, display.height)),
+                    select: |game, ctx, index| game.set_resolution(ctx, index),
+                },
+            )
+            .enabled(windowed)
+            .tooltip(if windowed {
+                "tip-size"
+            } else {
+                "tip-size-fullscreen"
+            }),
+            MenuItem::new(
+                "menu-mode",
+                Widget::Choice {
+                    options: vec![
+                        self.tr("value-windowed").to_string(),
+                        self.tr("value-fullscreen").to_string(),
+                    ],
+                    selected: if windowed { 0 } else { 1 },
+                    select: |game, ctx, index| {
+                        let mode = match index {
+                            0 => WindowMode::Windowed,
+                            _ => WindowMode::Fullscreen,
+                        };
+                        game.set_window_mode(ctx, mode)
+                    },
+                },
+            ),
+            vsync,
+            MenuItem::back(),
+        ]
+    }
+
+    fn controls_items(&self) -> Vec<MenuItem> {
+        let controls = &self.config.controls;
+        let key = |label: &'static str, action: PaddleAction, bind: fn(&mut GameState, KeyCode)| {
+            MenuItem::new(
+                label,
+                Widget::KeyBinding {
+                    key: controls.key(action),
+                    bind,
+                },
+            )
+            .tooltip("tip-key")
+        };
+        let control = |label: &'static str,
+                       paddle: usize,
+                       choose: fn(&mut GameState, &mut Context, usize)| {
+            let options = self.bot_options(paddle);
+            let selected = options
+                .iter()
+                .position(|command| *command == self.bot_commands[paddle])
+                .unwrap_or(0);
+            let options = options
+                .into_iter()
+                .map(|command| {
+                    if command.is_empty() {
+                        self.tr("value-keyboard").to_string()
+                    } else if let Some(name) = command.strip_prefix(AI_PREFIX) {
+                        self.locale.format("value-ai", &[("name", name)])
+                    } else if let Some(name) = command.strip_prefix(NETWORK_PREFIX) {
+                        self.locale.format("value-network", &[("name", name)])
+                    } else {
+                        command
+                    }
+                })
+                .collect();
+
+            MenuItem::new(
+                label,
+                Widget::Choice {
+                    options,
+                    selected,
+                    select: choose,
+                },
+            )
+            .tooltip("tip-bot")
+        };
+        let name = |label: &'static str, player: usize, set: fn(&mut GameState, String)| {
+            MenuItem::new(
+                label,
+                Widget::TextEntry {
+                    text: self.player_name(player).to_string(),
+                    max_length: MAX_NAME_LENGTH,
+                    set,
+                },
+            )
+            .tooltip("tip-name")
+        };
+
+        vec![
+            name("menu-p1-name", 1, |game, name| {
+                game.set_player_name(1, name)
+            }),
+            control("menu-p1-control", 0, |game, _, index| {
+                game.choose_bot(0, index)
+            }),
+            key("menu-p1-up", PaddleAction::LeftUp, |game, key| {
+                game.bind_key(PaddleAction::LeftUp, key)
+            }),
+            key("menu-p1-down", PaddleAction::LeftDown, |game, key| {
+                game.bind_key(PaddleAction::LeftDown, key)
+            }),
+            name("menu-p2-name", 2, |game, name| {
+                game.set_player_name(2, name)
+            }),
+            control("menu-p2-control", 1, |game, _, index| {
+                game.choose_bot(1, index)
+            }),
+            key("menu-p2-up", PaddleAction::RightUp, |game, key| {
+                game.bind_key(PaddleAction::RightUp, key)
+            }),
+            key("menu-p2-down", PaddleAction::RightDown, |game, key| {
+                game.bind_key(PaddleAction::RightDown, key)
+            }),
+            MenuItem::toggle(
+                "menu-adaptive-difficulty",
+                self.difficulty.settings.adaptive,
+                |game, _| game.toggle_adaptive_difficulty(),
+            )
+            .tooltip("tip-adaptive-difficulty"),
+            MenuItem::back(),
+        ]
+    }
+
+    fn accessibility_items(&self) -> Vec<MenuItem> {
+        let accessibility = &self.config.accessibility;
+
+        vec![
+            MenuItem::toggle(
+                "menu-high-contrast",
+                accessibility.high_contrast,
+                |game, _| game.toggle_high_contrast(),
+            )
+            .tooltip("tip-high-contrast"),
+            MenuItem::toggle(
+                "menu-reduced-motion",
+                accessibility.reduced_motion,
+                |game, _| game.toggle_reduced_motion(),
+            )
+            .tooltip("tip-reduced-motion"),
+            MenuItem::new(
+                "menu-palette",
+                Widget::Choice {
+                    options: ParticlePalette::ALL
+                        .iter()
+                        .map(|palette| self.tr(palette.label()).to_string())
+                        .collect(),
+                    selected: ParticlePalette::ALL
+                        .iter()
+                        .position(|palette| *palette == accessibility.particle_palette)
+                        .unwrap_or(0),
+                    select: |game, _, index| game.set_particle_palette(index),
+                },
+            )
+            .enabled(!accessibility.reduced_motion)
+            .tooltip("tip-palette"),
+            MenuItem::new(
+                "menu-text-size",
+                Widget::Choice {
+                    options: TEXT_SCALES
+                        .iter()
+                        .map(|scale| format!("{}%", (scale * 100.0).round()))
+                        .collect(),
+                    selected: accessibility.text_scale_index(),
+                    select: |game, _, index| game.set_text_scale(index),
+                },
+            )
+            .tooltip("tip-text-size"),
+            MenuItem::toggle(
+                "menu-large-objects",
+                accessibility.large_objects,
+                |game, _| game.toggle_large_objects(),
+            )
+            .tooltip("tip-large-objects"),
+            MenuItem::back(),
+        ]
+    }
+
+    fn game_over_items(&self) -> Vec<MenuItem> {
+        vec![
+            MenuItem::action("menu-rematch", |_, _| new_match()),
+            MenuItem::action("menu-stats", |_, _| Transition::Push(Box::new(StatsScene))),
+            MenuItem::action("menu-title-screen", |_, _| {
+                Transition::Reset(Box::new(MenuScene::new(MenuPage::Title))).faded()
+            }),
+            MenuItem::action("menu-quit", |_, _| Transition::Quit),
+        ]
+    }
+}
+
+fn new_match() -> Transition {
+    Transition::Reset(Box::new(GameScene::new_match())).faded()
+}
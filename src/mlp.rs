@@ -0,0 +1,459 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    arena::Arena,
+    config::RESOURCE_DIR,
+    env::{self, Action, OBSERVATION_SIZE},
+};
+
+// Version in the first line of a weights file, see "Neural network agents"
+// in the README for the format.
+pub const FORMAT_VERSION: u32 = 1;
+
+// Most inputs or outputs a layer in a weights file may have, so a broken
+// file can't ask for an absurd amount of memory.
+pub const MAX_LAYER_SIZE: usize = 4096;
+
+// Weights files offered in the Controls menu, under the resource directory.
+pub const AGENT_DIR: &str = "agents";
+
+// The weights file for "nn:<name>": `name` in `AGENT_DIR` without its
+// extension, or any path to a file.
+pub fn agent_path(name: &str) -> PathBuf {
+    let path = Path::new(name);
+
+    match path.extension().is_some() || path.components().count() > 1 {
+        true => path.to_path_buf(),
+        false => Path::new(RESOURCE_DIR)
+            .join(AGENT_DIR)
+            .join(format!("{}.mlp", name)),
+    }
+}
+
+// The networks in `AGENT_DIR`, by name.
+pub fn agent_names() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(Path::new(RESOURCE_DIR).join(AGENT_DIR))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "mlp"))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+
+    names
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Activation {
+    Linear,
+    Relu,
+    Tanh,
+    Sigmoid,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 4] = [
+        Activation::Linear,
+        Activation::Relu,
+        Activation::Tanh,
+        Activation::Sigmoid,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Activation::Linear => "linear",
+            Activation::Relu => "relu",
+            Activation::Tanh => "tanh",
+            Activation::Sigmoid => "sigmoid",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Activation> {
+        Activation::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Linear => x,
+            Activation::Relu => x.max(0.0),
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+        }
+    }
+}
+
+// A fully connected layer. `weights` has a row of `inputs()` values for each
+// output, like PyTorch's `Linear.weight`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Layer {
+    pub weights: Vec<f32>,
+    pub biases: Vec<f32>,
+    pub activation: Activation,
+}
+
+impl Layer {
+    pub fn outputs(&self) -> usize {
+        self.biases.len()
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.weights.len() / self.biases.len().max(1)
+    }
+}
+
+// A small multilayer perceptron, evaluated on the CPU without allocating.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Mlp {
+    pub inputs: usize,
+    pub layers: Vec<Layer>,
+}
+
+impl Mlp {
+    // Checks that each layer takes what the one before gives.
+    pub fn new(inputs: usize, layers: Vec<Layer>) -> Result<Mlp, String> {
+        if inputs == 0 || layers.is_empty() {
+            return Err("a network needs inputs and at least one layer".to_string());
+        }
+
+        let mut size = inputs;
+        for (i, layer) in layers.iter().enumerate() {
+            if layer.outputs() == 0 || layer.weights.len() != size * layer.outputs() {
+                return Err(format!(
+                    "layer {} has {} weights and {} biases, expected {} weights per bias",
+                    i + 1,
+                    layer.weights.len(),
+                    layer.outputs(),
+                    size
+                ));
+            }
+            size = layer.outputs();
+        }
+
+        Ok(Mlp { inputs, layers })
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.layers.last().map_or(0, Layer::outputs)
+    }
+
+    pub fn load(path: &Path) -> Result<Mlp, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Mlp::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<Mlp, String> {
+        let mut tokens = Tokens::new(text);
+
+        tokens.keyword("mlp")?;
+        let version: u32 = tokens.number()?;
+        if version != FORMAT_VERSION {
+            return Err(format!("unsupported format version {}", version));
+        }
+
+        tokens.keyword("inputs")?;
+        let inputs: usize = tokens.number()?;
+        if inputs > MAX_LAYER_SIZE {
+            return Err(format!("{} inputs is too many", inputs));
+        }
+
+        let mut layers = Vec::new();
+        let mut size = inputs;
+        while !tokens.is_empty() {
+            tokens.keyword("layer")?;
+            let outputs: usize = tokens.number()?;
+            let (line, name) = tokens.next()?;
+            let activation = Activation::from_name(name)
+                .ok_or_else(|| format!("line {}: unknown activation \"{}\"", line, name))?;
+
+            let count = size
+                .checked_mul(outputs)
+                .filter(|_| outputs <= MAX_LAYER_SIZE)
+                .ok_or_else(|| format!("line {}: layer too large", line))?;
+            let weights = (0..count)
+                .map(|_| tokens.number())
+                .collect::<Result<_, _>>()?;
+            let biases = (0..outputs)
+                .map(|_| tokens.number())
+                .collect::<Result<_, _>>()?;
+
+            layers.push(Layer {
+                weights,
+                biases,
+                activation,
+            });
+            size = outputs;
+        }
+
+        Mlp::new(inputs, layers)
+    }
+
+    // The weights file, with a row of weights per line. Floats are written
+    // so they read back exactly.
+    pub fn to_text(&self) -> String {
+        let row = |values: &[f32]| {
+            values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let mut text = format!("mlp {}\ninputs {}\n", FORMAT_VERSION, self.inputs);
+        for layer in self.layers.iter() {
+            text += &format!("\nlayer {} {}\n", layer.outputs(), layer.activation.name());
+            for weights in layer.weights.chunks(layer.inputs().max(1)) {
+                text += &row(weights);
+                text += "\n";
+            }
+            text += &row(&layer.biases);
+            text += "\n";
+        }
+
+        text
+    }
+
+    // The outputs for `input`, using `scratch` for the layers in between.
+    pub fn forward<'a>(&self, input: &[f32], scratch: &'a mut [Vec<f32>; 2]) -> &'a [f32] {
+        let [current, next] = scratch;
+        current.clear();
+        current.extend_from_slice(input);
+
+        for layer in self.layers.iter() {
+            next.clear();
+            for (weights, bias) in layer
+                .weights
+                .chunks(layer.inputs())
+                .zip(layer.biases.iter())
+            {
+                let sum: f32 = weights.iter().zip(current.iter()).map(|(w, x)| w * x).sum();
+                next.push(layer.activation.apply(sum + bias));
+            }
+            std::mem::swap(current, next);
+        }
+
+        current
+    }
+
+    pub fn evaluate(&self, input: &[f32]) -> Vec<f32> {
+        self.forward(input, &mut [Vec::new(), Vec::new()]).to_vec()
+    }
+}
+
+// Whitespace separated tokens with their line numbers, without comments.
+struct Tokens<'a> {
+    tokens: std::vec::IntoIter<(usize, &'a str)>,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Tokens<'a> {
+        let tokens: Vec<_> = text
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let line_number = i + 1;
+                let code = line.split('#').next().unwrap_or("");
+                code.split_whitespace()
+                    .map(move |token| (line_number, token))
+            })
+            .collect();
+
+        Tokens {
+            tokens: tokens.into_iter(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.tokens.len() == 0
+    }
+
+    fn next(&mut self) -> Result<(usize, &'a str), String> {
+        self.tokens
+            .next()
+            .ok_or_else(|| "unexpected end of file".to_string())
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), String> {
+        match self.next()? {
+            (_, token) if token == keyword => Ok(()),
+            (line, token) => Err(format!(
+                "line {}: expected \"{}\", found \"{}\"",
+                line, keyword, token
+            )),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let (line, token) = self.next()?;
+        token
+            .parse()
+            .map_err(|_| format!("line {}: expected a number, found \"{}\"", line, token))
+    }
+}
+
+// The action with the highest score, the first one on ties. `scores` are in
+// the order of `Action::ALL`.
+pub fn best_action(scores: &[f32]) -> Action {
+    let mut best = 0;
+    for (i, score) in scores.iter().enumerate() {
+        if *score > scores[best] {
+            best = i;
+        }
+    }
+
+    Action::from_index(best).unwrap_or(Action::Stay)
+}
+
+// A network playing one paddle. It takes an `Observation` and scores each
+// action, and always sees the game as the left player.
+#[derive(Clone)]
+pub struct NeuralAi {
+    pub mlp: Mlp,
+    paddle: usize,
+    scratch: [Vec<f32>; 2],
+}
+
+impl NeuralAi {
+    pub fn new(mlp: Mlp, paddle: usize) -> Result<NeuralAi, String> {
+        if mlp.inputs != OBSERVATION_SIZE || mlp.outputs() != Action::ALL.len() {
+            return Err(format!(
+                "the network takes {} inputs and gives {} outputs, expected {} and {}",
+                mlp.inputs,
+                mlp.outputs(),
+                OBSERVATION_SIZE,
+                Action::ALL.len()
+            ));
+        }
+
+        let size = mlp
+            .layers
+            .iter()
+            .map(Layer::outputs)
+            .fold(mlp.inputs, usize::max);
+        Ok(NeuralAi {
+            mlp,
+            paddle,
+            scratch: [Vec::with_capacity(size), Vec::with_capacity(size)],
+        })
+    }
+
+    pub fn load(path: &Path, paddle: usize) -> Result<NeuralAi, String> {
+        NeuralAi::new(Mlp::load(path)?, paddle).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn act(&mut self, arena: &Arena) -> Action {
+        let observation = match self.paddle {
+            0 => env::observe(arena),
+            _ => env::mirror(&env::observe(arena)),
+        };
+
+        best_action(self.mlp.forward(&observation, &mut self.scratch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::env::{follow_ball, Env, EnvConfig};
+
+    // 2 inputs, a hidden layer of 2 and one output.
+    const SMALL: &str = "
+        mlp 1
+        inputs 2  # x, y
+
+        layer 2 relu
+        1 -1
+        0.5 2
+        0 -1
+
+        layer 1 tanh
+        1 -1
+        0.25
+    ";
+
+    #[test]
+    fn known_network_gives_known_outputs() {
+        let mlp = Mlp::parse(SMALL).unwrap();
+
+        // Hidden relu(x - y), relu(0.5x + 2y - 1), then tanh(h0 - h1 + 0.25).
+        for (input, expected) in [
+            ([0.0, 0.0], 0.25f32.tanh()),
+            ([1.0, 0.0], (1.0f32 - 0.0 + 0.25).tanh()),
+            ([2.0, 1.0], (1.0f32 - 2.0 + 0.25).tanh()),
+            ([-1.0, 3.0], (0.0f32 - 4.5 + 0.25).tanh()),
+        ] {
+            let output = mlp.evaluate(&input);
+            assert_eq!(output.len(), 1);
+            assert!((output[0] - expected).abs() < 1e-6, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn weights_files_round_trip() {
+        let mlp = Mlp::new(
+            3,
+            vec![Layer {
+                weights: vec![0.1, -2.5e-7, 1.0 / 3.0, 4.0, 5.5, -6.0],
+                biases: vec![0.7, f32::MIN_POSITIVE],
+                activation: Activation::Sigmoid,
+            }],
+        )
+        .unwrap();
+
+        assert_eq!(Mlp::parse(&mlp.to_text()).unwrap(), mlp);
+    }
+
+    #[test]
+    fn bad_files_are_reported() {
+        let error = |text: &str| Mlp::parse(text).unwrap_err();
+
+        assert!(error("mlp 2").contains("version 2"));
+        assert!(error("mlp 1\ninputs 2\nlayer 1 softmax").contains("line 3"));
+        assert!(error("mlp 1\ninputs 2\nlayer 1 relu\n1 x 3").contains("\"x\""));
+        assert_eq!(
+            error("mlp 1\ninputs 2\nlayer 1 relu\n1 2"),
+            "unexpected end of file"
+        );
+        assert!(error("mlp 1\ninputs 2").contains("at least one layer"));
+        assert!(error("mlp 1\ninputs 99999\nlayer 1 relu").contains("too many"));
+        assert_eq!(
+            error("mlp 1\ninputs 2\nlayer 18446744073709551615 relu"),
+            "line 3: layer too large"
+        );
+        assert!(error("mlp 1\ninputs 2\nlayer 1 relu\n1 2 3 4").contains("\"layer\""));
+        assert!(NeuralAi::new(Mlp::parse(SMALL).unwrap(), 0).is_err());
+    }
+
+    #[test]
+    fn shipped_follower_plays_like_follow_ball() {
+        let path = agent_path("follower");
+        for name in agent_names() {
+            NeuralAi::load(&agent_path(&name), 0).unwrap();
+        }
+        let mut agents = [
+            NeuralAi::load(&path, 0).unwrap(),
+            NeuralAi::load(&path, 1).unwrap(),
+        ];
+
+        let mut env = Env::new(EnvConfig::default());
+        let mut observation = env.reset(3);
+        for _ in 0..2000 {
+            let actions = [agents[0].act(&env.arena), agents[1].act(&env.arena)];
+            assert_eq!(actions[0], follow_ball(&observation, 0));
+            assert_eq!(actions[1], follow_ball(&observation, 1));
+
+            observation = env.step(actions).0;
+        }
+    }
+}
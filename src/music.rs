@@ -0,0 +1,490 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use rand::{seq::SliceRandom, thread_rng};
+
+use crate::{
+    audio::{Mixer, TrackId},
+    config::RESOURCE_DIR,
+    game_state::GameState,
+    manifest,
+};
+
+pub const MUSIC_DIR: &str = "music";
+
+const CROSSFADE_SECONDS: f32 = 1.5;
+const LAYER_FADE_SECONDS: f32 = 2.0;
+
+// Rally lengths at which the first and second extra layers come in.
+const RALLY_INTENSITY: [u32; 2] = [4, 8];
+const MAX_INTENSITY: usize = RALLY_INTENSITY.len();
+
+// A piece of music made of a base layer and optional intensity layers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    pub name: String,
+    pub layers: Vec<PathBuf>,
+}
+
+// Groups music files into tracks. `theme.ogg` is the base layer of the track
+// "theme", `theme.layer1.ogg` and `theme.layer2.ogg` are its intensity layers.
+pub fn group_tracks(paths: Vec<PathBuf>) -> Vec<Track> {
+    let mut grouped: BTreeMap<String, BTreeMap<usize, PathBuf>> = BTreeMap::new();
+
+    for path in paths {
+        let stem = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => stem.to_string(),
+            None => continue,
+        };
+
+        let (name, layer) = match stem.rsplit_once(".layer") {
+            Some((name, layer)) => match layer.parse::<usize>() {
+                Ok(layer) if layer > 0 => (name.to_string(), layer),
+                _ => (stem.clone(), 0),
+            },
+            None => (stem.clone(), 0),
+        };
+
+        grouped.entry(name).or_default().insert(layer, path);
+    }
+
+    grouped
+        .into_iter()
+        .filter_map(|(name, layers)| {
+            if !layers.contains_key(&0) {
+                eprintln!("warning: music track \"{}\" has no base layer", name);
+                return None;
+            }
+
+            Some(Track {
+                name,
+                layers: layers.into_values().collect(),
+            })
+        })
+        .collect()
+}
+
+// Finds the playable tracks in the music directory. Their headers are checked
+// up front because a broken file must never reach the streaming decoder.
+pub fn scan_tracks(dir: &Path) -> Vec<Track> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        if !matches!(extension.as_deref(), Some("ogg") | Some("wav")) {
+            continue;
+        }
+
+        let valid = File::open(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| manifest::validate_sound_stream(BufReader::new(file)));
+
+        match valid {
+            Ok(()) => paths.push(path),
+            Err(e) => eprintln!("warning: skipping music {}: {}", path.display(), e),
+        }
+    }
+
+    group_tracks(paths)
+}
+
+// Number of intensity layers to add on top of the base layer.
+pub fn intensity(rally: u32, match_point: bool) -> usize {
+    if match_point {
+        return MAX_INTENSITY;
+    }
+
+    RALLY_INTENSITY
+        .iter()
+        .filter(|threshold| rally >= **threshold)
+        .count()
+}
+
+pub struct Playlist {
+    tracks: Vec<Track>,
+    order: Vec<usize>,
+    position: usize,
+    shuffle: bool,
+}
+
+impl Playlist {
+    pub fn new(tracks: Vec<Track>, shuffle: bool) -> Playlist {
+        let mut playlist = Playlist {
+            order: (0..tracks.len()).collect(),
+            tracks,
+            position: 0,
+            shuffle,
+        };
+        playlist.reorder(None);
+
+        playlist
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        if self.shuffle != shuffle {
+            self.shuffle = shuffle;
+            self.position = 0;
+            self.reorder(None);
+        }
+    }
+
+    // Loops forever, a shuffled playlist is reshuffled after every pass.
+    pub fn next_track(&mut self) -> Option<&Track> {
+        if self.tracks.is_empty() {
+            return None;
+        }
+
+        if self.position >= self.order.len() {
+            let last = self.order.last().copied();
+            self.position = 0;
+            self.reorder(last);
+        }
+
+        let track = self.order[self.position];
+        self.position += 1;
+
+        Some(&self.tracks[track])
+    }
+
+    fn reorder(&mut self, last: Option<usize>) {
+        self.order = (0..self.tracks.len()).collect();
+
+        if self.shuffle {
+            self.order.shuffle(&mut thread_rng());
+
+            // Don't play the same track twice in a row across passes.
+            if self.order.len() > 1 && self.order.first().copied() == last {
+                let end = self.order.len() - 1;
+                self.order.swap(0, end);
+            }
+        }
+    }
+}
+
+struct PlayingTrack {
+    id: TrackId,
+    gain: f32,
+    layers: Vec<f32>,
+}
+
+impl PlayingTrack {
+    fn levels(&self) -> Vec<f32> {
+        self.layers.iter().map(|layer| layer * self.gain).collect()
+    }
+}
+
+pub struct MusicPlayer {
+    playlist: Playlist,
+    current: Option<PlayingTrack>,
+    fading_out: Vec<PlayingTrack>,
+    failed: bool,
+}
+
+impl MusicPlayer {
+    pub fn new(tracks: Vec<Track>, shuffle: bool) -> MusicPlayer {
+        MusicPlayer {
+            playlist: Playlist::new(tracks, shuffle),
+            current: None,
+            fading_out: Vec::new(),
+            failed: false,
+        }
+    }
+
+    pub fn load(shuffle: bool) -> MusicPlayer {
+        let tracks = scan_tracks(&Path::new(RESOURCE_DIR).join(MUSIC_DIR));
+        MusicPlayer::new(tracks, shuffle)
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.playlist.track_count()
+    }
+
+    // Fades the current track out while the next one fades in.
+    pub fn crossfade(&mut self, mixer: &mut Mixer) {
+        if self.playlist.track_count() < 2 || mixer.music_volume() <= 0.0 {
+            return;
+        }
+
+        if let Some(track) = self.current.take() {
+            self.fading_out.push(track);
+        }
+
+        self.start_next(mixer);
+    }
+
+    pub fn update(&mut self, mixer: &mut Mixer, delta: f32, intensity: usize) {
+        self.playlist.set_shuffle(mixer.settings.shuffle_music);
+
+        // Nothing is streamed while the music is muted, it starts over with
+        // the next track once unmuted.
+        if mixer.music_volume() <= 0.0 {
+            self.stop(mixer);
+            return;
+        }
+
+        if let Some(track) = &self.current {
+            if mixer.track_finished(track.id) {
+                mixer.stop_track(track.id);
+                self.current = None;
+            }
+        }
+
+        if self.current.is_none() {
+            self.start_next(mixer);
+        }
+
+        let crossfade_step = delta / CROSSFADE_SECONDS;
+        let layer_step = delta / LAYER_FADE_SECONDS;
+
+        if let Some(track) = self.current.as_mut() {
+            track.gain = (track.gain + crossfade_step).min(1.0);
+
+            for (i, layer) in track.layers.iter_mut().enumerate() {
+                let target = if i <= intensity { 1.0 } else { 0.0 };
+                *layer += (target - *layer).clamp(-layer_step, layer_step);
+            }
+
+            mixer.set_track_levels(track.id, &track.levels());
+        }
+
+        self.fading_out.retain_mut(|track| {
+            track.gain -= crossfade_step;
+
+            if track.gain <= 0.0 {
+                mixer.stop_track(track.id);
+                false
+            } else {
+                mixer.set_track_levels(track.id, &track.levels());
+                true
+            }
+        });
+    }
+
+    fn start_next(&mut self, mixer: &mut Mixer) {
+        let track = match self.playlist.next_track() {
+            Some(track) => track,
+            None => return,
+        };
+
+        match mixer.start_track(&track.layers) {
+            Ok(id) => {
+                let mut layers = vec![0.0; track.layers.len()];
+                // The base layer is always on, only the gain fades it in.
+                layers[0] = 1.0;
+
+                self.current = Some(PlayingTrack {
+                    id,
+                    gain: 0.0,
+                    layers,
+                });
+            }
+            Err(e) => {
+                if !self.failed {
+                    eprintln!("warning: could not play music \"{}\": {}", track.name, e);
+                    self.failed = true;
+                }
+            }
+        }
+    }
+
+    fn stop(&mut self, mixer: &mut Mixer) {
+        for track in self
+            .current
+            .take()
+            .into_iter()
+            .chain(self.fading_out.drain(..))
+        {
+            mixer.stop_track(track.id);
+        }
+    }
+}
+
+impl GameState {
+    pub fn update_music(&mut self, delta: f32) {
+        let intensity = intensity(self.arena.rally, self.arena.is_match_point());
+        self.music.update(&mut self.mixer, delta, intensity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{MixerSettings, RecordingBackend};
+
+    fn track(name: &str, layers: usize) -> Track {
+        Track {
+            name: name.to_string(),
+            layers: (0..layers)
+                .map(|i| PathBuf::from(format!("{}.layer{}.ogg", name, i)))
+                .collect(),
+        }
+    }
+
+    fn player(tracks: Vec<Track>) -> (MusicPlayer, Mixer, RecordingBackend) {
+        let recording = RecordingBackend::default();
+        let settings = MixerSettings {
+            music: 1.0,
+            ..MixerSettings::default()
+        };
+        let mixer = Mixer::new(settings, Box::new(recording.clone()));
+
+        (MusicPlayer::new(tracks, false), mixer, recording)
+    }
+
+    #[test]
+    fn files_are_grouped_into_layered_tracks() {
+        let paths = vec![
+            PathBuf::from("music/theme.layer2.ogg"),
+            PathBuf::from("music/theme.ogg"),
+            PathBuf::from("music/theme.layer1.ogg"),
+            PathBuf::from("music/calm.wav"),
+            PathBuf::from("music/orphan.layer1.ogg"),
+        ];
+
+        let tracks = group_tracks(paths);
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].name, "calm");
+        assert_eq!(
+            tracks[1].layers,
+            vec![
+                PathBuf::from("music/theme.ogg"),
+                PathBuf::from("music/theme.layer1.ogg"),
+                PathBuf::from("music/theme.layer2.ogg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn scan_skips_broken_files() {
+        let dir = std::env::temp_dir().join(format!("ggezgame-music-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::copy("resources/goal.wav", dir.join("theme.wav")).unwrap();
+        fs::copy("resources/goal.wav", dir.join("theme.layer1.wav")).unwrap();
+        fs::write(dir.join("broken.ogg"), "OggS and nothing else").unwrap();
+        fs::write(
+            dir.join("cut.wav"),
+            &fs::read("resources/goal.wav").unwrap()[..60],
+        )
+        .unwrap();
+        fs::write(dir.join("notes.txt"), "not music").unwrap();
+
+        let tracks = scan_tracks(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].name, "theme");
+        assert_eq!(tracks[0].layers.len(), 2);
+    }
+
+    #[test]
+    fn playlist_loops_in_order() {
+        let mut playlist = Playlist::new(vec![track("a", 1), track("b", 1)], false);
+
+        let names: Vec<String> = (0..5)
+            .map(|_| playlist.next_track().unwrap().name.clone())
+            .collect();
+
+        assert_eq!(names, vec!["a", "b", "a", "b", "a"]);
+    }
+
+    #[test]
+    fn shuffle_plays_every_track_once_per_pass() {
+        let tracks: Vec<Track> = ["a", "b", "c", "d"].iter().map(|n| track(n, 1)).collect();
+        let mut playlist = Playlist::new(tracks, true);
+
+        let mut previous = String::new();
+        for _ in 0..10 {
+            let mut pass: Vec<String> = (0..4)
+                .map(|_| playlist.next_track().unwrap().name.clone())
+                .collect();
+
+            assert_ne!(pass[0], previous);
+            previous = pass[3].clone();
+
+            pass.sort();
+            assert_eq!(pass, vec!["a", "b", "c", "d"]);
+        }
+    }
+
+    #[test]
+    fn intensity_rises_with_rally_and_match_point() {
+        assert_eq!(intensity(0, false), 0);
+        assert_eq!(intensity(4, false), 1);
+        assert_eq!(intensity(20, false), 2);
+        assert_eq!(intensity(0, true), 2);
+    }
+
+    #[test]
+    fn layers_fade_in_with_intensity() {
+        let (mut music, mut mixer, recording) = player(vec![track("a", 3)]);
+
+        for _ in 0..10 {
+            music.update(&mut mixer, 1.0, 1);
+        }
+
+        assert_eq!(recording.tracks()[&0].volumes, vec![1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn crossfade_replaces_the_current_track() {
+        let (mut music, mut mixer, recording) = player(vec![track("a", 1), track("b", 1)]);
+
+        music.update(&mut mixer, 10.0, 0);
+        music.crossfade(&mut mixer);
+        music.update(&mut mixer, 0.5, 0);
+
+        // Both play during the crossfade.
+        let tracks = recording.tracks();
+        assert_eq!(tracks.len(), 2);
+        assert!(tracks[&0].volumes[0] > tracks[&1].volumes[0]);
+
+        music.update(&mut mixer, 10.0, 0);
+        let tracks = recording.tracks();
+        assert_eq!(tracks.keys().copied().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(tracks[&1].layers, vec![PathBuf::from("b.layer0.ogg")]);
+    }
+
+    #[test]
+    fn finished_tracks_advance_the_playlist() {
+        let (mut music, mut mixer, recording) = player(vec![track("a", 1), track("b", 1)]);
+
+        music.update(&mut mixer, 0.1, 0);
+        recording.finish_track(0);
+        music.update(&mut mixer, 0.1, 0);
+
+        let tracks = recording.tracks();
+        assert_eq!(tracks.keys().copied().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn muting_stops_the_music() {
+        let (mut music, mut mixer, recording) = player(vec![track("a", 1)]);
+
+        music.update(&mut mixer, 0.1, 0);
+        assert_eq!(recording.tracks().len(), 1);
+
+        mixer.toggle_mute(crate::audio::Channel::Music);
+        music.update(&mut mixer, 0.1, 0);
+        assert!(recording.tracks().is_empty());
+    }
+}
@@ -1,11 +1,8 @@
 use ggez::graphics::Rect;
 
-use crate::pong::Side;
-
-const PADDLE_WIDTH: f32 = 12.0;
-const PADDLE_HEIGHT: f32 = 100.0;
-const PADDLE_SPEED: f32 = 450.0;
+use crate::{arena::Side, config::Tuning};
 
+#[derive(Clone)]
 pub struct Paddle {
     pub rect: Rect,
     pub side: Side,
@@ -15,25 +12,48 @@ pub struct Paddle {
 }
 
 impl Paddle {
-    pub fn new(game_width: f32, game_height: f32, side: Side) -> Paddle {
+    pub fn new(game_width: f32, game_height: f32, side: Side, tuning: &Tuning) -> Paddle {
         let x = match side {
             Side::Left => 0.0,
-            Side::Right => game_width - PADDLE_WIDTH,
+            Side::Right => game_width - tuning.paddle_width,
             _ => panic!("Paddles go on the left or right walls."),
         };
 
-        let y = (game_height - PADDLE_HEIGHT) / 2.0;
-        let w = PADDLE_WIDTH;
-        let h = PADDLE_HEIGHT;
+        let y = (game_height - tuning.paddle_height) / 2.0;
+        let w = tuning.paddle_width;
+        let h = tuning.paddle_height;
 
         let rect = Rect::new(x, y, w, h);
 
         Paddle {
             rect,
             side,
-            dy: PADDLE_SPEED,
+            dy: tuning.paddle_speed,
             is_up_holding: false,
             is_down_holding: false,
         }
     }
+
+    // Units per second, negative while moving up.
+    pub fn velocity(&self) -> f32 {
+        match (self.is_up_holding, self.is_down_holding) {
+            (true, false) => -self.dy,
+            (false, true) => self.dy,
+            _ => 0.0,
+        }
+    }
+
+    // Applies new tuning values, keeping the paddle centered where it was.
+    pub fn retune(&mut self, game_width: f32, tuning: &Tuning) {
+        let center_y = self.rect.y + self.rect.h / 2.0;
+
+        self.rect.w = tuning.paddle_width;
+        self.rect.h = tuning.paddle_height;
+        self.rect.y = center_y - self.rect.h / 2.0;
+        if let Side::Right = self.side {
+            self.rect.x = game_width - self.rect.w;
+        }
+
+        self.dy = tuning.paddle_speed;
+    }
 }
@@ -1,4 +1,7 @@
-use rand::distributions::{Distribution, Uniform};
+use rand::{
+    distributions::{Distribution, Uniform},
+    Rng,
+};
 
 #[derive(Clone, Copy)]
 pub enum ParticleType {
@@ -20,21 +23,20 @@ pub struct Particle {
 }
 
 impl Particle {
-    pub fn new(x: f32, y: f32, shimmer: bool) -> Particle {
-        let mut rng = rand::thread_rng();
+    // Spawns near (`x`, `y`), the software rasterizer passes a seeded `rng`
+    // so its frames are repeatable.
+    pub fn new(x: f32, y: f32, shimmer: bool, rng: &mut impl Rng) -> Particle {
+        let x = x - 16.0 + Uniform::new_inclusive(0, 24).sample(rng) as f32;
+        let y = y - 16.0 + Uniform::new_inclusive(0, 24).sample(rng) as f32;
 
-        let x = x - 16.0 + Uniform::new_inclusive(0, 24).sample(&mut rng) as f32;
-        let y = y - 16.0 + Uniform::new_inclusive(0, 24).sample(&mut rng) as f32;
+        let frame = Uniform::new_inclusive(0, 4).sample(rng) as usize;
 
-        let frame = Uniform::new_inclusive(0, 4).sample(&mut rng) as usize;
-
-        let particle_type;
-        match Uniform::from(0..3).sample(&mut rng) {
-            0 => particle_type = ParticleType::Blue,
-            1 => particle_type = ParticleType::Red,
-            2 => particle_type = ParticleType::Green,
-            _ => particle_type = ParticleType::None,
-        }
+        let particle_type = match Uniform::from(0..3).sample(rng) {
+            0 => ParticleType::Blue,
+            1 => ParticleType::Red,
+            2 => ParticleType::Green,
+            _ => ParticleType::None,
+        };
 
         Particle {
             x,
@@ -45,4 +47,22 @@ impl Particle {
             shimmer,
         }
     }
+
+    // One drawn frame of the trail behind the ball at (`x`, `y`): dead
+    // particles respawn there, live ones age and shimmer every other frame.
+    pub fn advance(&mut self, x: f32, y: f32, rng: &mut impl Rng) {
+        if self.is_dead {
+            *self = Particle::new(x, y, false, rng);
+        }
+
+        if self.frame.is_multiple_of(2) {
+            self.shimmer = true;
+        }
+
+        self.frame += 1;
+
+        if self.frame > 12 {
+            self.is_dead = true;
+        }
+    }
 }
@@ -1,72 +1,151 @@
-use ggez::{
-    event,
-    input::keyboard::{KeyCode, KeyMods},
-    Context,
+use ggez::input::keyboard::KeyCode;
+
+use crate::{
+    audio::Channel,
+    config::{Players, MAX_NAME_LENGTH},
+    game_state::GameState,
+    hot_reload::config_modified,
+    keys::PaddleAction,
+    menu::MenuPage,
+    scene::Transition,
+    scenes::MenuScene,
+    toast::Toast,
 };
 
-use crate::game_state::{GameMode, GameState};
+impl GameState {
+    pub fn game_key_down(&mut self, keycode: KeyCode) -> Transition {
+        if self.debug.key_down(keycode) {
+            return Transition::None;
+        }
 
-// @Refactor
-const LEFT_PADDLE_INDEX: usize = 0;
-const RIGHT_PADDLE_INDEX: usize = 1;
+        match keycode {
+            KeyCode::F1 => self.debug.enabled = !self.debug.enabled,
+            KeyCode::F2 => self.toggle_channel_mute(Channel::Sfx),
+            KeyCode::Escape => {
+                return Transition::Push(Box::new(MenuScene::new(MenuPage::Pause)));
+            }
+            _ => self.set_paddle_key(keycode, true),
+        }
 
-impl GameState {
-    pub fn key_down_event(
-        &mut self,
-        ctx: &mut Context,
-        keycode: KeyCode,
-        _keymod: KeyMods,
-        _repeat: bool,
-    ) {
-        match self.game_mode {
-            GameMode::Game => match keycode {
-                KeyCode::F1 => self.debug_mode = !self.debug_mode,
-                KeyCode::F2 => self.play_sounds = !self.play_sounds,
-                KeyCode::Escape => self.toggle_menu(),
-                KeyCode::W => self.paddles[LEFT_PADDLE_INDEX].is_up_holding = true,
-                KeyCode::S => self.paddles[LEFT_PADDLE_INDEX].is_down_holding = true,
-                KeyCode::Up => self.paddles[RIGHT_PADDLE_INDEX].is_up_holding = true,
-                KeyCode::Down => self.paddles[RIGHT_PADDLE_INDEX].is_down_holding = true,
-                _ => (),
-            },
-
-            GameMode::Menu => match keycode {
-                KeyCode::Up => self.menu.advance_menu_choice(1),
-                KeyCode::Down => self.menu.advance_menu_choice(-1),
-                KeyCode::Return => self.handle_menu_return(ctx, self.menu.current_menu_choice),
-                KeyCode::Escape => self.toggle_menu(),
-                _ => (),
-            },
+        Transition::None
+    }
+
+    pub fn game_key_up(&mut self, keycode: KeyCode) {
+        self.set_paddle_key(keycode, false);
+    }
+
+    fn set_paddle_key(&mut self, keycode: KeyCode, held: bool) {
+        if let Some(action) = self.config.controls.action(keycode) {
+            // Bots hold their paddle's keys themselves.
+            if self.is_bot(action.paddle_index()) {
+                return;
+            }
+
+            let paddle = &mut self.arena.paddles[action.paddle_index()];
+
+            if action.is_up() {
+                paddle.is_up_holding = held;
+            } else {
+                paddle.is_down_holding = held;
+            }
         }
     }
 
-    pub fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
-        match self.game_mode {
-            GameMode::Game => match keycode {
-                KeyCode::W => self.paddles[LEFT_PADDLE_INDEX].is_up_holding = false,
-                KeyCode::S => self.paddles[LEFT_PADDLE_INDEX].is_down_holding = false,
-                KeyCode::Up => self.paddles[RIGHT_PADDLE_INDEX].is_up_holding = false,
-                KeyCode::Down => self.paddles[RIGHT_PADDLE_INDEX].is_down_holding = false,
-                _ => (),
-            },
-            _ => (),
+    pub fn bind_key(&mut self, action: PaddleAction, keycode: KeyCode) {
+        if self.config.controls.bind(action, keycode) {
+            self.save_config();
         }
     }
 
-    fn handle_menu_return(&mut self, ctx: &mut Context, current_menu_choice: isize) {
-        match current_menu_choice {
-            0 => self.toggle_menu(),
-            1 => self.play_sounds = !self.play_sounds,
-            2 => {
-                self.show_particles = !self.show_particles;
-                self.stop_particles();
+    // Empty names fall back to the default.
+    pub fn set_player_name(&mut self, player: usize, name: String) {
+        let mut name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+        let defaults = Players::default();
+
+        match player {
+            1 => {
+                if name.is_empty() {
+                    name = defaults.left_name;
+                }
+                self.config.players.left_name = name;
+            }
+            _ => {
+                if name.is_empty() {
+                    name = defaults.right_name;
+                }
+                self.config.players.right_name = name;
             }
-            3 => {
-                self.reset_game(true);
-                self.toggle_menu()
+        }
+
+        self.save_config();
+    }
+
+    pub fn player_name(&self, player: usize) -> &str {
+        match player {
+            1 => &self.config.players.left_name,
+            _ => &self.config.players.right_name,
+        }
+    }
+
+    // `index` into the loaded locales.
+    pub fn set_language(&mut self, index: usize) {
+        if let Some(locale) = self.locale.locales().get(index) {
+            self.config.language = locale.code.clone();
+            self.locale.set_language(&self.config.language);
+            self.save_config();
+        }
+    }
+
+    pub fn set_theme(&mut self, index: usize) {
+        if let Some(theme) = self.themes.get(index) {
+            self.theme_index = index;
+            self.config.theme = theme.name.clone();
+            self.save_config();
+        }
+    }
+
+    pub fn toggle_particles(&mut self) {
+        self.show_particles = !self.show_particles;
+        self.stop_particles();
+    }
+
+    pub fn adjust_volume(&mut self, channel: Channel, delta: f32) {
+        self.mixer.adjust(channel, delta);
+        self.save_audio_settings();
+    }
+
+    pub fn toggle_channel_mute(&mut self, channel: Channel) {
+        self.mixer.toggle_mute(channel);
+        self.save_audio_settings();
+    }
+
+    pub fn toggle_ball_cue(&mut self) {
+        self.mixer.settings.ball_cue = !self.mixer.settings.ball_cue;
+        self.save_audio_settings();
+    }
+
+    pub fn toggle_shuffle(&mut self) {
+        self.mixer.settings.shuffle_music = !self.mixer.settings.shuffle_music;
+        self.save_audio_settings();
+    }
+
+    fn save_audio_settings(&mut self) {
+        self.config.audio = self.mixer.settings;
+        self.save_config();
+    }
+
+    pub fn save_config(&mut self) {
+        if self.config_read_only {
+            return;
+        }
+
+        match self.config.save() {
+            Ok(()) => self.config_saved_at = config_modified(),
+            Err(e) => {
+                eprintln!("warning: could not save settings: {}", e);
+                self.toasts
+                    .push(Toast::error(format!("Could not save settings: {}", e)));
             }
-            4 => event::quit(ctx),
-            _ => (),
-        };
+        }
     }
 }
@@ -1,25 +1,19 @@
-use std::time::Duration;
+use std::f32::consts::SQRT_2;
 
 use ggez::{
     event::EventHandler,
-    graphics::Rect,
     input::keyboard::{KeyCode, KeyMods},
     timer, Context, GameResult,
 };
+use rand::{thread_rng, Rng};
 
 use crate::{
-    ball::Ball,
-    game_state::{GameMode, GameState},
-    paddle::Paddle,
+    arena::ArenaEvent, game_state::GameState, scene::Transition, scenes::ConsoleScene,
+    toast::tick_toasts,
 };
 
-#[derive(Clone, Copy)]
-pub enum Side {
-    Left,
-    Right,
-    Top,
-    Bottom,
-}
+// Random pitch offset so repeated bounces don't sound identical.
+const PITCH_JITTER: f32 = 0.05;
 
 pub enum SoundType {
     Goal,
@@ -27,189 +21,120 @@ pub enum SoundType {
     Wall,
 }
 
-pub struct Wall {
-    rect: Rect,
-    side: Side,
-}
-
-impl Wall {
-    pub fn new(rect: Rect, side: Side) -> Wall {
-        Wall { rect, side }
+impl SoundType {
+    // Logical ID of the sound in the asset manifest.
+    pub fn id(&self) -> &'static str {
+        match self {
+            SoundType::Goal => "goal",
+            SoundType::Pad => "pad",
+            SoundType::Wall => "wall",
+        }
     }
 }
 
 impl GameState {
-    pub fn simulate(&mut self, ctx: &mut Context, time: f64) {
-        match self.game_mode {
-            GameMode::Menu => return,
-            GameMode::Game => (),
+    // Runs the simulation in fixed steps of `dt` to cover `frame_time`.
+    pub fn advance(&mut self, mut frame_time: f64) {
+        // The match starts once every bot is ready.
+        if !self.bots_ready() {
+            return;
         }
 
-        // Pause for a bit when the game starts.
-        if timer::time_since_start(ctx) < Duration::from_millis(1000) {
-            self.paused = Some(Duration::from_millis(1000));
-        }
+        while frame_time > 0.0 {
+            let cmp = frame_time.partial_cmp(&self.dt).expect("float NaN error");
 
-        // If the game is paused, then we don't want to simulate.
-        match self.paused {
-            Some(time_paused) => {
-                self.paused = time_paused.checked_sub(timer::delta(ctx));
+            let delta_time: f64 = if let std::cmp::Ordering::Less = cmp {
+                frame_time
+            } else {
+                self.dt
+            };
 
-                return;
-            }
-            None => (),
+            self.simulate(delta_time);
+
+            frame_time -= delta_time;
         }
+    }
 
-        // Update paddle positions and check paddle collisions.
-        for i in 0..self.paddles.len() {
-            let distance = self.paddles[i].dy as f64 * time;
-
-            let direction_value;
-            if self.paddles[i].is_up_holding && self.paddles[i].is_down_holding {
-                direction_value = 0.0;
-            } else if self.paddles[i].is_up_holding {
-                direction_value = 1.0;
-            } else if self.paddles[i].is_down_holding {
-                direction_value = -1.0;
-            } else {
-                direction_value = 0.0;
-            }
+    // Runs the rules for `time` seconds and plays what happened.
+    pub fn simulate(&mut self, time: f64) {
+        self.drive_bots(time);
+        self.scripts.before_step(&mut self.arena, time);
+        self.difficulty.watch(&self.arena);
+        self.arena.simulate(time);
+
+        let mut events = std::mem::take(&mut self.arena.events);
+        for event in events.drain(..) {
+            let sound_type = match event {
+                ArenaEvent::Goal { .. } => SoundType::Goal,
+                ArenaEvent::PaddleHit { .. } => SoundType::Pad,
+                ArenaEvent::WallBounce => SoundType::Wall,
+            };
 
-            // Update paddle position
-            self.paddles[i].rect.y = self.paddles[i].rect.y - (distance as f32 * direction_value);
-
-            // Paddle collides with top or bottom wall
-            for j in 0..self.walls.len() {
-                if !self.paddles[i].rect.overlaps(&self.walls[j].rect) {
-                    continue;
-                }
-
-                match self.walls[j].side {
-                    Side::Top => self.paddles[i].rect.y = 0.0,
-                    Side::Bottom => {
-                        self.paddles[i].rect.y = self.game_height - self.paddles[i].rect.h;
-                    }
-                    _ => {}
-                }
+            self.play_sound(sound_type);
+            if let ArenaEvent::Goal { scorer } = event {
+                self.on_goal(scorer);
             }
+            self.scripts.on_event(&mut self.arena, event);
         }
 
-        // Update ball position
-        self.ball.x = (self.ball.x as f64 + (self.ball.dx as f64 * time)) as f32;
-        self.ball.y = (self.ball.y as f64 + (self.ball.dy as f64 * time)) as f32;
-
-        let ball_rect = Rect::new(
-            self.ball.x - self.ball.radius,
-            self.ball.y - self.ball.radius,
-            self.ball.radius * 2.0,
-            self.ball.radius * 2.0,
-        );
-
-        // Check if ball collides with any walls
-        for i in 0..self.walls.len() {
-            if !ball_rect.overlaps(&self.walls[i].rect)
-                && (ball_rect.top() > 0.0 || ball_rect.bottom() < self.game_height)
-            {
-                continue;
-            }
-
-            match self.walls[i].side {
-                Side::Left => {
-                    // Left wall
-                    self.ball.x = 0.0 + self.ball.radius;
-                    self.player2_score = self.player2_score + 1;
-
-                    self.ball.dx = -self.ball.dx;
-
-                    self.assets.play_sound(self.play_sounds, SoundType::Goal);
-
-                    self.reset_game(false);
-
-                    self.paused = Some(Duration::from_millis(1200));
-                }
-                Side::Right => {
-                    // Right wall
-                    self.ball.x = self.game_width - self.ball.radius;
-                    self.player1_score = self.player1_score + 1;
-
-                    self.ball.dx = -self.ball.dx;
-
-                    self.assets.play_sound(self.play_sounds, SoundType::Goal);
-
-                    self.reset_game(false);
-
-                    self.paused = Some(Duration::from_millis(1200));
-                }
-                Side::Top => {
-                    // Top wall
-                    self.ball.y = 0.0 + self.ball.radius;
-
-                    self.ball.dy = -self.ball.dy;
-
-                    self.assets.play_sound(self.play_sounds, SoundType::Wall)
-                }
-                Side::Bottom => {
-                    // Bottom wall
-                    self.ball.y = self.game_height - self.ball.radius;
+        // Hand the buffer back so it doesn't allocate every step.
+        self.arena.events = events;
+        self.report_scripts();
+    }
 
-                    self.ball.dy = -self.ball.dy;
+    pub fn play_sound(&mut self, sound_type: SoundType) {
+        let id = sound_type.id();
 
-                    self.assets.play_sound(self.play_sounds, SoundType::Wall);
-                }
+        // Bounces are placed where they happen and get higher as the ball
+        // speeds up, goals always play centered.
+        let (pan, pitch) = match sound_type {
+            SoundType::Goal => (0.0, 1.0),
+            SoundType::Pad | SoundType::Wall => {
+                let jitter = thread_rng().gen_range(-PITCH_JITTER..=PITCH_JITTER);
+                (self.ball_pan(), self.ball_speed_pitch() + jitter)
             }
-        }
+        };
 
-        for paddle in &self.paddles {
-            if ball_rect.overlaps(&paddle.rect) {
-                match paddle.side {
-                    Side::Left => {
-                        self.ball.x = paddle.rect.x + paddle.rect.w + self.ball.radius;
-                    }
-                    Side::Right => {
-                        self.ball.x = paddle.rect.x - self.ball.radius;
-                    }
-                    _ => {}
-                }
-
-                self.ball.dx = -self.ball.dx;
-
-                self.assets.play_sound(self.play_sounds, SoundType::Pad);
-            }
+        if let Some(clip) = self.assets.sound(id) {
+            self.mixer.play_sfx(id, clip, pan, pitch);
         }
     }
 
-    pub fn reset_game(&mut self, reset_score: bool) {
-        self.ball = Ball::new(self.game_width, self.game_height);
+    // -1.0 at the left edge, 1.0 at the right edge.
+    fn ball_pan(&self) -> f32 {
+        (self.arena.ball.x / self.game_width * 2.0 - 1.0).clamp(-1.0, 1.0)
+    }
 
-        for paddle in self.paddles.iter_mut() {
-            *paddle = Paddle::new(self.game_width, self.game_height, paddle.side);
-        }
+    // 1.0 at the tuned serve speed.
+    fn ball_speed_pitch(&self) -> f32 {
+        let speed = self.arena.ball.dx.hypot(self.arena.ball.dy);
+        let serve_speed = self.arena.tuning.ball_speed * SQRT_2;
 
-        if reset_score {
-            self.player1_score = 0;
-            self.player2_score = 0;
-        }
+        (speed / serve_speed).sqrt().clamp(0.75, 1.5)
+    }
 
-        self.paused = Some(Duration::from_millis(1200));
+    // The cue follows the ball left and right, and rises an octave from the
+    // bottom of the screen to the top.
+    fn update_ball_cue(&mut self) {
+        let height = 1.0 - (self.arena.ball.y / self.game_height).clamp(0.0, 1.0);
+        let pitch = 2.0_f32.powf(height - 0.5);
+        let active = self.scenes.is_playing() && !self.scenes.is_fading();
+
+        self.mixer.update_ball_cue(self.ball_pan(), pitch, active);
     }
 }
 
 impl EventHandler for GameState {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let mut frame_time = timer::delta(ctx).as_secs_f64();
-        while frame_time > 0.0 {
-            let cmp = frame_time.partial_cmp(&self.dt).expect("float NaN error");
+        self.debug.record_frame(timer::delta(ctx));
+        self.hot_reload(ctx);
+        tick_toasts(&mut self.toasts, timer::delta(ctx));
 
-            let delta_time: f64 = if let std::cmp::Ordering::Less = cmp {
-                frame_time
-            } else {
-                self.dt
-            };
-
-            self.simulate(ctx, delta_time);
+        let delta = timer::delta(ctx).as_secs_f32();
+        self.with_scenes(|scenes, game| scenes.update(game, ctx, delta));
 
-            frame_time -= delta_time;
-        }
+        self.update_ball_cue();
+        self.update_music(delta);
 
         Ok(())
     }
@@ -222,13 +147,39 @@ impl EventHandler for GameState {
         &mut self,
         ctx: &mut Context,
         keycode: KeyCode,
-        keymod: KeyMods,
-        repeat: bool,
+        _keymod: KeyMods,
+        _repeat: bool,
     ) {
-        self.key_down_event(ctx, keycode, keymod, repeat);
+        if keycode == KeyCode::F11 {
+            self.toggle_fullscreen(ctx);
+            return;
+        }
+
+        // The console opens over any screen, it closes itself.
+        if keycode == KeyCode::Grave && self.scenes.top_name() != Some("console") {
+            let transition = Transition::Push(Box::new(ConsoleScene));
+            self.with_scenes(|scenes, game| scenes.apply(game, ctx, transition));
+            return;
+        }
+
+        self.with_scenes(|scenes, game| scenes.key_down(game, ctx, keycode));
+    }
+
+    fn key_up_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+        self.with_scenes(|scenes, game| scenes.key_up(game, ctx, keycode));
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) {
+        self.with_scenes(|scenes, game| scenes.text_input(game, character));
+    }
+
+    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        let (x, y) = self.camera.to_arena(x, y);
+        self.mouse_x = x;
+        self.mouse_y = y;
     }
 
-    fn key_up_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods) {
-        self.key_up_event(ctx, keycode, keymods);
+    fn resize_event(&mut self, _ctx: &mut Context, width: f32, height: f32) {
+        self.camera.resize(width, height);
     }
 }
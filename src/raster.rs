@@ -0,0 +1,509 @@
+use std::{fs, io, path::Path};
+
+use ggez::graphics::{Color, Rect};
+use image::ColorType;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    arena::Arena,
+    env::{follow_ball, Env, EnvConfig},
+    particle::Particle,
+    theme::{BallShape, PaddleShape, Theme},
+};
+
+// Particle colors for themes without their own, the centers of the particle
+// sprites. Indexed by `ParticleType`.
+const SPRITE_PARTICLE_COLORS: [[u8; 4]; 3] = [[0, 255, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255]];
+
+// Same as the game: 5x5 sprites, 12 of them, shimmer at 50/255 white.
+const PARTICLE_SIZE: f32 = 5.0;
+const PARTICLE_COUNT: usize = 12;
+const SHIMMER: [u8; 4] = [255, 255, 255, 50];
+
+// Stroke width of outlined paddles, in arena units.
+const OUTLINE_WIDTH: f32 = 2.0;
+
+// Score digits, 3x5 cells each, a row per byte with the leftmost cell in
+// the highest of the 3 bits. The game writes scores with the theme font, a
+// font needs a GPU, so frames use these blocky digits instead.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// Arena units per digit cell, close to the size of the 80 pt score text.
+const DIGIT_CELL: f32 = 10.0;
+
+// Space between the center line and each score.
+const SCORE_GAP: f32 = 40.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PixelFormat {
+    // 3 bytes per pixel.
+    Rgb,
+    // 1 byte per pixel, luma.
+    Gray,
+}
+
+impl PixelFormat {
+    pub fn channels(self) -> usize {
+        match self {
+            PixelFormat::Rgb => 3,
+            PixelFormat::Gray => 1,
+        }
+    }
+}
+
+// Largest frame width or height `Rasterizer::new` accepts.
+pub const MAX_RASTER_SIZE: u32 = 8192;
+
+#[derive(Clone, Copy)]
+pub struct RasterConfig {
+    // Size of the frame in pixels, the arena is stretched to fit.
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub particles: bool,
+    // Seeds the particles, the only random part of a frame.
+    pub seed: u64,
+}
+
+impl Default for RasterConfig {
+    fn default() -> RasterConfig {
+        RasterConfig {
+            width: 320,
+            height: 180,
+            format: PixelFormat::Rgb,
+            particles: false,
+            seed: 0,
+        }
+    }
+}
+
+// Draws what `draw_game` draws, paddles, ball, scores and optionally
+// particles, into a byte buffer on the CPU, for pixel observations and
+// snapshot tests. Theme sprites, the title and other text are left out.
+//
+// Only integer blending and fixed rules, so the same arenas rendered in the
+// same order always give the same bytes.
+pub struct Rasterizer {
+    // Private since the buffers are sized for it.
+    config: RasterConfig,
+    background: [u8; 4],
+    paddle: [u8; 4],
+    ball: [u8; 4],
+    score: [u8; 4],
+    particle_colors: Vec<[u8; 4]>,
+    paddle_shape: PaddleShape,
+    ball_shape: BallShape,
+    particles: Vec<Particle>,
+    rng: StdRng,
+    // Always RGB, converted when the format is gray.
+    rgb: Vec<u8>,
+    gray: Vec<u8>,
+    // Pixels per arena unit.
+    scale_x: f32,
+    scale_y: f32,
+}
+
+impl Rasterizer {
+    pub fn new(config: RasterConfig, theme: &Theme) -> Result<Rasterizer, String> {
+        let size_ok = |size: u32| size > 0 && size <= MAX_RASTER_SIZE;
+        if !size_ok(config.width) || !size_ok(config.height) {
+            return Err(format!(
+                "frame size {}x{} must be between 1 and {} pixels per side",
+                config.width, config.height, MAX_RASTER_SIZE
+            ));
+        }
+
+        let rgba = |color: Color| {
+            let (r, g, b, a) = color.to_rgba();
+            [r, g, b, a]
+        };
+
+        let particle_colors = match theme.particle_colors.is_empty() {
+            true => SPRITE_PARTICLE_COLORS.to_vec(),
+            false => theme.particle_colors.iter().map(|c| rgba(*c)).collect(),
+        };
+
+        let pixels = config.width as usize * config.height as usize;
+
+        let mut rasterizer = Rasterizer {
+            config,
+            background: rgba(theme.background),
+            paddle: rgba(theme.paddle),
+            ball: rgba(theme.ball),
+            score: rgba(theme.dim_text),
+            particle_colors,
+            paddle_shape: theme.paddle_shape,
+            ball_shape: theme.ball_shape,
+            particles: Vec::new(),
+            rng: StdRng::seed_from_u64(config.seed),
+            rgb: vec![0; pixels * 3],
+            gray: vec![0; pixels],
+            scale_x: 1.0,
+            scale_y: 1.0,
+        };
+        rasterizer.reset(config.seed);
+
+        Ok(rasterizer)
+    }
+
+    pub fn config(&self) -> &RasterConfig {
+        &self.config
+    }
+
+    // Starts the particles over, e.g. when the environment is reset.
+    pub fn reset(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.particles.clear();
+    }
+
+    // Draws `arena` and returns the frame, rows top to bottom in
+    // `config.format`.
+    pub fn render(&mut self, arena: &Arena) -> &[u8] {
+        self.scale_x = self.config.width as f32 / arena.width;
+        self.scale_y = self.config.height as f32 / arena.height;
+
+        let background = self.background;
+        for pixel in self.rgb.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&background[..3]);
+        }
+
+        for paddle in arena.paddles.iter() {
+            self.draw_paddle(paddle.rect);
+        }
+
+        self.draw_ball(arena);
+
+        if self.config.particles {
+            self.draw_particles(arena.ball.x, arena.ball.y);
+        }
+
+        self.draw_scores(arena);
+
+        self.frame()
+    }
+
+    // The last rendered frame.
+    pub fn frame(&mut self) -> &[u8] {
+        match self.config.format {
+            PixelFormat::Rgb => &self.rgb,
+            PixelFormat::Gray => {
+                for (gray, rgb) in self.gray.iter_mut().zip(self.rgb.chunks_exact(3)) {
+                    *gray = luma(rgb);
+                }
+                &self.gray
+            }
+        }
+    }
+
+    pub fn save_png(&mut self, path: &Path) -> io::Result<()> {
+        let (width, height) = (self.config.width, self.config.height);
+        let color_type = match self.config.format {
+            PixelFormat::Rgb => ColorType::RGB(8),
+            PixelFormat::Gray => ColorType::Gray(8),
+        };
+
+        image::save_buffer(path, self.frame(), width, height, color_type)
+    }
+
+    fn draw_paddle(&mut self, rect: Rect) {
+        let color = self.paddle;
+
+        match self.paddle_shape {
+            PaddleShape::Solid => self.fill_rect(rect, color),
+            PaddleShape::Outline => {
+                // Centered on the edges like a ggez stroke, at least a pixel wide.
+                let t = OUTLINE_WIDTH.max(1.0 / self.scale_x.min(self.scale_y));
+                let (x, y, w, h) = (rect.x - t / 2.0, rect.y - t / 2.0, rect.w + t, rect.h + t);
+
+                self.fill_rect(Rect::new(x, y, w, t), color);
+                self.fill_rect(Rect::new(x, y + h - t, w, t), color);
+                self.fill_rect(Rect::new(x, y, t, h), color);
+                self.fill_rect(Rect::new(x + w - t, y, t, h), color);
+            }
+        }
+    }
+
+    fn draw_ball(&mut self, arena: &Arena) {
+        let ball = &arena.ball;
+        let color = self.ball;
+
+        match self.ball_shape {
+            BallShape::Square => self.fill_rect(ball.rect(), color),
+            BallShape::Circle => {
+                let (x0, x1) = self.columns(ball.x - ball.radius, ball.x + ball.radius);
+                let (y0, y1) = self.rows(ball.y - ball.radius, ball.y + ball.radius);
+
+                for py in y0..y1 {
+                    for px in x0..x1 {
+                        let dx = (px as f32 + 0.5) / self.scale_x - ball.x;
+                        let dy = (py as f32 + 0.5) / self.scale_y - ball.y;
+
+                        if dx * dx + dy * dy <= ball.radius * ball.radius {
+                            self.blend(px, py, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Same trail as the game's, one frame per render.
+    fn draw_particles(&mut self, x: f32, y: f32) {
+        if self.particles.is_empty() {
+            self.particles = (0..PARTICLE_COUNT)
+                .map(|_| Particle::new(x, y, false, &mut self.rng))
+                .collect();
+        }
+
+        for i in 0..self.particles.len() {
+            self.particles[i].advance(x, y, &mut self.rng);
+
+            let particle = self.particles[i].clone();
+            let rect = Rect::new(particle.x, particle.y, PARTICLE_SIZE, PARTICLE_SIZE);
+            let color =
+                self.particle_colors[particle.particle_type as usize % self.particle_colors.len()];
+
+            self.fill_rect(rect, color);
+
+            if particle.shimmer {
+                self.fill_rect(rect, SHIMMER);
+                self.particles[i].shimmer = false;
+            }
+        }
+    }
+
+    fn draw_scores(&mut self, arena: &Arena) {
+        let left = arena.player1_score.to_string();
+        let right = arena.player2_score.to_string();
+        let y = (arena.height - DIGIT_CELL * 5.0) / 2.0;
+
+        let left_x = arena.width / 2.0 - SCORE_GAP - text_width(&left);
+        self.draw_digits(&left, left_x, y);
+        self.draw_digits(&right, arena.width / 2.0 + SCORE_GAP, y);
+    }
+
+    fn draw_digits(&mut self, text: &str, x: f32, y: f32) {
+        let color = self.score;
+
+        for (i, digit) in text.bytes().enumerate() {
+            let rows = DIGITS[(digit - b'0') as usize];
+            let digit_x = x + i as f32 * DIGIT_CELL * 4.0;
+
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) == 0 {
+                        continue;
+                    }
+
+                    let cell = Rect::new(
+                        digit_x + column as f32 * DIGIT_CELL,
+                        y + row as f32 * DIGIT_CELL,
+                        DIGIT_CELL,
+                        DIGIT_CELL,
+                    );
+                    self.fill_rect(cell, color);
+                }
+            }
+        }
+    }
+
+    // Pixels whose centers are inside `rect`.
+    fn fill_rect(&mut self, rect: Rect, color: [u8; 4]) {
+        let (x0, x1) = self.columns(rect.x, rect.x + rect.w);
+        let (y0, y1) = self.rows(rect.y, rect.y + rect.h);
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                self.blend(px, py, color);
+            }
+        }
+    }
+
+    fn columns(&self, left: f32, right: f32) -> (usize, usize) {
+        span(left * self.scale_x, right * self.scale_x, self.config.width)
+    }
+
+    fn rows(&self, top: f32, bottom: f32) -> (usize, usize) {
+        span(
+            top * self.scale_y,
+            bottom * self.scale_y,
+            self.config.height,
+        )
+    }
+
+    fn blend(&mut self, px: usize, py: usize, color: [u8; 4]) {
+        let i = (py * self.config.width as usize + px) * 3;
+        let alpha = color[3] as u32;
+
+        for (dst, src) in self.rgb[i..i + 3].iter_mut().zip(color.iter()) {
+            *dst = ((*src as u32 * alpha + *dst as u32 * (255 - alpha) + 127) / 255) as u8;
+        }
+    }
+}
+
+// Pixels from `start` to `end` whose centers are inside, clamped to `size`.
+fn span(start: f32, end: f32, size: u32) -> (usize, usize) {
+    let clamp = |v: f32| (v - 0.5).ceil().max(0.0).min(size as f32) as usize;
+    (clamp(start), clamp(end))
+}
+
+fn text_width(text: &str) -> f32 {
+    (text.len() as f32 * 4.0 - 1.0) * DIGIT_CELL
+}
+
+// ITU-R BT.601 luma with integers.
+fn luma(rgb: &[u8]) -> u8 {
+    let (r, g, b) = (rgb[0] as u32, rgb[1] as u32, rgb[2] as u32);
+    ((r * 299 + g * 587 + b * 114 + 500) / 1000) as u8
+}
+
+// `--dump-frames <dir> [N]`: plays N steps of the headless environment with
+// seed 0 and both paddles following the ball, and saves every frame as
+// `frame_0000.png` and so on.
+pub fn dump_frames(dir: &Path, frames: usize) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let mut env = Env::new(EnvConfig::default());
+    let mut rasterizer = Rasterizer::new(
+        RasterConfig {
+            particles: true,
+            ..RasterConfig::default()
+        },
+        &Theme::classic(),
+    )
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut observation = env.reset(0);
+
+    for frame in 0..frames {
+        rasterizer.render(&env.arena);
+        rasterizer.save_png(&dir.join(format!("frame_{:04}.png", frame)))?;
+
+        let actions = [follow_ball(&observation, 0), follow_ball(&observation, 1)];
+        observation = env.step(actions).0;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Action;
+
+    fn rasterizer(format: PixelFormat, particles: bool, theme: &Theme) -> Rasterizer {
+        let config = RasterConfig {
+            format,
+            particles,
+            ..RasterConfig::default()
+        };
+        Rasterizer::new(config, theme).unwrap()
+    }
+
+    fn pixel(frame: &[u8], x: usize, y: usize) -> &[u8] {
+        let i = (y * 320 + x) * 3;
+        &frame[i..i + 3]
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_bytes() {
+        let run = || {
+            let mut env = Env::new(EnvConfig::default());
+            let mut rasterizer = rasterizer(PixelFormat::Rgb, true, &Theme::classic());
+            let mut observation = env.reset(5);
+            let mut frames = Vec::new();
+
+            for _ in 0..300 {
+                frames.extend_from_slice(rasterizer.render(&env.arena));
+                observation = env.step([follow_ball(&observation, 0), Action::Up]).0;
+            }
+            frames
+        };
+
+        assert!(run() == run());
+    }
+
+    #[test]
+    fn draws_ball_paddles_and_scores() {
+        let mut env = Env::new(EnvConfig::default());
+        env.reset(0);
+        env.arena.player1_score = 1;
+        let mut rasterizer = rasterizer(PixelFormat::Rgb, false, &Theme::classic());
+        let frame = rasterizer.render(&env.arena);
+
+        // The ball is in the middle, the paddles on both edges.
+        assert_eq!(pixel(frame, 160, 90), &[255, 255, 255]);
+        assert_eq!(pixel(frame, 1, 90), &[255, 255, 255]);
+        assert_eq!(pixel(frame, 318, 90), &[255, 255, 255]);
+        assert_eq!(pixel(frame, 100, 20), &[0, 0, 0]);
+
+        // The middle cell of the left "1", dim text over black.
+        assert_eq!(pixel(frame, 146, 90), &[25, 25, 25]);
+    }
+
+    #[test]
+    fn outlined_paddles_are_hollow() {
+        let mut theme = Theme::classic();
+        theme.paddle_shape = PaddleShape::Outline;
+        let mut env = Env::new(EnvConfig::default());
+        env.reset(0);
+        let mut rasterizer = Rasterizer::new(
+            RasterConfig {
+                width: 1280,
+                height: 720,
+                ..RasterConfig::default()
+            },
+            &theme,
+        )
+        .unwrap();
+        let frame = rasterizer.render(&env.arena);
+        let rect = env.arena.paddles[0].rect;
+        let at = |x: f32, y: f32| {
+            let i = (y as usize * 1280 + x as usize) * 3;
+            frame[i]
+        };
+
+        assert_eq!(at(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0), 0);
+        assert_eq!(at(rect.x + rect.w / 2.0, rect.y), 255);
+    }
+
+    #[test]
+    fn bad_sizes_are_rejected() {
+        let size = |width, height| {
+            let config = RasterConfig {
+                width,
+                height,
+                ..RasterConfig::default()
+            };
+            Rasterizer::new(config, &Theme::classic()).map(|_| ())
+        };
+
+        assert!(size(0, 180).is_err());
+        assert!(size(320, 0).is_err());
+        assert!(size(70000, 70000).is_err());
+        assert!(size(1, 1).is_ok());
+    }
+
+    #[test]
+    fn gray_frames_have_one_byte_per_pixel() {
+        let mut env = Env::new(EnvConfig::default());
+        env.reset(0);
+        let mut rasterizer = rasterizer(PixelFormat::Gray, false, &Theme::classic());
+        assert_eq!(rasterizer.config().format, PixelFormat::Gray);
+        let frame = rasterizer.render(&env.arena);
+
+        assert_eq!(frame.len(), 320 * 180);
+        assert_eq!(frame[90 * 320 + 160], 255);
+        assert_eq!(luma(&[255, 0, 0]), 76);
+    }
+}
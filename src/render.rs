@@ -1,110 +1,172 @@
 use ggez::{
     graphics::Font,
-    graphics::{self, DrawMode, DrawParam, Scale},
+    graphics::{self, DrawMode, DrawParam, MeshBuilder},
     nalgebra::Point2,
     timer, Context, GameResult,
 };
 use graphics::Color;
 use std::time::Duration;
 
-use crate::{assets::Assets, particle::Particle};
 use crate::{
-    game_state::{GameMode, GameState},
+    arena::Side,
+    debug::{predict_path, Overlay, FRAME_HISTORY},
+    game_state::GameState,
+    keys::key_name,
+    locale::Localizer,
+    menu::{Menu, MenuItem, MenuMode, MenuPage, Widget},
     particle::ParticleType,
+    render_cache::RenderCache,
+    theme::Theme,
 };
+use crate::{assets::Assets, particle::Particle};
+
+// How far ahead velocity vectors point, in seconds.
+const VECTOR_SECONDS: f32 = 0.25;
+
+// Frame-time graph scale, it is cut off at `GRAPH_HEIGHT`.
+const GRAPH_PIXELS_PER_MS: f32 = 3.0;
+const GRAPH_HEIGHT: f32 = 120.0;
+
+const DEBUG_WALL_COLOR: Color = Color::new(1.0, 0.3, 0.3, 1.0);
+const DEBUG_HITBOX_COLOR: Color = Color::new(0.3, 1.0, 0.3, 1.0);
+const DEBUG_VECTOR_COLOR: Color = Color::new(1.0, 1.0, 0.2, 1.0);
+const DEBUG_PATH_COLOR: Color = Color::new(0.3, 0.7, 1.0, 0.8);
+
+// The theme font, with fallbacks for text it has no glyphs for: the
+// locale's font and then the built-in one. Sizes are multiplied by
+// `text_scale`.
+#[derive(Clone, Copy)]
+struct UiFont<'a> {
+    assets: &'a Assets,
+    primary: Option<&'a str>,
+    fallback: Option<&'a str>,
+    text_scale: f32,
+}
+
+impl<'a> UiFont<'a> {
+    fn new(
+        assets: &'a Assets,
+        theme: &'a Theme,
+        locale: &'a Localizer,
+        text_scale: f32,
+    ) -> UiFont<'a> {
+        UiFont {
+            assets,
+            primary: theme.font.as_deref(),
+            fallback: locale.current().font.as_deref(),
+            text_scale,
+        }
+    }
 
-fn get_text_width(ctx: &mut Context, text: &str, font: Font, scale: f32) -> u32 {
-    let mut text = graphics::Text::new(text);
-    text.set_font(font, Scale::uniform(scale));
+    fn builtin(assets: &'a Assets) -> UiFont<'a> {
+        UiFont {
+            assets,
+            primary: None,
+            fallback: None,
+            text_scale: 1.0,
+        }
+    }
+
+    fn pick(&self, text: &str) -> Font {
+        let primary = match self.primary {
+            Some(primary) => primary,
+            None => return Font::default(),
+        };
 
-    text.width(ctx)
+        [Some(primary), self.fallback]
+            .iter()
+            .flatten()
+            .find(|id| self.assets.font_covers(id, text))
+            .map(|id| self.assets.font(id))
+            .unwrap_or_default()
+    }
 }
 
-fn get_text_height(ctx: &mut Context, text: &str, font: Font, scale: f32) -> u32 {
-    let mut text = graphics::Text::new(text);
-    text.set_font(font, Scale::uniform(scale));
+fn get_text_width(
+    ctx: &mut Context,
+    cache: &mut RenderCache,
+    text: &str,
+    font: UiFont,
+    scale: f32,
+) -> u32 {
+    cache
+        .text(text, font.pick(text), scale * font.text_scale)
+        .width(ctx)
+}
 
-    text.height(ctx)
+fn get_text_height(
+    ctx: &mut Context,
+    cache: &mut RenderCache,
+    text: &str,
+    font: UiFont,
+    scale: f32,
+) -> u32 {
+    cache
+        .text(text, font.pick(text), scale * font.text_scale)
+        .height(ctx)
 }
 
 fn draw_text(
     ctx: &mut Context,
+    cache: &mut RenderCache,
     text: &str,
     pos: Point2<f32>,
-    font: Font,
+    font: UiFont,
     scale: f32,
     color: Color,
 ) -> GameResult<()> {
-    let mut text = graphics::Text::new(text);
-    text.set_font(font, graphics::Scale::uniform(scale));
-
     let params = graphics::DrawParam::default().dest(pos).color(color);
 
-    graphics::draw(ctx, &text, params)?;
+    let text = cache.text(text, font.pick(text), scale * font.text_scale);
+    graphics::draw(ctx, text, params)?;
 
     Ok(())
 }
 
-fn build_rectangle(
-    ctx: &mut Context,
-    x: f32,
-    y: f32,
-    w: f32,
-    h: f32,
-) -> GameResult<graphics::Mesh> {
-    let mb = &mut graphics::MeshBuilder::new();
-
-    let rect = graphics::Rect::new(x as f32, y as f32, w as f32, h as f32);
-
-    mb.rectangle(DrawMode::fill(), rect, graphics::WHITE);
-
-    mb.build(ctx)
-}
-
-fn build_circle(ctx: &mut Context, x: f32, y: f32, r: f32) -> GameResult<graphics::Mesh> {
-    let mb = &mut graphics::MeshBuilder::new();
-
-    mb.circle(
-        DrawMode::fill(),
-        Point2::new(x, y),
-        r,
-        0.01,
-        graphics::WHITE,
-    );
-
-    mb.build(ctx)
+// Draws `image` stretched over `rect`.
+fn draw_sprite(ctx: &mut Context, image: &graphics::Image, rect: graphics::Rect) -> GameResult<()> {
+    let scale = [
+        rect.w / image.width() as f32,
+        rect.h / image.height() as f32,
+    ];
+
+    graphics::draw(
+        ctx,
+        image,
+        DrawParam::new()
+            .dest(Point2::new(rect.x, rect.y))
+            .scale(scale),
+    )
 }
 
 fn draw_particles(
     ctx: &mut Context,
     x: f32,
     y: f32,
-    particles: &mut Vec<Particle>,
+    particles: &mut [Particle],
     assets: &mut Assets,
+    colors: &[Color],
 ) -> GameResult<()> {
-    for particle in particles.iter_mut() {
-        if particle.is_dead {
-            *particle = Particle::new(x, y, false);
-        }
+    let mut rng = rand::thread_rng();
 
-        if particle.frame % 2 == 0 {
-            particle.shimmer = true;
-        }
+    for particle in particles.iter_mut() {
+        particle.advance(x, y, &mut rng);
 
-        particle.frame = particle.frame + 1;
+        let dest = Point2::new(particle.x, particle.y);
 
-        if particle.frame > 12 {
-            particle.is_dead = true;
+        // Themes and palettes with their own colors tint a blank sprite instead.
+        if colors.is_empty() {
+            let image = assets.particle_image(particle.particle_type);
+            graphics::draw(ctx, image, DrawParam::new().dest(dest))?;
+        } else {
+            let color = colors[particle.particle_type as usize % colors.len()];
+            graphics::draw(
+                ctx,
+                &assets.blank_particle,
+                DrawParam::new().dest(dest).color(color),
+            )?;
         }
 
-        let image = assets.particle_image(particle.particle_type);
-
-        graphics::draw(
-            ctx,
-            image,
-            DrawParam::new().dest(Point2::new(particle.x, particle.y)),
-        )?;
-
         if particle.shimmer {
             let shimmer_image = assets.particle_image(ParticleType::Shimmer);
 
@@ -112,7 +174,7 @@ fn draw_particles(
                 ctx,
                 shimmer_image,
                 DrawParam::new()
-                    .dest(Point2::new(particle.x, particle.y))
+                    .dest(dest)
                     .color(Color::from_rgba(255, 255, 255, 50)),
             )?;
 
@@ -125,173 +187,707 @@ fn draw_particles(
 
 impl GameState {
     pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        // Everything is drawn in arena coordinates, the camera fits the arena
+        // to the window.
+        self.camera.apply(ctx)?;
         graphics::clear(ctx, graphics::BLACK);
 
-        match self.game_mode {
-            GameMode::Menu => {
-                self.draw_menu(ctx)?;
-            }
-            GameMode::Game => {
-                self.draw_game(ctx)?;
-            }
+        let background = self.theme().background;
+        self.draw_rect(
+            ctx,
+            graphics::Rect::new(0.0, 0.0, self.game_width, self.game_height),
+            background,
+        )?;
+
+        if let Some(background) = &self.theme().sprites.background {
+            let (w, h) = (self.game_width, self.game_height);
+            draw_sprite(ctx, background, graphics::Rect::new(0.0, 0.0, w, h))?;
         }
 
+        self.with_scenes(|scenes, game| scenes.draw(game, ctx))?;
+
+        // Letterbox last, so nothing drawn near the edges spills out of the arena.
+        for bar in self.camera.bars() {
+            self.draw_rect(ctx, bar, graphics::BLACK)?;
+        }
+
+        self.render_cache.end_frame();
+
         graphics::present(ctx)
     }
 
-    fn draw_game(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // Draw debug mode information like FPS, mouse coordinates, time scale.
-        if self.debug_mode {
-            draw_text(
-                ctx,
-                &format!("FPS: {}", timer::fps(ctx)),
-                Point2::new(0.0, 0.0),
-                Font::default(),
-                20.0,
-                graphics::WHITE,
-            )?;
-        }
+    // The mesh is white and tinted, so fading colors reuse one cached mesh.
+    fn draw_rect(
+        &mut self,
+        ctx: &mut Context,
+        rect: graphics::Rect,
+        color: Color,
+    ) -> GameResult<()> {
+        let mesh = self
+            .render_cache
+            .rect_mesh(ctx, rect.w, rect.h, graphics::WHITE)?;
+        graphics::draw(
+            ctx,
+            mesh,
+            DrawParam::new()
+                .dest(Point2::new(rect.x, rect.y))
+                .color(color),
+        )
+    }
 
-        // Draw the paddles
-        for paddle in self.paddles.iter_mut() {
-            let paddle_rect = build_rectangle(
-                ctx,
-                (*paddle).rect.x,
-                (*paddle).rect.y,
-                (*paddle).rect.w,
-                (*paddle).rect.h,
-            )?;
+    fn arena_rect(&self) -> graphics::Rect {
+        graphics::Rect::new(0.0, 0.0, self.game_width, self.game_height)
+    }
+
+    // Black over the whole arena, `alpha` 1.0 hides everything.
+    pub fn draw_fade(&mut self, ctx: &mut Context, alpha: f32) -> GameResult<()> {
+        let color = Color::new(0.0, 0.0, 0.0, alpha);
+        self.draw_rect(ctx, self.arena_rect(), color)
+    }
+
+    // Particles are left out when `covered`, so the game looks frozen under
+    // the pause menu.
+    pub fn draw_game(&mut self, ctx: &mut Context, covered: bool) -> GameResult<()> {
+        let colors = self.ui_colors();
+        let particles_visible = self.particles_visible();
+        let accessibility = self.config.accessibility;
+        let theme = &self.themes[self.theme_index];
 
-            graphics::draw(ctx, &paddle_rect, DrawParam::default())?;
+        // Draw the paddles
+        for paddle in self.arena.paddles.iter() {
+            match &theme.sprites.paddle {
+                Some(sprite) => draw_sprite(ctx, sprite, paddle.rect)?,
+                None => {
+                    let rect = paddle.rect;
+                    let mesh = self.render_cache.paddle_mesh(
+                        ctx,
+                        rect.w,
+                        rect.h,
+                        theme.paddle_shape,
+                        theme.paddle,
+                    )?;
+                    graphics::draw(
+                        ctx,
+                        mesh,
+                        DrawParam::new().dest(Point2::new(rect.x, rect.y)),
+                    )?;
+                }
+            }
         }
 
         // Draw the ball
-        let ball = build_circle(ctx, self.ball.x, self.ball.y, self.ball.radius)?;
-        graphics::draw(ctx, &ball, DrawParam::default())?;
+        match &theme.sprites.ball {
+            Some(sprite) => {
+                let r = self.arena.ball.radius;
+                let rect = graphics::Rect::new(
+                    self.arena.ball.x - r,
+                    self.arena.ball.y - r,
+                    r * 2.0,
+                    r * 2.0,
+                );
+                draw_sprite(ctx, sprite, rect)?;
+            }
+            None => {
+                let mesh = self.render_cache.ball_mesh(
+                    ctx,
+                    self.arena.ball.radius,
+                    theme.ball_shape,
+                    theme.ball,
+                )?;
+                let dest = Point2::new(self.arena.ball.x, self.arena.ball.y);
+                graphics::draw(ctx, mesh, DrawParam::new().dest(dest))?;
+            }
+        }
 
         // Draw ball particles
-        if self.show_particles {
+        if particles_visible && !covered {
+            let palette = accessibility.particle_palette.colors();
+            let particle_colors = match &palette {
+                Some(palette) => &palette[..],
+                None => &theme.particle_colors[..],
+            };
+
             draw_particles(
                 ctx,
-                self.ball.x,
-                self.ball.y,
+                self.arena.ball.x,
+                self.arena.ball.y,
                 &mut self.particles,
                 &mut self.assets,
+                particle_colors,
             )?;
         }
 
         // Draw UI text
-        let fancy_font = Font::new(ctx, "/joystix_mono.ttf")?;
+        let fancy_font = UiFont::new(&self.assets, theme, &self.locale, accessibility.text_scale);
 
         // Game title
-        let width = get_text_width(ctx, "PONG", fancy_font.clone(), 80.0);
+        let title = self.locale.get("title");
+        let width = get_text_width(ctx, &mut self.render_cache, title, fancy_font, 80.0);
         draw_text(
             ctx,
-            "PONG",
+            &mut self.render_cache,
+            title,
             Point2::new(self.game_width / 2.0 - width as f32 / 2.0, 10.0),
-            fancy_font.clone(),
+            fancy_font,
             80.0,
-            graphics::WHITE,
+            colors.text,
         )?;
 
         // Scores
-        let score_text = &format!("{} \t {}", self.player1_score, self.player2_score);
-        let width = get_text_width(ctx, score_text, fancy_font.clone(), 80.0);
-        let height = get_text_height(ctx, score_text, fancy_font.clone(), 80.0);
+        let score_text = &format!(
+            "{} \t {}",
+            self.arena.player1_score, self.arena.player2_score
+        );
+        let width = get_text_width(ctx, &mut self.render_cache, score_text, fancy_font, 80.0);
+        let height = get_text_height(ctx, &mut self.render_cache, score_text, fancy_font, 80.0);
         draw_text(
             ctx,
+            &mut self.render_cache,
             score_text,
             Point2::new(
                 self.game_width / 2.0 - width as f32 / 2.0,
                 self.game_height / 2.0 - height as f32 / 2.0,
             ),
-            fancy_font.clone(),
+            fancy_font,
             80.0,
-            Color::from_rgba(255, 255, 255, 25),
+            colors.dim_text,
         )?;
 
+        // How hard the AI playing a human is, see `difficulty.rs`
+        if self.difficulty_opponent().is_some() {
+            let text = self.difficulty_text();
+            let width = get_text_width(ctx, &mut self.render_cache, &text, fancy_font, 20.0);
+            draw_text(
+                ctx,
+                &mut self.render_cache,
+                &text,
+                Point2::new(
+                    self.game_width / 2.0 - width as f32 / 2.0,
+                    self.game_height - 40.0,
+                ),
+                fancy_font,
+                20.0,
+                colors.dim_text,
+            )?;
+        }
+
         // Draw READY then draw START! when the game is reset
-        if let Some(pause_time) = self.paused {
-            let mut status_text_string = "READY";
+        if let Some(pause_time) = self.arena.paused {
+            let mut status_text_string = self.locale.get("ready");
 
             if pause_time <= Duration::from_millis(500) {
-                status_text_string = "START!";
+                status_text_string = self.locale.get("start");
             }
 
-            let width = get_text_width(ctx, status_text_string, fancy_font.clone(), 25.0);
-            let height = get_text_height(ctx, status_text_string, fancy_font.clone(), 25.0);
+            let width = get_text_width(
+                ctx,
+                &mut self.render_cache,
+                status_text_string,
+                fancy_font,
+                25.0,
+            );
+            let height = get_text_height(
+                ctx,
+                &mut self.render_cache,
+                status_text_string,
+                fancy_font,
+                25.0,
+            );
             draw_text(
                 ctx,
+                &mut self.render_cache,
                 status_text_string,
                 Point2::new(
                     self.game_width / 2.0 - width as f32 / 2.0,
                     (self.game_height / 2.0 - height as f32 / 2.0) + height as f32 * 1.7,
                 ),
-                fancy_font.clone(),
+                fancy_font,
                 25.0,
-                Color::from_rgba(255, 255, 255, 25),
+                colors.dim_text,
             )?;
         }
 
+        if self.debug.enabled {
+            self.draw_debug(ctx, colors.text)?;
+        }
+
         Ok(())
     }
 
-    fn draw_menu(&mut self, ctx: &mut Context) -> GameResult<()> {
+    // Everything F1 turns on, see `debug.rs`. Drawn over the game so the
+    // hitboxes line up with what they belong to.
+    fn draw_debug(&mut self, ctx: &mut Context, text_color: Color) -> GameResult<()> {
+        let mut builder = MeshBuilder::new();
+        let mut has_shapes = false;
+
+        if self.debug.is_shown(Overlay::Hitboxes) {
+            for wall in self.arena.walls.iter() {
+                let r = wall.rect();
+                let points = [Point2::new(r.x, r.y), Point2::new(r.x + r.w, r.y + r.h)];
+                builder.line(&points, 4.0, DEBUG_WALL_COLOR)?;
+            }
+            for paddle in self.arena.paddles.iter() {
+                builder.rectangle(DrawMode::stroke(2.0), paddle.rect, DEBUG_HITBOX_COLOR);
+            }
+            builder.rectangle(
+                DrawMode::stroke(2.0),
+                self.arena.ball.rect(),
+                DEBUG_HITBOX_COLOR,
+            );
+            has_shapes = true;
+        }
+
+        if self.debug.is_shown(Overlay::Vectors) {
+            let ball = Point2::new(self.arena.ball.x, self.arena.ball.y);
+            let end = Point2::new(
+                ball.x + self.arena.ball.dx * VECTOR_SECONDS,
+                ball.y + self.arena.ball.dy * VECTOR_SECONDS,
+            );
+            if ball != end {
+                builder.line(&[ball, end], 2.0, DEBUG_VECTOR_COLOR)?;
+                has_shapes = true;
+            }
+
+            for paddle in self.arena.paddles.iter().filter(|p| p.velocity() != 0.0) {
+                let center = Point2::new(
+                    paddle.rect.x + paddle.rect.w / 2.0,
+                    paddle.rect.y + paddle.rect.h / 2.0,
+                );
+                let end = Point2::new(center.x, center.y + paddle.velocity() * VECTOR_SECONDS);
+                builder.line(&[center, end], 2.0, DEBUG_VECTOR_COLOR)?;
+                has_shapes = true;
+            }
+        }
+
+        if self.debug.is_shown(Overlay::Path) {
+            let (mut left_x, mut right_x) = (0.0, self.game_width);
+            for paddle in self.arena.paddles.iter() {
+                match paddle.side {
+                    Side::Left => left_x = paddle.rect.right(),
+                    _ => right_x = paddle.rect.left(),
+                }
+            }
+
+            let path = predict_path(&self.arena.ball, left_x, right_x, self.game_height);
+            if path.len() > 1 {
+                builder.line(&path, 1.0, DEBUG_PATH_COLOR)?;
+                has_shapes = true;
+            }
+        }
+
+        // One bar per frame, with a line at the 60 FPS budget.
+        if self.debug.is_shown(Overlay::FrameGraph) {
+            let bottom = self.game_height;
+            let budget = 1.0 / 60.0;
+
+            for (i, frame_time) in self.debug.frame_times().enumerate() {
+                let h = (frame_time * 1000.0 * GRAPH_PIXELS_PER_MS).min(GRAPH_HEIGHT);
+                let color = if frame_time > budget * 1.5 {
+                    DEBUG_WALL_COLOR
+                } else {
+                    DEBUG_HITBOX_COLOR
+                };
+                let bar = graphics::Rect::new(i as f32 * 2.0, bottom - h, 2.0, h);
+                builder.rectangle(DrawMode::fill(), bar, color);
+            }
+
+            let y = bottom - budget * 1000.0 * GRAPH_PIXELS_PER_MS;
+            let width = FRAME_HISTORY as f32 * 2.0;
+            builder.line(
+                &[Point2::new(0.0, y), Point2::new(width, y)],
+                1.0,
+                text_color,
+            )?;
+            has_shapes = true;
+        }
+
+        if has_shapes {
+            let mesh = builder.build(ctx)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        let mut lines = Vec::new();
+        if self.debug.is_shown(Overlay::Info) {
+            let alive = self.particles.iter().filter(|p| !p.is_dead).count();
+
+            lines.push(
+                self.locale
+                    .format("fps", &[("fps", &timer::fps(ctx).to_string())]),
+            );
+            lines.push(format!(
+                "frame {:.1} ms",
+                timer::delta(ctx).as_secs_f64() * 1000.0
+            ));
+            lines.push(format!("time {}", self.debug.time_text()));
+            lines.push(format!(
+                "particles {}/{}{}",
+                alive,
+                self.particles.len(),
+                if self.particles_visible() {
+                    ""
+                } else {
+                    " (off)"
+                }
+            ));
+            lines.push(format!(
+                "ball ({:.0}, {:.0}) speed {:.0}",
+                self.arena.ball.x,
+                self.arena.ball.y,
+                self.arena.ball.dx.hypot(self.arena.ball.dy)
+            ));
+            lines.push(format!("mouse ({:.0}, {:.0})", self.mouse_x, self.mouse_y));
+            lines.push(String::from(
+                "F3-F7 overlays, F8/F9 speed, F10 freeze, F12 step",
+            ));
+        }
+
+        let font = UiFont::builtin(&self.assets);
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(
+                ctx,
+                &mut self.render_cache,
+                line,
+                Point2::new(0.0, 22.0 * i as f32),
+                font,
+                20.0,
+                text_color,
+            )?;
+        }
+
+        // Hot reload results, newest at the bottom.
+        for (i, toast) in self.toasts.iter().enumerate() {
+            let color = match toast.is_error {
+                true => Color::from_rgba(255, 80, 80, 255),
+                false => Color::from_rgba(80, 255, 80, 255),
+            };
+
+            draw_text(
+                ctx,
+                &mut self.render_cache,
+                &toast.message,
+                Point2::new(0.0, 22.0 * (lines.len() + i) as f32),
+                font,
+                20.0,
+                color,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn draw_menu(&mut self, ctx: &mut Context, menu: &Menu) -> GameResult<()> {
+        let items = self.menu_items(menu.page);
+        let colors = self.ui_colors();
+        let accessibility = self.config.accessibility;
+
+        // Pages with sliders and switches need room for their values.
+        let scale = if items.iter().any(|item| item.has_value()) {
+            40.0
+        } else {
+            60.0
+        };
+        let label_width = items
+            .iter()
+            .filter(|item| item.has_value())
+            .map(|item| self.tr(item.label).chars().count())
+            .max()
+            .unwrap_or(0);
+
+        // Dim the game under overlay pages.
+        if menu.page != MenuPage::Title {
+            self.draw_rect(ctx, self.arena_rect(), colors.shade)?;
+        }
+
+        let header = match self.match_winner() {
+            Some(player) if menu.page == MenuPage::GameOver => {
+                let name = self.player_name(player).to_uppercase();
+                self.locale.format("winner", &[("name", &name)])
+            }
+            _ => self.tr("title").to_string(),
+        };
+
+        let theme = &self.themes[self.theme_index];
+
         // Draw UI text
-        let fancy_font = Font::new(ctx, "/joystix_mono.ttf")?;
+        let fancy_font = UiFont::new(&self.assets, theme, &self.locale, accessibility.text_scale);
 
         // Game title
-        let width = get_text_width(ctx, "PONG", fancy_font.clone(), 80.0);
+        let width = get_text_width(ctx, &mut self.render_cache, &header, fancy_font, 80.0);
         draw_text(
             ctx,
-            "PONG",
+            &mut self.render_cache,
+            &header,
             Point2::new(self.game_width / 2.0 - width as f32 / 2.0, 10.0),
-            fancy_font.clone(),
+            fancy_font,
             80.0,
-            graphics::WHITE,
+            colors.text,
         )?;
 
-        let sound_toggle_text = match self.play_sounds {
-            true => "Sounds ON",
-            false => "Sounds OFF",
-        };
-
-        let particles_toggle_text = match self.show_particles {
-            true => "Particles ON",
-            false => "Particles OFF",
-        };
+        let mut rows = Vec::with_capacity(items.len());
+        for (i, item) in items.iter().enumerate() {
+            let selected = menu.selected == i;
 
-        let menu_items = vec![
-            "Resume",
-            sound_toggle_text,
-            particles_toggle_text,
-            "Restart",
-            "Quit",
-        ];
+            let mut color = colors.dim_text;
+            if !item.enabled {
+                color.a *= 0.4;
+            } else if selected {
+                color = colors.highlight;
+            }
 
-        for i in 0..menu_items.len() {
-            let mut color = Color::from_rgba(255, 255, 255, 25);
+            let mode = if selected {
+                &menu.mode
+            } else {
+                &MenuMode::Browsing
+            };
+            let mut text = item_text(&self.locale, item, mode, label_width);
 
-            if self.menu.current_menu_choice == i as isize {
-                color = Color::from_rgba(0, 51, 102, 255);
+            // The selection shouldn't depend on telling colors apart.
+            if selected && accessibility.high_contrast {
+                text = format!("> {} <", text);
             }
 
-            let width = get_text_width(ctx, menu_items[i], fancy_font.clone(), 60.0);
-            let height = get_text_height(ctx, menu_items[i], fancy_font.clone(), 60.0);
+            let height = get_text_height(ctx, &mut self.render_cache, &text, fancy_font, scale);
+            rows.push((text, height, color));
+        }
+
+        let mut y = first_row_y(self.game_height, rows.iter().map(|(_, height, _)| *height));
+        for (text, height, color) in rows.iter() {
+            let width = get_text_width(ctx, &mut self.render_cache, text, fancy_font, scale);
 
             draw_text(
                 ctx,
-                menu_items[i],
+                &mut self.render_cache,
+                text,
+                Point2::new(self.game_width / 2.0 - width as f32 / 2.0, y),
+                fancy_font,
+                scale,
+                *color,
+            )?;
+
+            y += (height + 10) as f32;
+        }
+
+        // Why the match ended early takes the place of a tooltip.
+        let forfeit = match &self.forfeit {
+            Some(forfeit) if menu.page == MenuPage::GameOver => {
+                let name = self.player_name(forfeit.player);
+                Some(
+                    self.locale
+                        .format("forfeit", &[("name", name), ("reason", &forfeit.reason)]),
+                )
+            }
+            _ => None,
+        };
+        let tooltip = items
+            .get(menu.selected)
+            .and_then(|item| item.tooltip)
+            .map(|tooltip| self.locale.get(tooltip).to_string());
+
+        if let Some(tooltip) = forfeit.or(tooltip) {
+            let tooltip = tooltip.as_str();
+            let width = get_text_width(ctx, &mut self.render_cache, tooltip, fancy_font, 25.0);
+            let height = get_text_height(ctx, &mut self.render_cache, tooltip, fancy_font, 25.0);
+            draw_text(
+                ctx,
+                &mut self.render_cache,
+                tooltip,
                 Point2::new(
                     self.game_width / 2.0 - width as f32 / 2.0,
-                    self.game_height / 3.0 + ((height + 10) * i as u32) as f32,
+                    self.game_height - 20.0 - height as f32,
                 ),
-                fancy_font.clone(),
-                60.0,
+                fancy_font,
+                25.0,
+                colors.dim_text,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    // Log lines above the input line, newest at the bottom, over the top
+    // part of the arena.
+    pub fn draw_console(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let height = (self.game_height * 0.45).round();
+        let panel = graphics::Rect::new(0.0, 0.0, self.game_width, height);
+        self.draw_rect(ctx, panel, Color::new(0.0, 0.0, 0.0, 0.85))?;
+
+        let font = UiFont::builtin(&self.assets);
+        let line_height = 22.0;
+
+        let input = format!("> {}_", self.console.input);
+        let mut y = height - line_height - 6.0;
+        draw_text(
+            ctx,
+            &mut self.render_cache,
+            &input,
+            Point2::new(8.0, y),
+            font,
+            20.0,
+            graphics::WHITE,
+        )?;
+
+        for line in self.console.log.iter().rev() {
+            y -= line_height;
+            if y < 0.0 {
+                break;
+            }
+
+            let color = match line.is_error {
+                true => Color::from_rgba(255, 80, 80, 255),
+                false => Color::from_rgba(200, 200, 200, 255),
+            };
+            draw_text(
+                ctx,
+                &mut self.render_cache,
+                &line.text,
+                Point2::new(8.0, y),
+                font,
+                20.0,
                 color,
             )?;
         }
 
         Ok(())
     }
+
+    pub fn draw_stats(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let stats = self.arena.stats;
+        let values = [
+            ("stat-points", stats.points.to_string()),
+            ("stat-paddle-hits", stats.paddle_hits.to_string()),
+            ("stat-wall-bounces", stats.wall_bounces.to_string()),
+            ("stat-longest-rally", stats.longest_rally.to_string()),
+            (
+                "stat-average-rally",
+                format!("{:.1}", stats.average_rally()),
+            ),
+            ("stat-play-time", stats.play_time_text()),
+        ];
+
+        let label_width = values
+            .iter()
+            .map(|(key, _)| self.tr(key).chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut rows: Vec<String> = values
+            .iter()
+            .map(|(key, value)| {
+                format!("{:<width$} {:>6}", self.tr(key), value, width = label_width)
+            })
+            .collect();
+        rows.push(self.tr("menu-back").to_string());
+
+        let colors = self.ui_colors();
+        let theme = &self.themes[self.theme_index];
+        let fancy_font = UiFont::new(
+            &self.assets,
+            theme,
+            &self.locale,
+            self.config.accessibility.text_scale,
+        );
+
+        let title = self.locale.get("stats-title");
+        let width = get_text_width(ctx, &mut self.render_cache, title, fancy_font, 80.0);
+        draw_text(
+            ctx,
+            &mut self.render_cache,
+            title,
+            Point2::new(self.game_width / 2.0 - width as f32 / 2.0, 10.0),
+            fancy_font,
+            80.0,
+            colors.text,
+        )?;
+
+        let render_cache = &mut self.render_cache;
+        let heights: Vec<u32> = rows
+            .iter()
+            .map(|row| get_text_height(ctx, render_cache, row, fancy_font, 40.0))
+            .collect();
+
+        // Back is the only item, highlighted and set apart from the numbers
+        // by a gap, counted here as an extra 10 unit row.
+        let gap = std::iter::once(10);
+        let mut y = first_row_y(self.game_height, heights.iter().copied().chain(gap));
+        for (i, (row, height)) in rows.iter().zip(heights.iter()).enumerate() {
+            let is_back = i == rows.len() - 1;
+            if is_back {
+                y += 20.0;
+            }
+
+            let width = get_text_width(ctx, &mut self.render_cache, row, fancy_font, 40.0);
+            draw_text(
+                ctx,
+                &mut self.render_cache,
+                row,
+                Point2::new(self.game_width / 2.0 - width as f32 / 2.0, y),
+                fancy_font,
+                40.0,
+                if is_back {
+                    colors.highlight
+                } else {
+                    colors.dim_text
+                },
+            )?;
+
+            y += (height + 10) as f32;
+        }
+
+        Ok(())
+    }
+}
+
+// Rows start a third of the way down, or higher when large text would push
+// the last one into the tooltip at the bottom.
+fn first_row_y(game_height: f32, heights: impl Iterator<Item = u32>) -> f32 {
+    let total: f32 = heights.map(|height| (height + 10) as f32).sum();
+
+    (game_height / 3.0).min(game_height - 70.0 - total)
+}
+
+// Label and value columns, e.g. `Music  [#######---]`.
+fn item_text(locale: &Localizer, item: &MenuItem, mode: &MenuMode, label_width: usize) -> String {
+    let label = locale.get(item.label);
+
+    let value = match (&item.widget, mode) {
+        (Widget::Action(_), _) | (Widget::Submenu(_), _) | (Widget::Back, _) => {
+            return String::from(label);
+        }
+        (Widget::Toggle { on, .. }, _) => on_off_switch(locale, *on),
+        (Widget::Slider { level, muted, .. }, _) => volume_slider(locale, *level, *muted),
+        (
+            Widget::Choice {
+                options, selected, ..
+            },
+            _,
+        ) => format!(
+            "< {} >",
+            options.get(*selected).map(String::as_str).unwrap_or("")
+        ),
+        (Widget::KeyBinding { .. }, MenuMode::Binding) => {
+            format!("[ {} ]", locale.get("value-press-key"))
+        }
+        (Widget::KeyBinding { key, .. }, _) => {
+            format!("[ {} ]", key_name(*key).unwrap_or("?"))
+        }
+        (Widget::TextEntry { .. }, MenuMode::Editing(text)) => format!("{}_", text),
+        (Widget::TextEntry { text, .. }, _) => text.clone(),
+    };
+
+    format!("{:<width$} {}", label, value, width = label_width)
+}
+
+// Same width as `volume_slider` so the settings rows line up.
+fn on_off_switch(locale: &Localizer, on: bool) -> String {
+    let key = if on { "value-on" } else { "value-off" };
+    format!("[{:^10}]", locale.get(key))
+}
+
+// Ten segment text slider, e.g. `[#######---]`.
+fn volume_slider(locale: &Localizer, level: f32, muted: bool) -> String {
+    if muted {
+        return format!("[{:^10}]", locale.get("value-muted"));
+    }
+
+    let filled = (level * 10.0).round() as usize;
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(10 - filled))
 }
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use ggez::{
+    graphics::{self, Color, DrawMode, Font, Mesh, Scale, Text},
+    nalgebra::Point2,
+    Context, GameResult,
+};
+
+use crate::theme::{BallShape, PaddleShape};
+
+// Entries unused for this many frames are dropped, so changing text such as
+// the FPS counter doesn't grow the cache forever.
+const SWEEP_INTERVAL: u64 = 120;
+
+struct Cached<T> {
+    value: T,
+    last_used: u64,
+}
+
+// Text objects for one font and size, keyed by content.
+struct TextGroup {
+    font: Font,
+    scale: u32,
+    texts: HashMap<String, Cached<Text>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum MeshKey {
+    // Shape, RGBA color, width and height bits.
+    Paddle(PaddleShape, [u8; 4], u32, u32),
+    // Shape, RGBA color, radius bits.
+    Ball(BallShape, [u8; 4], u32),
+    // RGBA color, width and height bits.
+    Rect([u8; 4], u32, u32),
+}
+
+// Keeps `Text` objects and meshes alive between frames. Text measurement is
+// cached inside `Text`, and meshes are built at the origin and moved into
+// place with `DrawParam::dest` instead of being rebuilt every frame.
+pub struct RenderCache {
+    // When disabled everything is rebuilt every frame, for benchmarking.
+    pub enabled: bool,
+    frame: u64,
+    texts: Vec<TextGroup>,
+    meshes: HashMap<MeshKey, Cached<Mesh>>,
+}
+
+impl Default for RenderCache {
+    fn default() -> RenderCache {
+        RenderCache {
+            enabled: true,
+            frame: 0,
+            texts: Vec::new(),
+            meshes: HashMap::new(),
+        }
+    }
+}
+
+impl RenderCache {
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+
+        if !self.enabled {
+            self.texts.clear();
+            self.meshes.clear();
+            return;
+        }
+
+        if self.frame.is_multiple_of(SWEEP_INTERVAL) {
+            let frame = self.frame;
+            let fresh = |last_used: u64| frame - last_used < SWEEP_INTERVAL;
+
+            for group in self.texts.iter_mut() {
+                group.texts.retain(|_, text| fresh(text.last_used));
+            }
+            self.texts.retain(|group| !group.texts.is_empty());
+            self.meshes.retain(|_, mesh| fresh(mesh.last_used));
+        }
+    }
+
+    pub fn text(&mut self, content: &str, font: Font, scale: f32) -> &Text {
+        let scale_bits = scale.to_bits();
+
+        let index = match self
+            .texts
+            .iter()
+            .position(|group| group.font == font && group.scale == scale_bits)
+        {
+            Some(index) => index,
+            None => {
+                self.texts.push(TextGroup {
+                    font,
+                    scale: scale_bits,
+                    texts: HashMap::new(),
+                });
+                self.texts.len() - 1
+            }
+        };
+
+        let texts = &mut self.texts[index].texts;
+        if !texts.contains_key(content) {
+            let mut text = Text::new(content);
+            text.set_font(font, Scale::uniform(scale));
+
+            texts.insert(
+                content.to_string(),
+                Cached {
+                    value: text,
+                    last_used: self.frame,
+                },
+            );
+        }
+
+        let cached = texts.get_mut(content).unwrap();
+        cached.last_used = self.frame;
+
+        &cached.value
+    }
+
+    // Rectangle with its top left corner at the origin.
+    pub fn paddle_mesh(
+        &mut self,
+        ctx: &mut Context,
+        w: f32,
+        h: f32,
+        shape: PaddleShape,
+        color: Color,
+    ) -> GameResult<&Mesh> {
+        let key = MeshKey::Paddle(shape, rgba(color), w.to_bits(), h.to_bits());
+
+        self.mesh(ctx, key, |mb| {
+            let mode = match shape {
+                PaddleShape::Solid => DrawMode::fill(),
+                PaddleShape::Outline => DrawMode::stroke(2.0),
+            };
+
+            mb.rectangle(mode, graphics::Rect::new(0.0, 0.0, w, h), color);
+        })
+    }
+
+    // Filled rectangle with its top left corner at the origin.
+    pub fn rect_mesh(
+        &mut self,
+        ctx: &mut Context,
+        w: f32,
+        h: f32,
+        color: Color,
+    ) -> GameResult<&Mesh> {
+        let key = MeshKey::Rect(rgba(color), w.to_bits(), h.to_bits());
+
+        self.mesh(ctx, key, |mb| {
+            mb.rectangle(DrawMode::fill(), graphics::Rect::new(0.0, 0.0, w, h), color);
+        })
+    }
+
+    // Ball centered on the origin.
+    pub fn ball_mesh(
+        &mut self,
+        ctx: &mut Context,
+        r: f32,
+        shape: BallShape,
+        color: Color,
+    ) -> GameResult<&Mesh> {
+        let key = MeshKey::Ball(shape, rgba(color), r.to_bits());
+
+        self.mesh(ctx, key, |mb| match shape {
+            BallShape::Circle => {
+                mb.circle(DrawMode::fill(), Point2::new(0.0, 0.0), r, 0.01, color);
+            }
+            BallShape::Square => {
+                let rect = graphics::Rect::new(-r, -r, r * 2.0, r * 2.0);
+                mb.rectangle(DrawMode::fill(), rect, color);
+            }
+        })
+    }
+
+    fn mesh<F>(&mut self, ctx: &mut Context, key: MeshKey, build: F) -> GameResult<&Mesh>
+    where
+        F: FnOnce(&mut graphics::MeshBuilder),
+    {
+        if !self.meshes.contains_key(&key) {
+            let mut mb = graphics::MeshBuilder::new();
+            build(&mut mb);
+
+            self.meshes.insert(
+                key,
+                Cached {
+                    value: mb.build(ctx)?,
+                    last_used: self.frame,
+                },
+            );
+        }
+
+        let cached = self.meshes.get_mut(&key).unwrap();
+        cached.last_used = self.frame;
+
+        Ok(&cached.value)
+    }
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    let (r, g, b, a) = color.to_rgba();
+    [r, g, b, a]
+}
@@ -0,0 +1,374 @@
+use ggez::{event, input::keyboard::KeyCode, Context, GameResult};
+
+use crate::game_state::GameState;
+
+// Length of each half of a fade, to black and back.
+const FADE_SECONDS: f32 = 0.25;
+
+pub enum Transition {
+    None,
+    Push(Box<dyn Scene>),
+    Pop,
+    Replace(Box<dyn Scene>),
+    // Replaces the whole stack.
+    Reset(Box<dyn Scene>),
+    // Fades to black, applies the inner transition, then fades back in.
+    Fade(Box<Transition>),
+    Quit,
+}
+
+impl Transition {
+    pub fn faded(self) -> Transition {
+        Transition::Fade(Box::new(self))
+    }
+}
+
+// One screen of the game. Scenes borrow the whole `GameState` and ask for
+// stack changes by returning a `Transition`.
+pub trait Scene {
+    fn name(&self) -> &'static str;
+
+    // Called when the scene is pushed, or replaces another one.
+    fn enter(&mut self, _game: &mut GameState) {}
+
+    fn update(&mut self, _game: &mut GameState, _ctx: &mut Context) -> Transition {
+        Transition::None
+    }
+
+    // `covered` is true when an overlay is drawn on top, the scene should
+    // look frozen.
+    fn draw(&mut self, game: &mut GameState, ctx: &mut Context, covered: bool) -> GameResult;
+
+    fn key_down(
+        &mut self,
+        _game: &mut GameState,
+        _ctx: &mut Context,
+        _keycode: KeyCode,
+    ) -> Transition {
+        Transition::None
+    }
+
+    fn key_up(&mut self, _game: &mut GameState, _ctx: &mut Context, _keycode: KeyCode) {}
+
+    fn text_input(&mut self, _game: &mut GameState, _character: char) {}
+
+    // Overlays are drawn on top of the closest opaque scene below them.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    // True while the ball is in play.
+    fn is_gameplay(&self) -> bool {
+        false
+    }
+}
+
+struct Fade {
+    elapsed: f32,
+    // Applied once the screen is black.
+    pending: Option<Transition>,
+}
+
+impl Fade {
+    // 0.0 is fully visible, 1.0 is black.
+    fn alpha(&self) -> f32 {
+        let alpha = if self.elapsed < FADE_SECONDS {
+            self.elapsed / FADE_SECONDS
+        } else {
+            2.0 - self.elapsed / FADE_SECONDS
+        };
+
+        alpha.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Default)]
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    fade: Option<Fade>,
+}
+
+impl SceneStack {
+    pub fn new(scene: Box<dyn Scene>) -> SceneStack {
+        SceneStack {
+            scenes: vec![scene],
+            fade: None,
+        }
+    }
+
+    pub fn top_name(&self) -> Option<&'static str> {
+        self.scenes.last().map(|scene| scene.name())
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.scenes.last().is_some_and(|scene| scene.is_gameplay())
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    // Scenes are frozen while a fade runs.
+    pub fn update(&mut self, game: &mut GameState, ctx: &mut Context, delta: f32) {
+        if let Some(fade) = self.fade.as_mut() {
+            fade.elapsed += delta;
+
+            let pending = if fade.elapsed >= FADE_SECONDS {
+                fade.pending.take()
+            } else {
+                None
+            };
+            if fade.elapsed >= FADE_SECONDS * 2.0 {
+                self.fade = None;
+            }
+
+            if let Some(transition) = pending {
+                self.apply(game, ctx, transition);
+            }
+            return;
+        }
+
+        let transition = match self.scenes.last_mut() {
+            Some(scene) => scene.update(game, ctx),
+            None => Transition::None,
+        };
+        self.apply(game, ctx, transition);
+    }
+
+    pub fn draw(&mut self, game: &mut GameState, ctx: &mut Context) -> GameResult {
+        let visible = self.visible();
+        let top = visible.last().copied();
+
+        for i in visible {
+            self.scenes[i].draw(game, ctx, Some(i) != top)?;
+        }
+
+        if let Some(fade) = &self.fade {
+            game.draw_fade(ctx, fade.alpha())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn key_down(&mut self, game: &mut GameState, ctx: &mut Context, keycode: KeyCode) {
+        if self.fade.is_some() {
+            return;
+        }
+
+        let transition = match self.scenes.last_mut() {
+            Some(scene) => scene.key_down(game, ctx, keycode),
+            None => Transition::None,
+        };
+        self.apply(game, ctx, transition);
+    }
+
+    pub fn key_up(&mut self, game: &mut GameState, ctx: &mut Context, keycode: KeyCode) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.key_up(game, ctx, keycode);
+        }
+    }
+
+    pub fn text_input(&mut self, game: &mut GameState, character: char) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.text_input(game, character);
+        }
+    }
+
+    pub fn apply(&mut self, game: &mut GameState, ctx: &mut Context, transition: Transition) {
+        let was_playing = self.is_playing();
+
+        match transition {
+            Transition::None => return,
+            // Fading to black and back counts as flashing.
+            Transition::Fade(inner) if game.config.accessibility.reduced_motion => {
+                return self.apply(game, ctx, *inner);
+            }
+            Transition::Fade(inner) => {
+                // A fade already running wins, so mashing keys can't queue several.
+                if self.fade.is_none() {
+                    self.fade = Some(Fade {
+                        elapsed: 0.0,
+                        pending: Some(*inner),
+                    });
+                }
+                return;
+            }
+            Transition::Quit => event::quit(ctx),
+            transition => {
+                let entered = !matches!(transition, Transition::Pop);
+                self.change(transition);
+
+                if entered {
+                    if let Some(scene) = self.scenes.last_mut() {
+                        scene.enter(game);
+                    }
+                }
+            }
+        }
+
+        if was_playing != self.is_playing() {
+            game.play_state_changed();
+        }
+    }
+
+    // The stack never becomes empty, popping the last scene does nothing.
+    fn change(&mut self, transition: Transition) {
+        match transition {
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Pop => {
+                if self.scenes.len() > 1 {
+                    self.scenes.pop();
+                }
+            }
+            Transition::Replace(scene) => {
+                self.scenes.pop();
+                self.scenes.push(scene);
+            }
+            Transition::Reset(scene) => {
+                self.scenes.clear();
+                self.scenes.push(scene);
+            }
+            Transition::None | Transition::Fade(_) | Transition::Quit => (),
+        }
+    }
+
+    // Indices of the scenes to draw, bottom first: the top scene and, when
+    // it is an overlay, the closest opaque scene below it.
+    fn visible(&self) -> Vec<usize> {
+        let top = match self.scenes.len() {
+            0 => return Vec::new(),
+            len => len - 1,
+        };
+
+        if !self.scenes[top].is_overlay() {
+            return vec![top];
+        }
+
+        match self.scenes[..top]
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+        {
+            Some(base) => vec![base, top],
+            None => vec![top],
+        }
+    }
+}
+
+impl GameState {
+    // The stack is moved out while it runs so scenes can borrow the whole game.
+    pub fn with_scenes<T, F>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut SceneStack, &mut GameState) -> T,
+    {
+        let mut scenes = std::mem::take(&mut self.scenes);
+        let result = f(&mut scenes, self);
+        self.scenes = scenes;
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestScene {
+        name: &'static str,
+        overlay: bool,
+    }
+
+    impl Scene for TestScene {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn draw(
+            &mut self,
+            _game: &mut GameState,
+            _ctx: &mut Context,
+            _covered: bool,
+        ) -> GameResult {
+            Ok(())
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+    }
+
+    fn scene(name: &'static str) -> Box<dyn Scene> {
+        Box::new(TestScene {
+            name,
+            overlay: false,
+        })
+    }
+
+    fn overlay(name: &'static str) -> Box<dyn Scene> {
+        Box::new(TestScene {
+            name,
+            overlay: true,
+        })
+    }
+
+    fn names(stack: &SceneStack) -> Vec<&'static str> {
+        stack.scenes.iter().map(|scene| scene.name()).collect()
+    }
+
+    #[test]
+    fn push_pop_replace_and_reset() {
+        let mut stack = SceneStack::new(scene("title"));
+
+        stack.change(Transition::Replace(scene("game")));
+        stack.change(Transition::Push(overlay("pause")));
+        stack.change(Transition::Push(overlay("settings")));
+        assert_eq!(names(&stack), vec!["game", "pause", "settings"]);
+
+        stack.change(Transition::Pop);
+        assert_eq!(stack.top_name(), Some("pause"));
+
+        stack.change(Transition::Reset(scene("title")));
+        assert_eq!(names(&stack), vec!["title"]);
+    }
+
+    #[test]
+    fn the_last_scene_is_never_popped() {
+        let mut stack = SceneStack::new(scene("title"));
+        stack.change(Transition::Pop);
+
+        assert_eq!(names(&stack), vec!["title"]);
+    }
+
+    #[test]
+    fn overlays_draw_over_the_closest_opaque_scene() {
+        let mut stack = SceneStack::new(scene("game"));
+        assert_eq!(stack.visible(), vec![0]);
+
+        stack.change(Transition::Push(overlay("pause")));
+        assert_eq!(stack.visible(), vec![0, 1]);
+
+        // The pause menu is hidden behind the settings overlay.
+        stack.change(Transition::Push(overlay("settings")));
+        assert_eq!(stack.visible(), vec![0, 2]);
+
+        stack.change(Transition::Push(scene("stats")));
+        assert_eq!(stack.visible(), vec![3]);
+    }
+
+    #[test]
+    fn fades_go_to_black_and_back() {
+        let mut fade = Fade {
+            elapsed: 0.0,
+            pending: None,
+        };
+        assert_eq!(fade.alpha(), 0.0);
+
+        fade.elapsed = FADE_SECONDS;
+        assert_eq!(fade.alpha(), 1.0);
+
+        fade.elapsed = FADE_SECONDS * 1.5;
+        assert_eq!(fade.alpha(), 0.5);
+
+        fade.elapsed = FADE_SECONDS * 3.0;
+        assert_eq!(fade.alpha(), 0.0);
+    }
+}
@@ -0,0 +1,201 @@
+use ggez::{input::keyboard::KeyCode, timer, Context, GameResult};
+
+use crate::{
+    game_state::GameState,
+    menu::{Menu, MenuPage},
+    scene::{Scene, Transition},
+};
+
+// The match itself.
+pub struct GameScene {
+    // Resets scores and stats when the scene is entered.
+    new_match: bool,
+}
+
+impl GameScene {
+    pub fn new_match() -> GameScene {
+        GameScene { new_match: true }
+    }
+
+    // Keeps the scores and stats, for matches set up by a script.
+    pub fn continue_match() -> GameScene {
+        GameScene { new_match: false }
+    }
+}
+
+impl Scene for GameScene {
+    fn name(&self) -> &'static str {
+        "game"
+    }
+
+    fn enter(&mut self, game: &mut GameState) {
+        if self.new_match {
+            game.arena.reset(true);
+            self.new_match = false;
+        }
+
+        game.start_bots();
+    }
+
+    fn update(&mut self, game: &mut GameState, ctx: &mut Context) -> Transition {
+        let frame_time = timer::delta(ctx).as_secs_f64();
+        let game_time = game.debug.scaled_time(frame_time, game.dt);
+        game.advance(game_time);
+
+        if game.match_winner().is_some() {
+            game.stop_bots();
+            return Transition::Push(Box::new(MenuScene::new(MenuPage::GameOver))).faded();
+        }
+
+        Transition::None
+    }
+
+    fn draw(&mut self, game: &mut GameState, ctx: &mut Context, covered: bool) -> GameResult {
+        game.draw_game(ctx, covered)
+    }
+
+    fn key_down(
+        &mut self,
+        game: &mut GameState,
+        _ctx: &mut Context,
+        keycode: KeyCode,
+    ) -> Transition {
+        game.game_key_down(keycode)
+    }
+
+    fn key_up(&mut self, game: &mut GameState, _ctx: &mut Context, keycode: KeyCode) {
+        game.game_key_up(keycode);
+    }
+
+    fn is_gameplay(&self) -> bool {
+        true
+    }
+}
+
+// Every menu page, see `menus.rs`. Everything but the
+// title screen is drawn over the frozen game.
+pub struct MenuScene {
+    menu: Menu,
+}
+
+impl MenuScene {
+    pub fn new(page: MenuPage) -> MenuScene {
+        MenuScene {
+            menu: Menu::new(page),
+        }
+    }
+}
+
+impl Scene for MenuScene {
+    fn name(&self) -> &'static str {
+        match self.menu.page {
+            MenuPage::Title => "title",
+            MenuPage::Pause => "pause",
+            MenuPage::Settings => "settings",
+            MenuPage::Audio => "audio",
+            MenuPage::Display => "display",
+            MenuPage::Controls => "controls",
+            MenuPage::Accessibility => "accessibility",
+            MenuPage::GameOver => "game_over",
+        }
+    }
+
+    fn draw(&mut self, game: &mut GameState, ctx: &mut Context, _covered: bool) -> GameResult {
+        game.draw_menu(ctx, &self.menu)
+    }
+
+    fn key_down(
+        &mut self,
+        game: &mut GameState,
+        ctx: &mut Context,
+        keycode: KeyCode,
+    ) -> Transition {
+        self.menu.key_down(game, ctx, keycode)
+    }
+
+    fn text_input(&mut self, game: &mut GameState, character: char) {
+        self.menu.text_input(game, character);
+    }
+
+    fn is_overlay(&self) -> bool {
+        self.menu.page != MenuPage::Title
+    }
+}
+
+// The drop-down developer console, see `console.rs`. The game is frozen
+// while it is open.
+pub struct ConsoleScene;
+
+impl Scene for ConsoleScene {
+    fn name(&self) -> &'static str {
+        "console"
+    }
+
+    fn draw(&mut self, game: &mut GameState, ctx: &mut Context, _covered: bool) -> GameResult {
+        game.draw_console(ctx)
+    }
+
+    fn key_down(
+        &mut self,
+        game: &mut GameState,
+        _ctx: &mut Context,
+        keycode: KeyCode,
+    ) -> Transition {
+        let console = &mut game.console;
+
+        match keycode {
+            KeyCode::Grave | KeyCode::Escape => return Transition::Pop,
+            KeyCode::Return => game.submit_console_line(),
+            KeyCode::Back => console.backspace(),
+            KeyCode::Tab => console.complete(),
+            KeyCode::Up => console.history_back(),
+            KeyCode::Down => console.history_forward(),
+            _ => (),
+        }
+
+        Transition::None
+    }
+
+    fn text_input(&mut self, game: &mut GameState, character: char) {
+        // The key that opens the console types a backtick too.
+        if character != '`' {
+            game.console.type_char(character);
+        }
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+}
+
+pub struct StatsScene;
+
+impl Scene for StatsScene {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    fn draw(&mut self, game: &mut GameState, ctx: &mut Context, _covered: bool) -> GameResult {
+        game.draw_stats(ctx)
+    }
+
+    fn key_down(
+        &mut self,
+        _game: &mut GameState,
+        _ctx: &mut Context,
+        keycode: KeyCode,
+    ) -> Transition {
+        match keycode {
+            KeyCode::Return | KeyCode::Escape => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+}
+
+impl GameState {
+    // Called when play starts or stops, e.g. when the pause menu opens.
+    pub fn play_state_changed(&mut self) {
+        self.stop_particles();
+        self.music.crossfade(&mut self.mixer);
+    }
+}
@@ -0,0 +1,506 @@
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT, INT};
+
+use crate::{
+    arena::{Arena, ArenaEvent},
+    config::RESOURCE_DIR,
+    env::Action,
+    game_state::GameState,
+    toast::Toast,
+};
+
+// Rhai scripts are the `.rhai` files directly in this resource directory.
+pub const SCRIPT_DIR: &str = "/scripts";
+
+// Work one hook call may do before it is stopped, well under a frame.
+pub const MAX_OPERATIONS: u64 = 100_000;
+
+// Functions a script can define, see "Scripting" in the README.
+pub const HOOKS: [&str; 4] = ["control_paddle", "on_tick", "on_paddle_hit", "on_goal"];
+
+// Fastest a script can make the ball, so it can't pass through paddles.
+const MAX_BALL_SPEED: f32 = 3000.0;
+
+// A change a script asked for, applied once its hook returns.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScriptCommand {
+    SetBallVelocity(f32, f32),
+    // Side 0 (left) or 1 (right).
+    AddPoint(usize),
+    Serve,
+}
+
+pub struct Script {
+    pub name: String,
+    ast: AST,
+    // `this` in every hook, kept until the script is reloaded.
+    memory: Dynamic,
+    // Why the script was turned off, it stays off until it is reloaded.
+    pub error: Option<String>,
+}
+
+impl Script {
+    // The hooks the script defines.
+    pub fn hooks(&self) -> Vec<&'static str> {
+        HOOKS
+            .iter()
+            .copied()
+            .filter(|hook| self.ast.iter_functions().any(|f| f.name == *hook))
+            .collect()
+    }
+
+    fn has(&self, hook: &str) -> bool {
+        self.error.is_none() && self.ast.iter_functions().any(|f| f.name == hook)
+    }
+}
+
+// User scripts that can steer paddles and change the rules while a match
+// runs. They only see a copy of the arena and change it through the
+// functions registered in `new`, and each call is limited to
+// `MAX_OPERATIONS`, so a broken script can't hang or crash the game.
+pub struct ScriptEngine {
+    engine: Engine,
+    pub scripts: Vec<Script>,
+    commands: Rc<RefCell<Vec<ScriptCommand>>>,
+    // What scripts printed, for the console.
+    output: Rc<RefCell<Vec<String>>>,
+    errors: Vec<String>,
+}
+
+impl Default for ScriptEngine {
+    fn default() -> ScriptEngine {
+        ScriptEngine::new()
+    }
+}
+
+impl ScriptEngine {
+    pub fn new() -> ScriptEngine {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(4096)
+            .set_max_array_size(4096)
+            .set_max_map_size(256);
+
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let output = Rc::new(RefCell::new(Vec::new()));
+
+        let queue = commands.clone();
+        engine.register_fn("set_ball_velocity", move |dx: FLOAT, dy: FLOAT| {
+            queue
+                .borrow_mut()
+                .push(ScriptCommand::SetBallVelocity(dx as f32, dy as f32));
+        });
+
+        let queue = commands.clone();
+        engine.register_fn(
+            "add_point",
+            move |side: INT| -> Result<(), Box<EvalAltResult>> {
+                match side {
+                    0 | 1 => {
+                        queue
+                            .borrow_mut()
+                            .push(ScriptCommand::AddPoint(side as usize));
+                        Ok(())
+                    }
+                    _ => Err(format!("add_point: side {} is not 0 or 1", side).into()),
+                }
+            },
+        );
+
+        let queue = commands.clone();
+        engine.register_fn("serve", move || {
+            queue.borrow_mut().push(ScriptCommand::Serve);
+        });
+
+        let printed = output.clone();
+        engine.on_print(move |text| printed.borrow_mut().push(text.to_string()));
+        let printed = output.clone();
+        engine.on_debug(move |text, _, _| printed.borrow_mut().push(text.to_string()));
+
+        ScriptEngine {
+            engine,
+            scripts: Vec::new(),
+            commands,
+            output,
+            errors: Vec::new(),
+        }
+    }
+
+    // Compiles `source` as the script called `name`, replacing the loaded
+    // one. If it doesn't compile, the previous version keeps running.
+    pub fn load(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|e| format!("{}: {}", name, e))?;
+
+        let script = Script {
+            name: name.to_string(),
+            ast,
+            memory: Dynamic::from_map(Map::new()),
+            error: None,
+        };
+
+        match self.scripts.iter_mut().find(|s| s.name == name) {
+            Some(loaded) => *loaded = script,
+            None => {
+                self.scripts.push(script);
+                self.scripts.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Loads every `.rhai` file directly in `dir` and drops scripts whose
+    // file is gone.
+    pub fn load_dir(&mut self, dir: &Path) {
+        let mut paths: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+
+        let names: Vec<String> = paths
+            .iter()
+            .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+            .collect();
+        self.scripts.retain(|script| names.contains(&script.name));
+
+        for (path, name) in paths.iter().zip(names.iter()) {
+            let result = fs::read_to_string(path)
+                .map_err(|e| format!("{}: {}", name, e))
+                .and_then(|source| self.load(name, &source));
+
+            if let Err(e) = result {
+                self.errors.push(e);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    // Lets scripts steer the paddles and runs `on_tick`, before a step of
+    // `time` seconds.
+    pub fn before_step(&mut self, arena: &mut Arena, time: f64) {
+        if self.is_empty() {
+            return;
+        }
+
+        for side in 0..2 {
+            let args = vec![state(arena), Dynamic::from(side as INT)];
+
+            for (index, value) in self.call("control_paddle", args, arena) {
+                let action = match value.clone().into_string() {
+                    Ok(name) => Action::from_name(&name.to_lowercase()),
+                    Err(_) if value.is_unit() => continue,
+                    Err(_) => None,
+                };
+
+                match action {
+                    Some(action) => {
+                        let paddle = &mut arena.paddles[side];
+                        paddle.is_up_holding = action == Action::Up;
+                        paddle.is_down_holding = action == Action::Down;
+                    }
+                    None => self.fail(
+                        index,
+                        format!(
+                            "control_paddle returned {}, not \"up\", \"down\", \"stay\" or ()",
+                            value
+                        ),
+                    ),
+                }
+            }
+        }
+
+        let args = vec![state(arena), Dynamic::from(time as FLOAT)];
+        self.call("on_tick", args, arena);
+    }
+
+    // Runs the hook for something that happened during a step.
+    pub fn on_event(&mut self, arena: &mut Arena, event: ArenaEvent) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (hook, side) = match event {
+            ArenaEvent::PaddleHit { paddle } => ("on_paddle_hit", paddle),
+            ArenaEvent::Goal { scorer } => ("on_goal", scorer - 1),
+            ArenaEvent::WallBounce => return,
+        };
+
+        let args = vec![state(arena), Dynamic::from(side as INT)];
+        self.call(hook, args, arena);
+    }
+
+    // Errors since the last call, one line each.
+    pub fn take_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.errors)
+    }
+
+    // What scripts printed since the last call.
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut *self.output.borrow_mut())
+    }
+
+    // Calls `hook` in every script that has it and applies what each one
+    // asked for. Returns each script's index and return value.
+    fn call(&mut self, hook: &str, args: Vec<Dynamic>, arena: &mut Arena) -> Vec<(usize, Dynamic)> {
+        let mut results = Vec::new();
+
+        for index in 0..self.scripts.len() {
+            let script = &mut self.scripts[index];
+            if !script.has(hook) {
+                continue;
+            }
+
+            let options = CallFnOptions::new()
+                .eval_ast(false)
+                .bind_this_ptr(&mut script.memory);
+            let result = self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut Scope::new(),
+                &script.ast,
+                hook,
+                args.clone(),
+            );
+
+            let commands: Vec<ScriptCommand> = self.commands.borrow_mut().drain(..).collect();
+            match result {
+                Ok(value) => {
+                    for command in commands {
+                        apply(arena, command);
+                    }
+                    results.push((index, value));
+                }
+                Err(e) => self.fail(index, format!("{}: {}", hook, e)),
+            }
+        }
+
+        results
+    }
+
+    fn fail(&mut self, index: usize, error: String) {
+        let script = &mut self.scripts[index];
+
+        self.errors
+            .push(format!("{} turned off, {}", script.name, error));
+        script.error = Some(error);
+    }
+}
+
+// What scripts see of the arena. It is a copy, so changing it does nothing.
+pub fn state(arena: &Arena) -> Dynamic {
+    let float = |value: f32| Dynamic::from(value as FLOAT);
+    let int = |value: usize| Dynamic::from(value as INT);
+
+    let mut ball = Map::new();
+    ball.insert("x".into(), float(arena.ball.x));
+    ball.insert("y".into(), float(arena.ball.y));
+    ball.insert("dx".into(), float(arena.ball.dx));
+    ball.insert("dy".into(), float(arena.ball.dy));
+    ball.insert("radius".into(), float(arena.ball.radius));
+
+    let paddles: Vec<Dynamic> = arena
+        .paddles
+        .iter()
+        .map(|paddle| {
+            let rect = paddle.rect;
+            let mut map = Map::new();
+            map.insert("x".into(), float(rect.x));
+            map.insert("y".into(), float(rect.y + rect.h / 2.0));
+            map.insert("width".into(), float(rect.w));
+            map.insert("height".into(), float(rect.h));
+            map.insert("speed".into(), float(paddle.dy));
+            Dynamic::from_map(map)
+        })
+        .collect();
+
+    let mut state = Map::new();
+    state.insert("width".into(), float(arena.width));
+    state.insert("height".into(), float(arena.height));
+    state.insert("time".into(), Dynamic::from(arena.stats.play_time as FLOAT));
+    state.insert("serving".into(), Dynamic::from(arena.paused.is_some()));
+    state.insert("rally".into(), int(arena.rally as usize));
+    state.insert(
+        "scores".into(),
+        Dynamic::from_array(vec![int(arena.player1_score), int(arena.player2_score)]),
+    );
+    state.insert("winning_score".into(), int(arena.tuning.winning_score));
+    state.insert("ball".into(), Dynamic::from_map(ball));
+    state.insert("paddles".into(), Dynamic::from_array(paddles));
+
+    Dynamic::from_map(state)
+}
+
+pub fn apply(arena: &mut Arena, command: ScriptCommand) {
+    match command {
+        ScriptCommand::SetBallVelocity(dx, dy) => {
+            let speed = dx.hypot(dy);
+            if !speed.is_finite() {
+                return;
+            }
+
+            let scale = (MAX_BALL_SPEED / speed).min(1.0);
+            arena.ball.dx = dx * scale;
+            arena.ball.dy = dy * scale;
+        }
+        ScriptCommand::AddPoint(0) => arena.player1_score += 1,
+        ScriptCommand::AddPoint(_) => arena.player2_score += 1,
+        ScriptCommand::Serve => arena.reset(false),
+    }
+}
+
+impl GameState {
+    // Loads the scripts again after one changed, see `hot_reload.rs`.
+    pub fn reload_scripts(&mut self) {
+        self.scripts
+            .load_dir(Path::new(&format!("{}{}", RESOURCE_DIR, SCRIPT_DIR)));
+        self.report_scripts();
+    }
+
+    // Script errors go to the debug overlay and the console, prints to the
+    // console.
+    pub fn report_scripts(&mut self) {
+        for error in self.scripts.take_errors() {
+            self.console.print(&error, true);
+            self.toasts.push(Toast::error(error));
+        }
+
+        for line in self.scripts.take_output() {
+            self.console.print(&line, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Tuning;
+
+    fn setup(source: &str) -> (ScriptEngine, Arena) {
+        let mut scripts = ScriptEngine::new();
+        scripts.load("test.rhai", source).unwrap();
+
+        let mut arena = Arena::seeded(1280.0, 720.0, Tuning::default(), 1);
+        arena.paused = None;
+
+        (scripts, arena)
+    }
+
+    #[test]
+    fn scripts_steer_paddles() {
+        let (mut scripts, mut arena) = setup(
+            r#"
+            fn control_paddle(state, side) {
+                if side == 1 {
+                    if state.ball.y < state.paddles[side].y { "up" } else { "down" }
+                }
+            }
+            "#,
+        );
+        arena.ball.y = 0.0;
+
+        scripts.before_step(&mut arena, 1.0 / 60.0);
+
+        assert!(!arena.paddles[0].is_up_holding && !arena.paddles[0].is_down_holding);
+        assert!(arena.paddles[1].is_up_holding);
+        assert!(scripts.take_errors().is_empty());
+        assert_eq!(scripts.scripts[0].hooks(), vec!["control_paddle"]);
+    }
+
+    #[test]
+    fn hooks_change_the_arena_through_commands() {
+        let (mut scripts, mut arena) = setup(
+            r#"
+            fn on_paddle_hit(state, side) {
+                set_ball_velocity(state.ball.dx * 2.0, 1.0e9);
+                state.ball.x = -1.0;
+            }
+
+            fn on_goal(state, side) {
+                this.goals = (this.goals ?? 0) + 1;
+                add_point(side);
+                print(`goal ${this.goals}`);
+            }
+            "#,
+        );
+        arena.ball.dx = 300.0;
+
+        let x = arena.ball.x;
+        scripts.on_event(&mut arena, ArenaEvent::PaddleHit { paddle: 0 });
+        assert_eq!(arena.ball.x, x);
+        assert!(arena.ball.dx > 0.0);
+        assert!((arena.ball.dx.hypot(arena.ball.dy) - MAX_BALL_SPEED).abs() < 1.0);
+
+        scripts.on_event(&mut arena, ArenaEvent::Goal { scorer: 2 });
+        scripts.on_event(&mut arena, ArenaEvent::Goal { scorer: 2 });
+        assert_eq!((arena.player1_score, arena.player2_score), (0, 2));
+        assert_eq!(scripts.take_output(), vec!["goal 1", "goal 2"]);
+    }
+
+    #[test]
+    fn broken_scripts_are_turned_off() {
+        let (mut scripts, mut arena) = setup("fn on_tick(state, dt) { loop {} }");
+        scripts
+            .load("bad_return.rhai", "fn control_paddle(state, side) { 42 }")
+            .unwrap();
+        scripts
+            .load("bad_point.rhai", "fn on_tick(state, dt) { add_point(5); }")
+            .unwrap();
+
+        scripts.before_step(&mut arena, 1.0 / 60.0);
+        let errors = scripts.take_errors();
+
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(scripts.scripts.iter().all(|script| script.error.is_some()));
+        assert_eq!((arena.player1_score, arena.player2_score), (0, 0));
+
+        scripts.before_step(&mut arena, 1.0 / 60.0);
+        assert!(scripts.take_errors().is_empty());
+    }
+
+    #[test]
+    fn examples_play_a_match() {
+        let mut scripts = ScriptEngine::new();
+        scripts.load_dir(Path::new("resources/scripts/examples"));
+        assert!(scripts.take_errors().is_empty());
+        assert_eq!(scripts.scripts.len(), 2);
+
+        let mut arena = Arena::seeded(1280.0, 720.0, Tuning::default(), 3);
+        while arena.winner().is_none() {
+            scripts.before_step(&mut arena, 1.0 / 60.0);
+            arena.simulate(1.0 / 60.0);
+            for event in std::mem::take(&mut arena.events) {
+                scripts.on_event(&mut arena, event);
+            }
+        }
+
+        assert!(scripts.take_errors().is_empty());
+        assert!(arena.stats.paddle_hits > 0);
+        assert_eq!(arena.winner(), Some(2));
+    }
+
+    #[test]
+    fn scripts_that_dont_compile_keep_the_previous_version() {
+        let (mut scripts, mut arena) = setup("fn on_tick(state, dt) { serve(); }");
+
+        assert!(scripts
+            .load("test.rhai", "fn on_tick(state, dt) {")
+            .is_err());
+        scripts.before_step(&mut arena, 1.0 / 60.0);
+
+        assert!(arena.paused.is_some());
+    }
+}
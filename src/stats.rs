@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+// Numbers shown on the stats screen after a match.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MatchStats {
+    pub points: u32,
+    pub paddle_hits: u32,
+    pub wall_bounces: u32,
+    pub longest_rally: u32,
+    // Seconds the ball was in play.
+    pub play_time: f64,
+}
+
+impl MatchStats {
+    pub fn average_rally(&self) -> f32 {
+        if self.points == 0 {
+            0.0
+        } else {
+            self.paddle_hits as f32 / self.points as f32
+        }
+    }
+
+    // `m:ss`
+    pub fn play_time_text(&self) -> String {
+        let seconds = self.play_time as u64;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_rally_is_hits_per_point() {
+        let mut stats = MatchStats::default();
+        assert_eq!(stats.average_rally(), 0.0);
+
+        stats.points = 4;
+        stats.paddle_hits = 10;
+        assert_eq!(stats.average_rally(), 2.5);
+    }
+
+    #[test]
+    fn play_time_is_minutes_and_seconds() {
+        let stats = MatchStats {
+            play_time: 125.7,
+            ..MatchStats::default()
+        };
+
+        assert_eq!(stats.play_time_text(), "2:05");
+    }
+}
@@ -0,0 +1,306 @@
+use serde::{Deserialize, Serialize};
+
+use crate::assets::wav_bytes;
+
+pub const SAMPLE_RATE: u32 = 22050;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Noise,
+}
+
+// Where an event's sound comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SoundSource {
+    #[default]
+    File,
+    Synth,
+}
+
+// Per event sound settings from the `[sounds.<id>]` config tables. Without a
+// `synth` table the built-in preset for the event is used.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub source: SoundSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synth: Option<SynthParams>,
+}
+
+impl SoundSettings {
+    pub fn synth_params(&self, id: &str) -> SynthParams {
+        self.synth.unwrap_or_else(|| SynthParams::preset(id))
+    }
+}
+
+// A single retro beep. Times are in seconds, `sustain` is a level from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SynthParams {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    // Slides linearly from `frequency` to this over the whole sound.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_frequency: Option<f32>,
+    pub duration: f32,
+    pub volume: f32,
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for SynthParams {
+    fn default() -> SynthParams {
+        SynthParams {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            end_frequency: None,
+            duration: 0.08,
+            volume: 0.3,
+            attack: 0.002,
+            decay: 0.02,
+            sustain: 0.6,
+            release: 0.03,
+        }
+    }
+}
+
+impl SynthParams {
+    // Built-in sound for each game event, also used when a sound file is missing.
+    pub fn preset(id: &str) -> SynthParams {
+        match id {
+            "goal" => SynthParams {
+                frequency: 330.0,
+                end_frequency: Some(110.0),
+                duration: 0.4,
+                release: 0.15,
+                ..SynthParams::default()
+            },
+            "wall" => SynthParams {
+                waveform: Waveform::Triangle,
+                frequency: 660.0,
+                duration: 0.06,
+                volume: 0.4,
+                ..SynthParams::default()
+            },
+            _ => SynthParams::default(),
+        }
+    }
+
+    pub fn sample_count(&self, sample_rate: u32) -> usize {
+        (self.duration.max(0.0) * sample_rate as f32) as usize
+    }
+
+    // Volume envelope at time `t`, the release ends exactly at `duration`.
+    pub fn envelope(&self, t: f32) -> f32 {
+        let level = if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+        } else {
+            self.sustain
+        };
+
+        let remaining = self.duration - t;
+        if remaining < self.release {
+            level * (remaining / self.release).max(0.0)
+        } else {
+            level
+        }
+    }
+}
+
+// Renders the sound as mono samples in the -1.0..=1.0 range. Noise uses a
+// fixed seed so the same parameters always give the same buffer.
+pub fn render(params: &SynthParams, sample_rate: u32) -> Vec<f32> {
+    let count = params.sample_count(sample_rate);
+    let end_frequency = params.end_frequency.unwrap_or(params.frequency);
+
+    let mut samples = Vec::with_capacity(count);
+    let mut phase = 0.0_f32;
+    let mut noise = Xorshift(0x2545_f491);
+    let mut noise_level = noise.next_level();
+
+    for i in 0..count {
+        let progress = i as f32 / count as f32;
+        let frequency = params.frequency + (end_frequency - params.frequency) * progress;
+
+        let level = match params.waveform {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            // Held for a whole period, so the frequency still sets the pitch.
+            Waveform::Noise => noise_level,
+        };
+
+        let t = i as f32 / sample_rate as f32;
+        samples.push((level * params.envelope(t) * params.volume).clamp(-1.0, 1.0));
+
+        phase += frequency / sample_rate as f32;
+        if phase >= 1.0 {
+            phase = phase.fract();
+            noise_level = noise.next_level();
+        }
+    }
+
+    samples
+}
+
+// Renders the sound as a 16-bit mono WAV file that rodio can decode.
+pub fn render_wav(params: &SynthParams) -> Vec<u8> {
+    let samples: Vec<i16> = render(params, SAMPLE_RATE)
+        .iter()
+        .map(|sample| (sample * i16::MAX as f32) as i16)
+        .collect();
+
+    wav_bytes(&samples, SAMPLE_RATE)
+}
+
+struct Xorshift(u32);
+
+impl Xorshift {
+    fn next_level(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign_changes(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+            .count()
+    }
+
+    #[test]
+    fn length_follows_duration() {
+        let params = SynthParams {
+            duration: 0.5,
+            ..SynthParams::default()
+        };
+
+        assert_eq!(render(&params, 1000).len(), 500);
+    }
+
+    #[test]
+    fn square_wave_has_the_requested_frequency() {
+        let params = SynthParams {
+            frequency: 100.0,
+            duration: 1.0,
+            ..SynthParams::default()
+        };
+        let samples = render(&params, 8000);
+
+        // Two sign changes per period.
+        let changes = sign_changes(&samples) as i32;
+        assert!((changes - 200).abs() <= 2, "{} sign changes", changes);
+    }
+
+    #[test]
+    fn envelope_starts_and_ends_silent() {
+        for waveform in [Waveform::Square, Waveform::Triangle, Waveform::Noise] {
+            let params = SynthParams {
+                waveform,
+                ..SynthParams::default()
+            };
+            let samples = render(&params, SAMPLE_RATE);
+
+            assert_eq!(samples[0], 0.0);
+            assert!(samples.last().unwrap().abs() < 0.01);
+            assert!(samples.iter().all(|s| s.abs() <= params.volume));
+        }
+    }
+
+    #[test]
+    fn sustain_level_is_held() {
+        let params = SynthParams {
+            waveform: Waveform::Square,
+            duration: 1.0,
+            volume: 1.0,
+            attack: 0.0,
+            decay: 0.0,
+            sustain: 0.5,
+            release: 0.0,
+            ..SynthParams::default()
+        };
+        let samples = render(&params, 1000);
+
+        assert!(samples.iter().all(|s| s.abs() == 0.5));
+    }
+
+    #[test]
+    fn slide_lowers_the_pitch() {
+        let params = SynthParams {
+            frequency: 400.0,
+            end_frequency: Some(100.0),
+            duration: 1.0,
+            ..SynthParams::default()
+        };
+        let samples = render(&params, 8000);
+
+        let first_half = sign_changes(&samples[..4000]);
+        let second_half = sign_changes(&samples[4000..]);
+        assert!(first_half > second_half * 3 / 2);
+    }
+
+    #[test]
+    fn noise_is_deterministic() {
+        let params = SynthParams {
+            waveform: Waveform::Noise,
+            ..SynthParams::default()
+        };
+
+        assert_eq!(render(&params, SAMPLE_RATE), render(&params, SAMPLE_RATE));
+    }
+
+    #[test]
+    fn wav_output_is_a_valid_sound() {
+        let bytes = render_wav(&SynthParams::preset("goal"));
+
+        assert!(crate::manifest::validate(crate::manifest::AssetKind::Sound, &bytes).is_ok());
+    }
+
+    #[test]
+    fn sound_settings_round_trip_through_the_config() {
+        let config = crate::config::Config::parse(
+            r#"
+            [sounds.pad]
+            source = "synth"
+
+            [sounds.pad.synth]
+            waveform = "noise"
+            frequency = 200.0
+
+            [sounds.goal]
+            source = "synth"
+            "#,
+        )
+        .unwrap();
+
+        let pad = &config.sounds["pad"];
+        assert_eq!(pad.synth_params("pad").waveform, Waveform::Noise);
+        assert_eq!(pad.synth_params("pad").duration, 0.08);
+        assert_eq!(
+            config.sounds["goal"].synth_params("goal"),
+            SynthParams::preset("goal")
+        );
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(
+            crate::config::Config::parse(&saved).unwrap().sounds,
+            config.sounds
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::Read;
+
+use ggez::{
+    filesystem,
+    graphics::{self, Color},
+    Context,
+};
+use serde::{Deserialize, Deserializer};
+
+use crate::assets::Assets;
+
+pub const THEME_DIR: &str = "/themes";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaddleShape {
+    Solid,
+    Outline,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BallShape {
+    Circle,
+    Square,
+}
+
+// Images referenced by a theme, loaded once when the theme is loaded.
+#[derive(Clone, Default)]
+pub struct ThemeSprites {
+    pub background: Option<graphics::Image>,
+    pub paddle: Option<graphics::Image>,
+    pub ball: Option<graphics::Image>,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Theme {
+    pub name: String,
+    #[serde(deserialize_with = "rgba")]
+    pub background: Color,
+    #[serde(deserialize_with = "rgba")]
+    pub paddle: Color,
+    #[serde(deserialize_with = "rgba")]
+    pub ball: Color,
+    #[serde(deserialize_with = "rgba")]
+    pub text: Color,
+    #[serde(deserialize_with = "rgba")]
+    pub dim_text: Color,
+    #[serde(deserialize_with = "rgba")]
+    pub highlight: Color,
+    // Asset manifest ID of the UI font, `None` uses the ggez built-in font.
+    #[serde(default)]
+    pub font: Option<String>,
+    pub paddle_shape: PaddleShape,
+    pub ball_shape: BallShape,
+    #[serde(default)]
+    pub background_image: Option<String>,
+    #[serde(default)]
+    pub paddle_sprite: Option<String>,
+    #[serde(default)]
+    pub ball_sprite: Option<String>,
+    // Empty means the particles use their own colored sprites.
+    #[serde(default, deserialize_with = "rgba_list")]
+    pub particle_colors: Vec<Color>,
+    #[serde(skip)]
+    pub sprites: ThemeSprites,
+    // Resource path of the file it came from, `None` for built-in themes.
+    #[serde(skip)]
+    pub path: Option<String>,
+}
+
+fn rgba<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let [r, g, b, a] = <[u8; 4]>::deserialize(deserializer)?;
+    Ok(Color::from_rgba(r, g, b, a))
+}
+
+fn rgba_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Color>, D::Error> {
+    let colors = Vec::<[u8; 4]>::deserialize(deserializer)?;
+    Ok(colors
+        .into_iter()
+        .map(|[r, g, b, a]| Color::from_rgba(r, g, b, a))
+        .collect())
+}
+
+impl Theme {
+    pub fn classic() -> Theme {
+        Theme {
+            name: String::from("Classic"),
+            background: graphics::BLACK,
+            paddle: graphics::WHITE,
+            ball: graphics::WHITE,
+            text: graphics::WHITE,
+            dim_text: Color::from_rgba(255, 255, 255, 25),
+            highlight: Color::from_rgba(0, 51, 102, 255),
+            font: Some(String::from("ui")),
+            paddle_shape: PaddleShape::Solid,
+            ball_shape: BallShape::Circle,
+            background_image: None,
+            paddle_sprite: None,
+            ball_sprite: None,
+            particle_colors: Vec::new(),
+            sprites: ThemeSprites::default(),
+            path: None,
+        }
+    }
+
+    pub fn neon() -> Theme {
+        Theme {
+            name: String::from("Neon"),
+            background: Color::from_rgba(12, 0, 28, 255),
+            paddle: Color::from_rgba(0, 255, 230, 255),
+            ball: Color::from_rgba(255, 40, 200, 255),
+            text: Color::from_rgba(255, 40, 200, 255),
+            dim_text: Color::from_rgba(0, 255, 230, 60),
+            highlight: Color::from_rgba(255, 230, 0, 255),
+            font: Some(String::from("ui")),
+            paddle_shape: PaddleShape::Outline,
+            ball_shape: BallShape::Circle,
+            background_image: None,
+            paddle_sprite: None,
+            ball_sprite: None,
+            particle_colors: vec![
+                Color::from_rgba(0, 255, 230, 255),
+                Color::from_rgba(255, 40, 200, 255),
+                Color::from_rgba(255, 230, 0, 255),
+            ],
+            sprites: ThemeSprites::default(),
+            path: None,
+        }
+    }
+
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: String::from("High Contrast"),
+            background: graphics::BLACK,
+            paddle: graphics::WHITE,
+            ball: Color::from_rgba(255, 255, 0, 255),
+            text: graphics::WHITE,
+            dim_text: graphics::WHITE,
+            highlight: Color::from_rgba(255, 255, 0, 255),
+            font: Some(String::from("ui")),
+            paddle_shape: PaddleShape::Solid,
+            ball_shape: BallShape::Square,
+            background_image: None,
+            paddle_sprite: None,
+            ball_sprite: None,
+            particle_colors: vec![graphics::WHITE, Color::from_rgba(255, 255, 0, 255)],
+            sprites: ThemeSprites::default(),
+            path: None,
+        }
+    }
+
+    // Built-in themes followed by every valid `.toml` file in the theme directory.
+    pub fn load_all(ctx: &mut Context) -> Vec<Theme> {
+        let mut themes = vec![Theme::classic(), Theme::neon(), Theme::high_contrast()];
+
+        let mut paths: Vec<_> = match filesystem::read_dir(ctx, THEME_DIR) {
+            Ok(paths) => paths
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+
+        for theme in themes.iter_mut() {
+            theme.load_sprites(ctx);
+        }
+
+        for path in paths {
+            let path = path.to_string_lossy().replace('\\', "/");
+            match Theme::from_file(ctx, &path) {
+                Ok(theme) => themes.push(theme),
+                Err(e) => eprintln!("warning: skipping theme {}: {}", path, e),
+            }
+        }
+
+        themes
+    }
+
+    // Reads one theme file and loads its sprites.
+    pub fn from_file(ctx: &mut Context, path: &str) -> Result<Theme, String> {
+        let mut contents = String::new();
+        filesystem::open(ctx, path)
+            .and_then(|mut file| Ok(file.read_to_string(&mut contents)?))
+            .map_err(|e| e.to_string())?;
+
+        let mut theme = Theme::parse(&contents)?;
+        theme.path = Some(path.to_string());
+        theme.load_sprites(ctx);
+
+        Ok(theme)
+    }
+
+    pub fn parse(contents: &str) -> Result<Theme, String> {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    }
+
+    pub fn load_sprites(&mut self, ctx: &mut Context) {
+        self.sprites = ThemeSprites {
+            background: load_sprite(ctx, &self.background_image),
+            paddle: load_sprite(ctx, &self.paddle_sprite),
+            ball: load_sprite(ctx, &self.ball_sprite),
+        };
+    }
+
+    pub fn font(&self, assets: &Assets) -> graphics::Font {
+        match &self.font {
+            Some(id) => assets.font(id),
+            None => graphics::Font::default(),
+        }
+    }
+}
+
+fn load_sprite(ctx: &mut Context, path: &Option<String>) -> Option<graphics::Image> {
+    let path = path.as_ref()?;
+
+    match graphics::Image::new(ctx, path) {
+        Ok(image) => Some(image),
+        Err(e) => {
+            eprintln!("warning: could not load theme sprite {}: {}", path, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+// Short-lived status message shown in the debug overlay.
+pub struct Toast {
+    pub message: String,
+    pub is_error: bool,
+    pub remaining: Duration,
+}
+
+impl Toast {
+    pub fn info(message: String) -> Toast {
+        Toast {
+            message,
+            is_error: false,
+            remaining: TOAST_DURATION,
+        }
+    }
+
+    pub fn error(message: String) -> Toast {
+        Toast {
+            message,
+            is_error: true,
+            remaining: TOAST_DURATION,
+        }
+    }
+}
+
+pub fn tick_toasts(toasts: &mut Vec<Toast>, delta: Duration) {
+    for toast in toasts.iter_mut() {
+        toast.remaining = toast.remaining.checked_sub(delta).unwrap_or_default();
+    }
+
+    toasts.retain(|toast| toast.remaining > Duration::from_secs(0));
+}
@@ -0,0 +1,321 @@
+use std::fmt::Write;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ai::{Ai, Personality, PRESETS},
+    config::Tuning,
+    env::{Env, EnvConfig},
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entrant {
+    pub name: String,
+    #[serde(flatten)]
+    pub personality: Personality,
+}
+
+// A round robin between AI personalities, read from a TOML file by
+// `--tournament`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TournamentConfig {
+    // Matches each pair plays, taking turns on the left.
+    pub matches: usize,
+    pub seed: u64,
+    // Matches still going after this many steps of 1/60 s are draws.
+    pub max_steps: u32,
+    pub tuning: Tuning,
+    pub entrants: Vec<Entrant>,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> TournamentConfig {
+        TournamentConfig {
+            matches: 20,
+            seed: 0,
+            max_steps: 60 * 60 * 10,
+            // Angled bounces, so aiming matters, and a ball fast enough that
+            // good AIs miss sometimes and matches end.
+            tuning: Tuning {
+                bounce_angle: 45.0,
+                ball_speed: 800.0,
+                ..Tuning::default()
+            },
+            entrants: PRESETS
+                .iter()
+                .map(|(name, personality)| Entrant {
+                    name: name.to_string(),
+                    personality: *personality,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Standing {
+    pub name: String,
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+    pub points_for: usize,
+    pub points_against: usize,
+}
+
+impl Standing {
+    pub fn played(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    // Draws count as half a win.
+    pub fn win_rate(&self) -> f32 {
+        match self.played() {
+            0 => 0.0,
+            played => (self.wins as f32 + self.draws as f32 / 2.0) / played as f32,
+        }
+    }
+}
+
+pub struct TournamentResult {
+    // Best win rate first.
+    pub standings: Vec<Standing>,
+    // `wins[i][j]` is how often entrant `i` beat entrant `j`.
+    pub wins: Vec<Vec<usize>>,
+    pub names: Vec<String>,
+}
+
+// Plays a match between two AIs without a window and returns the scores,
+// left then right.
+pub fn play_match(
+    left: Personality,
+    right: Personality,
+    config: &TournamentConfig,
+    seed: u64,
+) -> [usize; 2] {
+    let mut env = Env::new(EnvConfig {
+        tuning: config.tuning,
+        max_steps: config.max_steps,
+        ..EnvConfig::default()
+    });
+    let dt = env.config.dt;
+    env.reset(seed);
+
+    let mut ais = [
+        Ai::new(left, 0, seed.wrapping_add(1)),
+        Ai::new(right, 1, seed.wrapping_add(2)),
+    ];
+
+    loop {
+        let actions = [ais[0].act(&env.arena, dt), ais[1].act(&env.arena, dt)];
+        if env.step(actions).2 {
+            break;
+        }
+    }
+
+    [env.arena.player1_score, env.arena.player2_score]
+}
+
+// Every entrant plays every other one `config.matches` times. Matches run in
+// parallel, and the same config always gives the same result.
+pub fn run_tournament(config: &TournamentConfig) -> TournamentResult {
+    let count = config.entrants.len();
+
+    let mut fixtures = Vec::new();
+    for i in 0..count {
+        for j in i + 1..count {
+            for game in 0..config.matches {
+                let (left, right) = if game % 2 == 0 { (i, j) } else { (j, i) };
+                fixtures.push((left, right));
+            }
+        }
+    }
+
+    let scores: Vec<[usize; 2]> = fixtures
+        .par_iter()
+        .enumerate()
+        .map(|(index, &(left, right))| {
+            let seed = config
+                .seed
+                .wrapping_add(index as u64)
+                .wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            play_match(
+                config.entrants[left].personality,
+                config.entrants[right].personality,
+                config,
+                seed,
+            )
+        })
+        .collect();
+
+    let mut standings: Vec<Standing> = config
+        .entrants
+        .iter()
+        .map(|entrant| Standing {
+            name: entrant.name.clone(),
+            ..Standing::default()
+        })
+        .collect();
+    let mut wins = vec![vec![0; count]; count];
+
+    for (&(left, right), score) in fixtures.iter().zip(scores.iter()) {
+        for (side, &entrant) in [left, right].iter().enumerate() {
+            standings[entrant].points_for += score[side];
+            standings[entrant].points_against += score[1 - side];
+        }
+
+        let winner = config.tuning.winning_score;
+        let result = match (score[0] >= winner, score[1] >= winner) {
+            (true, _) => Some((left, right)),
+            (_, true) => Some((right, left)),
+            _ => None,
+        };
+
+        match result {
+            Some((winner, loser)) => {
+                standings[winner].wins += 1;
+                standings[loser].losses += 1;
+                wins[winner][loser] += 1;
+            }
+            None => {
+                standings[left].draws += 1;
+                standings[right].draws += 1;
+            }
+        }
+    }
+
+    let names = standings.iter().map(|s| s.name.clone()).collect();
+    standings.sort_by(|a, b| b.win_rate().total_cmp(&a.win_rate()));
+
+    TournamentResult {
+        standings,
+        wins,
+        names,
+    }
+}
+
+// The standings, then how often each row beat each column.
+pub fn report(result: &TournamentResult) -> String {
+    let width = result
+        .names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+    let mut text = String::new();
+
+    let _ = writeln!(
+        text,
+        "{:<width$}  {:>4} {:>4} {:>4} {:>6} {:>9}",
+        "",
+        "won",
+        "lost",
+        "draw",
+        "rate",
+        "points",
+        width = width
+    );
+    for standing in result.standings.iter() {
+        let _ = writeln!(
+            text,
+            "{:<width$}  {:>4} {:>4} {:>4} {:>5.1}% {:>4}:{:<4}",
+            standing.name,
+            standing.wins,
+            standing.losses,
+            standing.draws,
+            standing.win_rate() * 100.0,
+            standing.points_for,
+            standing.points_against,
+            width = width
+        );
+    }
+
+    let _ = writeln!(text);
+    let _ = write!(text, "{:<width$} ", "wins vs", width = width);
+    for name in result.names.iter() {
+        let _ = write!(text, " {:>width$}", name, width = width);
+    }
+    let _ = writeln!(text);
+    for (i, row) in result.wins.iter().enumerate() {
+        let _ = write!(text, "{:<width$} ", result.names[i], width = width);
+        for (j, wins) in row.iter().enumerate() {
+            match i == j {
+                true => {
+                    let _ = write!(text, " {:>width$}", "-", width = width);
+                }
+                false => {
+                    let _ = write!(text, " {:>width$}", wins, width = width);
+                }
+            }
+        }
+        let _ = writeln!(text);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ai::preset;
+
+    fn config(names: &[&str]) -> TournamentConfig {
+        let mut config = TournamentConfig {
+            matches: 4,
+            entrants: names
+                .iter()
+                .map(|name| Entrant {
+                    name: name.to_string(),
+                    personality: preset(name).unwrap(),
+                })
+                .collect(),
+            ..TournamentConfig::default()
+        };
+        config.tuning.winning_score = 3;
+        config
+    }
+
+    #[test]
+    fn better_ais_win_more() {
+        let result = run_tournament(&config(&["rookie", "wall"]));
+
+        assert_eq!(result.standings[0].name, "wall");
+        assert_eq!(result.standings[0].played(), 4);
+        assert!(result.wins[1][0] > result.wins[0][1]);
+    }
+
+    #[test]
+    fn tournaments_are_repeatable() {
+        let config = config(&["rookie", "steady", "sniper"]);
+        let first = report(&run_tournament(&config));
+
+        assert_eq!(first, report(&run_tournament(&config)));
+        assert_eq!(first.lines().count(), 4 + 1 + 4);
+    }
+
+    #[test]
+    fn configs_are_read_from_toml() {
+        let config: TournamentConfig = toml::from_str(
+            r#"
+            matches = 2
+
+            [[entrants]]
+            name = "slow"
+            reaction_time = 0.5
+
+            [[entrants]]
+            name = "fast"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.matches, 2);
+        assert_eq!(config.entrants[0].personality.reaction_time, 0.5);
+        assert_eq!(config.entrants[1].personality, Personality::default());
+        assert_eq!(config.tuning.bounce_angle, 45.0);
+        assert_eq!(config.tuning.ball_speed, 800.0);
+    }
+}